@@ -21,7 +21,7 @@ impl<T> Lock<T> {
 }
 
 impl<T> Lock<T> {
-	pub fn lock(&self) -> LockGuard<T> {
+	pub fn lock(&self) -> LockGuard<'_, T> {
 		LockGuard::new(&self.inner)
 	}
 
@@ -1,17 +1,111 @@
-use std::future::Future;
+use std::{cell::RefCell, future::Future, pin::Pin, rc::Rc};
 use tracing::Instrument;
 
 // It's not pretty, but we have per-platform implementations of spawn.
 // The main problem is Send; it's an annoying trait that colors everything.
 // The rest of this crate is Send agnostic so it will work on WASM.
-// TODO: use a send feature and make this runtime agnostic?
+
+/// A boxed task passed to a [Spawn] implementation.
+#[cfg(any(not(target_arch = "wasm32"), target_os = "wasi"))]
+pub type Task = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// A boxed task passed to a [Spawn] implementation.
+#[cfg(all(target_arch = "wasm32", not(target_os = "wasi")))]
+pub type Task = Pin<Box<dyn Future<Output = ()> + 'static>>;
+
+/// An executor that can run background tasks, used instead of the default runtime.
+///
+/// By default, [spawn] uses tokio on native platforms and `wasm_bindgen_futures` on the web.
+/// Call [set_spawner] to run tasks on any other executor.
+pub trait Spawn {
+	fn spawn(&self, task: Task);
+}
+
+impl Spawn for futures::executor::LocalSpawner {
+	fn spawn(&self, task: Task) {
+		use futures::task::LocalSpawnExt;
+
+		// Only fails if the executor has shut down, in which case the task would never run anyway.
+		if let Err(err) = self.spawn_local(task) {
+			tracing::warn!(?err, "failed to spawn task");
+		}
+	}
+}
+
+thread_local! {
+	static SPAWNER: RefCell<Option<Rc<dyn Spawn>>> = const { RefCell::new(None) };
+}
+
+/// Use the provided executor for any tasks spawned on the current thread, returning the previous one.
+///
+/// This is per-thread so single-threaded executors don't need to be [Send].
+/// A multi-threaded executor should call this on each of its worker threads.
+pub fn set_spawner<S: Spawn + 'static>(spawner: S) -> Option<Rc<dyn Spawn>> {
+	SPAWNER.with(|current| current.borrow_mut().replace(Rc::new(spawner)))
+}
+
+/// Revert to the default runtime for the current thread, returning the previous executor.
+pub fn reset_spawner() -> Option<Rc<dyn Spawn>> {
+	SPAWNER.with(|current| current.borrow_mut().take())
+}
+
+// Returns the executor for the current thread, if any.
+fn spawner() -> Option<Rc<dyn Spawn>> {
+	SPAWNER.with(|current| current.borrow().clone())
+}
 
 #[cfg(any(not(target_arch = "wasm32"), target_os = "wasi"))]
 pub fn spawn<F: Future<Output = ()> + Send + 'static>(f: F) {
-	tokio::task::spawn(f.in_current_span());
+	let f = f.in_current_span();
+
+	match spawner() {
+		Some(spawner) => spawner.spawn(Box::pin(f)),
+		None => {
+			tokio::task::spawn(f);
+		}
+	}
 }
 
 #[cfg(all(target_arch = "wasm32", not(target_os = "wasi")))]
 pub fn spawn<F: Future<Output = ()> + 'static>(f: F) {
-	wasm_bindgen_futures::spawn_local(f.in_current_span());
+	let f = f.in_current_span();
+
+	match spawner() {
+		Some(spawner) => spawner.spawn(Box::pin(f)),
+		None => wasm_bindgen_futures::spawn_local(f),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use futures::executor::LocalPool;
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+
+	#[test]
+	fn local() {
+		let mut pool = LocalPool::new();
+		set_spawner(pool.spawner());
+
+		let count = Arc::new(AtomicUsize::new(0));
+
+		// Spawned tasks can spawn further tasks on the same executor, without a tokio runtime.
+		let inner = count.clone();
+		spawn(async move {
+			inner.fetch_add(1, Ordering::Relaxed);
+
+			let inner = inner.clone();
+			spawn(async move {
+				inner.fetch_add(1, Ordering::Relaxed);
+			});
+		});
+
+		pool.run_until_stalled();
+		assert_eq!(count.load(Ordering::Relaxed), 2);
+
+		assert!(reset_spawner().is_some());
+	}
 }
@@ -22,6 +22,10 @@ pub struct Config {
 	#[command(flatten)]
 	pub tls: moq_native::tls::Args,
 
+	/// The qlog configuration.
+	#[command(flatten)]
+	pub qlog: moq_native::qlog::Args,
+
 	/// The path of the clock track.
 	#[arg(long, default_value = "clock")]
 	pub path: String,
@@ -48,12 +52,33 @@ async fn main() -> anyhow::Result<()> {
 
 	let tls = config.tls.load()?;
 
-	let quic = quic::Endpoint::new(quic::Config { bind: config.bind, tls })?;
+	let quic = quic::Endpoint::new(quic::Config {
+		bind: config.bind,
+		tls,
+		qlog: config.qlog.load()?,
+	})?;
 
 	tracing::info!(url = ?config.url, "connecting to server");
 
+	// Speak the IETF draft when connecting to a moq-transport implementation.
+	let version = match config.url.scheme() {
+		"moqt" => moq_transfork::ietf::VERSION,
+		_ => moq_transfork::proto::message::Version::CURRENT,
+	};
+
+	let token = quic::token(&config.url);
 	let session = quic.client.connect(config.url).await?;
-	let mut session = moq_transfork::Session::connect(session).await?;
+	let qlog = quic.client.qlog(&session);
+
+	let mut session = match version {
+		moq_transfork::ietf::VERSION => {
+			moq_transfork::Session::connect_version(session, version, Default::default()).await?
+		}
+		_ => moq_transfork::Session::connect_token(session, token.as_deref()).await?,
+	};
+	if let Some(qlog) = qlog {
+		session.observe(qlog);
+	}
 
 	let track = Track::new(config.path);
 
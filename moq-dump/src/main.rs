@@ -0,0 +1,302 @@
+use std::{fmt, fs, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use bytes::{Buf, Bytes};
+use clap::{Parser, Subcommand, ValueEnum};
+
+use moq_transfork::{
+	capture,
+	proto::{coding::*, ietf, message},
+};
+
+#[derive(Parser, Clone)]
+pub struct Config {
+	#[command(subcommand)]
+	pub command: Command,
+}
+
+#[derive(Subcommand, Clone)]
+pub enum Command {
+	/// Print a capture file written by `moq_transfork::capture`.
+	Capture {
+		/// The path of the capture file.
+		path: PathBuf,
+	},
+
+	/// Print a raw byte dump of one half of a single stream.
+	Stream {
+		/// The path of the dump.
+		path: PathBuf,
+
+		/// Which stream, and which half of it, was dumped.
+		#[arg(long, value_enum, default_value_t = Kind::Control)]
+		kind: Kind,
+
+		/// The subscriber sent a trace context after each subscribe.
+		#[arg(long)]
+		trace: bool,
+
+		/// The session used FORK_04, so announce requests don't include a cursor.
+		#[arg(long)]
+		fork04: bool,
+	},
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Kind {
+	/// Sent by the opener of a control stream, starting with the control type.
+	Control,
+
+	/// A unidirectional data stream, starting with the data type.
+	Data,
+
+	/// The response half of a session stream.
+	Session,
+
+	/// The response half of an announce stream.
+	Announce,
+
+	/// The response half of a subscribe stream.
+	Subscribe,
+
+	/// The response half of an info stream.
+	Info,
+
+	/// The IETF control stream, in either direction.
+	IetfControl,
+
+	/// An IETF unidirectional data stream.
+	IetfData,
+}
+
+fn main() -> anyhow::Result<()> {
+	let config = Config::parse();
+
+	match config.command {
+		Command::Capture { path } => {
+			let data = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+			print_capture(data.into())
+		}
+		Command::Stream {
+			path,
+			kind,
+			trace,
+			fork04,
+		} => {
+			let data = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+			print_stream(&mut Dump::new(data.into()), kind, trace, fork04)
+		}
+	}
+}
+
+fn print_capture(mut buf: Bytes) -> anyhow::Result<()> {
+	if !buf.starts_with(capture::MAGIC) {
+		anyhow::bail!("not a capture file");
+	}
+	buf.advance(capture::MAGIC.len());
+
+	let mut start = None;
+
+	while buf.has_remaining() {
+		let record = capture::Record::decode(&mut buf).context("truncated capture")?;
+
+		// Print timestamps relative to the first record.
+		let start = *start.get_or_insert(record.timestamp);
+		let elapsed = record.timestamp.saturating_sub(start);
+
+		let direction = match record.direction {
+			capture::Direction::Recv => "<-",
+			capture::Direction::Send => "->",
+		};
+
+		let msg = describe(&record.name, record.payload.clone())
+			.unwrap_or_else(|err| format!("{} (failed to decode: {})", hex(&record.payload), err));
+
+		println!(
+			"{:>12} stream={:<4} {} {}: {}",
+			format_elapsed(elapsed),
+			record.stream,
+			direction,
+			record.name,
+			msg
+		);
+	}
+
+	Ok(())
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+	format!("+{}.{:06}", elapsed.as_secs(), elapsed.subsec_micros())
+}
+
+fn hex(data: &[u8]) -> String {
+	data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Decode a captured message based on its recorded type name.
+macro_rules! describe {
+	($($name:literal => $type:ty,)*) => {
+		fn describe(name: &str, mut payload: Bytes) -> anyhow::Result<String> {
+			let msg = match name {
+				$($name => format!("{:?}", <$type>::decode(&mut payload)?),)*
+				_ => anyhow::bail!("unknown message type"),
+			};
+
+			if payload.has_remaining() {
+				anyhow::bail!("{} trailing bytes", payload.remaining());
+			}
+
+			Ok(msg)
+		}
+	};
+}
+
+describe! {
+	"u64" => u64,
+	"ControlType" => message::ControlType,
+	"DataType" => message::DataType,
+	"ClientSetup" => message::ClientSetup,
+	"ServerSetup" => message::ServerSetup,
+	"SessionInfo" => message::SessionInfo,
+	"AnnouncePlease" => message::AnnouncePlease,
+	"Filter" => message::Filter,
+	"Announce" => message::Announce,
+	"Subscribe" => message::Subscribe,
+	"SubscribeUpdate" => message::SubscribeUpdate,
+	"TraceContext" => message::TraceContext,
+	"Info" => message::Info,
+	"InfoRequest" => message::InfoRequest,
+	"Group" => message::Group,
+	"GroupDrop" => message::GroupDrop,
+	"Frame" => message::Frame,
+	"ietf::Message" => ietf::Message,
+	"ietf::ClientSetup" => ietf::ClientSetup,
+	"ietf::ServerSetup" => ietf::ServerSetup,
+	"ietf::StreamHeader" => ietf::StreamHeader,
+	"ietf::GroupObject" => ietf::GroupObject,
+	"ietf::TrackObject" => ietf::TrackObject,
+}
+
+/// Walks a raw stream, printing each message along with its offset.
+struct Dump {
+	buf: Bytes,
+	size: usize,
+}
+
+impl Dump {
+	fn new(buf: Bytes) -> Self {
+		let size = buf.len();
+		Self { buf, size }
+	}
+
+	fn offset(&self) -> usize {
+		self.size - self.buf.len()
+	}
+
+	fn is_empty(&self) -> bool {
+		self.buf.is_empty()
+	}
+
+	fn next<T: Decode + fmt::Debug>(&mut self) -> anyhow::Result<T> {
+		let offset = self.offset();
+		let msg = T::decode(&mut self.buf)
+			.with_context(|| format!("failed to decode {} at offset {}", std::any::type_name::<T>(), offset))?;
+
+		println!("{:>8}: {:?}", offset, msg);
+
+		Ok(msg)
+	}
+
+	// Decode messages until the end of the stream.
+	fn rest<T: Decode + fmt::Debug>(&mut self) -> anyhow::Result<()> {
+		while !self.is_empty() {
+			self.next::<T>()?;
+		}
+
+		Ok(())
+	}
+
+	// Skip over a payload without printing it.
+	fn skip(&mut self, size: usize) -> anyhow::Result<()> {
+		let offset = self.offset();
+		if self.buf.remaining() < size {
+			anyhow::bail!("truncated payload at offset {}: expected {} bytes", offset, size);
+		}
+
+		self.buf.advance(size);
+		println!("{:>8}: <{} byte payload>", offset, size);
+
+		Ok(())
+	}
+}
+
+fn print_stream(dump: &mut Dump, kind: Kind, trace: bool, fork04: bool) -> anyhow::Result<()> {
+	match kind {
+		Kind::Control => match dump.next::<message::ControlType>()? {
+			message::ControlType::Session => {
+				dump.next::<message::ClientSetup>()?;
+				dump.rest::<message::SessionInfo>()?;
+			}
+			message::ControlType::Announce => match fork04 {
+				true => {
+					dump.next::<message::Filter>()?;
+				}
+				false => {
+					dump.next::<message::AnnouncePlease>()?;
+				}
+			},
+			message::ControlType::Subscribe => {
+				dump.next::<message::Subscribe>()?;
+				if trace {
+					dump.next::<message::TraceContext>()?;
+				}
+				dump.rest::<message::SubscribeUpdate>()?;
+			}
+			message::ControlType::Info => {
+				dump.next::<message::InfoRequest>()?;
+			}
+		},
+		Kind::Data => match dump.next::<message::DataType>()? {
+			message::DataType::Group => {
+				dump.next::<message::Group>()?;
+				while !dump.is_empty() {
+					let frame = dump.next::<message::Frame>()?;
+					dump.skip(frame.size)?;
+				}
+			}
+		},
+		Kind::Session => {
+			dump.next::<message::ServerSetup>()?;
+			dump.rest::<message::SessionInfo>()?;
+		}
+		Kind::Announce => dump.rest::<message::Announce>()?,
+		Kind::Subscribe => {
+			dump.next::<message::Info>()?;
+			dump.rest::<message::GroupDrop>()?;
+		}
+		Kind::Info => {
+			dump.next::<message::Info>()?;
+		}
+		Kind::IetfControl => dump.rest::<ietf::Message>()?,
+		Kind::IetfData => match dump.next::<ietf::StreamHeader>()? {
+			ietf::StreamHeader::Group(_) => {
+				while !dump.is_empty() {
+					let object = dump.next::<ietf::GroupObject>()?;
+					dump.skip(object.size)?;
+				}
+			}
+			ietf::StreamHeader::Track(_) => {
+				while !dump.is_empty() {
+					let object = dump.next::<ietf::TrackObject>()?;
+					dump.skip(object.size)?;
+				}
+			}
+		},
+	}
+
+	if !dump.is_empty() {
+		anyhow::bail!("unexpected data at offset {}", dump.offset());
+	}
+
+	Ok(())
+}
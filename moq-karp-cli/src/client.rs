@@ -48,12 +48,19 @@ impl<T: AsyncRead + Unpin> BroadcastClient<T> {
 		let quic = quic::Endpoint::new(quic::Config {
 			bind: self.config.bind,
 			tls,
+			qlog: self.config.qlog.load()?,
 		})?;
 
 		tracing::info!(?url, "connecting");
 
+		let token = quic::token(&url);
 		let session = quic.client.connect(url).await?;
-		let session = Session::connect(session).await?;
+		let qlog = quic.client.qlog(&session);
+
+		let session = Session::connect_token(session, token.as_deref()).await?;
+		if let Some(qlog) = qlog {
+			session.observe(qlog);
+		}
 
 		Ok(session)
 	}
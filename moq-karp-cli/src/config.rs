@@ -15,6 +15,10 @@ pub struct Config {
 	#[command(flatten)]
 	pub tls: moq_native::tls::Args,
 
+	/// The qlog configuration.
+	#[command(flatten)]
+	pub qlog: moq_native::qlog::Args,
+
 	/// If we're publishing or subscribing.
 	#[command(subcommand)]
 	pub command: Command,
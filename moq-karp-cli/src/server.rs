@@ -35,6 +35,7 @@ impl<T: AsyncRead + Unpin> BroadcastServer<T> {
 		let quic = quic::Endpoint::new(quic::Config {
 			bind: self.config.bind,
 			tls: tls.clone(),
+			qlog: self.config.qlog.load()?,
 		})?;
 		let server = quic.server.context("missing TLS certificate")?;
 
@@ -67,11 +68,16 @@ impl<T: AsyncRead + Unpin> BroadcastServer<T> {
 		tokio::spawn(async move {
 			while let Some(conn) = server.accept().await {
 				// Create a new connection
+				let qlog = server.qlog(&conn);
 				let session: web_transport::Session = conn.into();
 				let transfork_session = moq_transfork::Session::accept(session)
 					.await
 					.expect("failed to accept session");
 
+				if let Some(qlog) = qlog {
+					transfork_session.observe(qlog);
+				}
+
 				conn_id += 1;
 				broadcast.add_session(transfork_session).expect("failed to add session");
 
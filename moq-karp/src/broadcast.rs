@@ -6,15 +6,14 @@ use moq_transfork::{Announced, AnnouncedConsumer, AnnouncedMatch, Session};
 
 struct BroadcastProducerState {
 	catalog: CatalogProducer,
-	tracks: Vec<moq_transfork::TrackProducer>,
-	subscribers: Vec<Session>,
 }
 
 #[derive(Debug, Clone)]
 #[debug("{:?}", path)]
 pub struct BroadcastProducer {
 	pub path: String,
-	id: u64,
+	#[debug(skip)]
+	inner: moq_transfork::BroadcastProducer,
 	state: Lock<BroadcastProducerState>,
 }
 
@@ -27,64 +26,34 @@ impl BroadcastProducer {
 			.unwrap()
 			.as_millis() as u64;
 
+		let (mut inner, _) = moq_transfork::Broadcast::new(format!("{}/{}", path, id)).produce();
+
 		// Create the catalog track
-		let full = format!("{}/{}/catalog.json", path.clone(), id.clone());
-		let (catalog, _) = moq_transfork::Track {
-			path: full,
+		let catalog = inner.create_track(moq_transfork::Track {
+			path: "catalog.json".to_string(),
 			priority: -1,
 			order: moq_transfork::GroupOrder::Desc,
-		}
-		.produce();
+		});
 		let catalog = CatalogProducer::new(catalog)?;
 
 		// Create the BroadcastProducerState
-		let state = Lock::new(BroadcastProducerState {
-			catalog,
-			tracks: vec![],
-			subscribers: vec![],
-		});
+		let state = Lock::new(BroadcastProducerState { catalog });
 
-		Ok(Self { path, id, state })
+		Ok(Self { path, inner, state })
 	}
 
 	/// Add a session to the broadcast.
-	/// If the session closes, it will be removed from the broadcast automatically.
+	/// Any tracks published later are served to the session automatically, until it closes.
 	pub fn add_session(&mut self, mut session: Session) -> Result<()> {
-		let mut state = self.state.lock();
-
-		// Publish the catalog
-		session.publish(state.catalog.track.subscribe())?;
-
-		// Publish all tracks
-		for track in &state.tracks {
-			tracing::info!("publishing track, {:?}", track.path);
-			session.publish(track.subscribe())?;
-		}
-
-		// Add the session to the list of subscribers
-		state.subscribers.push(session.clone());
-
-		// If the session closes, remove it from the list of subscribers
-		let state = self.state.clone();
-		moq_async::spawn(async move {
-			session.closed().await;
-			state.lock().subscribers.retain(|s| s != &session);
-		});
-
+		session.publish_broadcast(self.inner.consume())?;
 		Ok(())
 	}
 
-	/// Remove a session from the broadcast.
-	pub fn remove_session(&mut self, session: &Session) {
-		let mut state = self.state.lock();
-		state.subscribers.retain(|s| s != session);
-	}
-
 	/// Publish a video track to all listeners & future listeners.
 	pub fn publish_video(&mut self, info: Video) -> Result<TrackProducer> {
-		let mut state = self.state.lock();
+		let producer = self.publish(info.track.clone());
 
-		let producer = self.publish(info.track.clone(), &mut state)?;
+		let mut state = self.state.lock();
 		state.catalog.current.video.push(info);
 		state.catalog.publish()?;
 
@@ -93,37 +62,24 @@ impl BroadcastProducer {
 
 	/// Publish an audio track to all listeners & future listeners.
 	pub fn publish_audio(&mut self, info: Audio) -> Result<TrackProducer> {
-		let mut state = self.state.lock();
+		let producer = self.publish(info.track.clone());
 
-		let producer = self.publish(info.track.clone(), &mut state)?;
+		let mut state = self.state.lock();
 		state.catalog.current.audio.push(info);
 		state.catalog.publish()?;
 
 		Ok(producer)
 	}
 
-	fn publish(&self, track: Track, state: &mut BroadcastProducerState) -> Result<TrackProducer> {
-		// Create the TrackProducer
-		let path = format!("{}/{}/{}.karp", self.path, self.id, &track.name);
-		let (producer, _) = moq_transfork::Track {
-			path,
+	fn publish(&mut self, track: Track) -> TrackProducer {
+		let producer = self.inner.create_track(moq_transfork::Track {
+			path: format!("{}.karp", &track.name),
 			priority: track.priority,
 			// TODO add these to the catalog and support higher latencies.
 			order: moq_transfork::GroupOrder::Desc,
-		}
-		.produce();
-
-		// Let all the listeners know about the new video track
-		state.subscribers.iter_mut().for_each(|session| {
-			session.publish(producer.subscribe()).unwrap();
 		});
 
-		// Update the state
-		state.tracks.push(producer.clone());
-
-		// Return the producer
-		let producer = TrackProducer::new(producer);
-		Ok(producer)
+		TrackProducer::new(producer)
 	}
 }
 
@@ -283,16 +239,18 @@ impl BroadcastConsumer {
 	pub fn track(&self, track: &Track) -> Result<TrackConsumer> {
 		let id = self.current.as_ref().ok_or(Error::MissingTrack)?;
 
+		let broadcast = moq_transfork::Broadcast::new(format!("{}/{}", self.path, id));
+
 		let track = moq_transfork::Track {
 			// TODO use the catalog to find the path
-			path: format!("{}/{}/{}.karp", self.path, id, &track.name),
+			path: format!("{}.karp", &track.name),
 			priority: track.priority,
 
 			// TODO add these to the catalog and support higher latencies.
 			order: moq_transfork::GroupOrder::Desc,
 		};
 
-		let track = self.session.subscribe(track);
+		let track = self.session.broadcast(broadcast).subscribe(track)?;
 		Ok(TrackConsumer::new(track))
 	}
 }
@@ -1,4 +1,5 @@
 pub mod log;
+pub mod qlog;
 pub mod quic;
 pub mod tls;
 
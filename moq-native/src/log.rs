@@ -1,8 +1,10 @@
 use clap::Parser;
+use serde::Deserialize;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::EnvFilter;
 
-#[derive(Parser, Clone, Default)]
+#[derive(Parser, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Args {
 	#[arg(long, short, action = clap::ArgAction::Count)]
 	pub verbose: u8,
@@ -39,6 +41,13 @@ impl Args {
 			.with_env_filter(filter)
 			.finish();
 
+		// Forward trace context between sessions, even if we're not exporting spans.
+		#[cfg(feature = "otel")]
+		let logger = {
+			use tracing_subscriber::layer::SubscriberExt;
+			logger.with(tracing_opentelemetry::layer())
+		};
+
 		tracing::subscriber::set_global_default(logger).unwrap();
 	}
 }
@@ -0,0 +1,268 @@
+//! Write a [qlog](https://datatracker.ietf.org/doc/draft-ietf-quic-qlog-main-schema/) file for each connection.
+//!
+//! Each file uses the JSON-SEQ format and can be loaded into [qvis](https://qvis.quictools.info/).
+//! QUIC events are limited to what quinn exposes, so congestion metrics are sampled periodically rather than per packet.
+//! MoQ events use the `moq` category, reported via [moq_transfork::Session::observe].
+use std::{
+	collections::HashMap,
+	fs,
+	io::{self, Write},
+	path::PathBuf,
+	sync::{Arc, Mutex},
+	time,
+};
+
+use anyhow::Context;
+use clap::Parser;
+use moq_transfork::{Event, StreamKind};
+use serde::Deserialize;
+use serde_json::json;
+
+// How often to sample the connection stats.
+const SAMPLE_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+#[derive(Parser, Clone, Default, Deserialize)]
+#[group(id = "qlog")]
+#[serde(default, deny_unknown_fields)]
+pub struct Args {
+	/// Write a qlog file for each connection to this directory.
+	#[arg(long = "qlog-dir")]
+	pub dir: Option<PathBuf>,
+}
+
+impl Args {
+	pub fn load(&self) -> anyhow::Result<Option<Dir>> {
+		let Some(path) = &self.dir else {
+			return Ok(None);
+		};
+
+		fs::create_dir_all(path).with_context(|| format!("failed to create qlog dir: {}", path.display()))?;
+
+		Ok(Some(Dir {
+			path: path.clone(),
+			active: Default::default(),
+		}))
+	}
+}
+
+/// Which side of the connection we are.
+#[derive(Clone, Copy, Debug)]
+pub enum Vantage {
+	Client,
+	Server,
+}
+
+impl Vantage {
+	fn as_str(&self) -> &'static str {
+		match self {
+			Self::Client => "client",
+			Self::Server => "server",
+		}
+	}
+}
+
+/// A directory containing a qlog file per connection.
+#[derive(Clone)]
+pub struct Dir {
+	path: PathBuf,
+
+	// The logs for open connections, indexed by their stable ID.
+	active: Arc<Mutex<HashMap<usize, Arc<Qlog>>>>,
+}
+
+impl Dir {
+	/// Start logging a new connection, until it's closed.
+	pub(crate) fn start(&self, conn: &quinn::Connection, vantage: Vantage) -> anyhow::Result<Arc<Qlog>> {
+		let id = conn.stable_id();
+
+		let now = time::SystemTime::now()
+			.duration_since(time::UNIX_EPOCH)
+			.unwrap_or_default();
+
+		let path = self
+			.path
+			.join(format!("{}-{}-{}.sqlog", now.as_millis(), id, vantage.as_str()));
+		let file = fs::File::create(&path).with_context(|| format!("failed to create qlog: {}", path.display()))?;
+
+		let log = Arc::new(Qlog {
+			file: Mutex::new(file),
+			start: time::Instant::now(),
+		});
+
+		log.header(now, id, vantage)?;
+		log.write(
+			"connectivity:connection_started",
+			json!({
+				"dst_ip": conn.remote_address().ip().to_string(),
+				"dst_port": conn.remote_address().port(),
+			}),
+		);
+
+		self.active.lock().unwrap().insert(id, log.clone());
+
+		tokio::spawn(Self::run(self.active.clone(), conn.clone(), log.clone()));
+
+		Ok(log)
+	}
+
+	/// Returns the log for an open connection, if any.
+	pub fn get(&self, conn: &quinn::Connection) -> Option<Arc<Qlog>> {
+		self.active.lock().unwrap().get(&conn.stable_id()).cloned()
+	}
+
+	async fn run(active: Arc<Mutex<HashMap<usize, Arc<Qlog>>>>, conn: quinn::Connection, log: Arc<Qlog>) {
+		let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+		let mut prev = None;
+
+		let err = loop {
+			tokio::select! {
+				_ = interval.tick() => {
+					let stats = conn.stats().path;
+					log.sample(&stats, prev.as_ref());
+					prev = Some(stats);
+				},
+				err = conn.closed() => break err,
+			}
+		};
+
+		log.write(
+			"connectivity:connection_closed",
+			json!({
+				"reason": err.to_string(),
+			}),
+		);
+
+		active.lock().unwrap().remove(&conn.stable_id());
+	}
+}
+
+/// The qlog for a single connection.
+pub struct Qlog {
+	file: Mutex<fs::File>,
+	start: time::Instant,
+}
+
+impl Qlog {
+	fn header(&self, now: time::Duration, id: usize, vantage: Vantage) -> io::Result<()> {
+		let header = json!({
+			"qlog_version": "0.3",
+			"qlog_format": "JSON-SEQ",
+			"title": "moq-native",
+			"trace": {
+				"vantage_point": { "type": vantage.as_str() },
+				"common_fields": {
+					"group_id": id.to_string(),
+					"reference_time": now.as_secs_f64() * 1000.0,
+					"time_format": "relative",
+				},
+			},
+		});
+
+		self.append(&header)
+	}
+
+	fn write(&self, name: &str, data: serde_json::Value) {
+		let event = json!({
+			"time": self.start.elapsed().as_secs_f64() * 1000.0,
+			"name": name,
+			"data": data,
+		});
+
+		if let Err(err) = self.append(&event) {
+			tracing::warn!(?err, "failed to write qlog");
+		}
+	}
+
+	// Each record is prefixed by a record separator (RFC 7464), written in one call so they don't interleave.
+	fn append(&self, record: &serde_json::Value) -> io::Result<()> {
+		let mut buf = vec![0x1e];
+		serde_json::to_writer(&mut buf, record)?;
+		buf.push(b'\n');
+
+		self.file.lock().unwrap().write_all(&buf)
+	}
+
+	// Log any metrics that changed since the previous sample.
+	fn sample(&self, stats: &quinn::PathStats, prev: Option<&quinn::PathStats>) {
+		let changed = prev.is_none_or(|prev| prev.rtt != stats.rtt || prev.cwnd != stats.cwnd);
+
+		if changed {
+			self.write(
+				"recovery:metrics_updated",
+				json!({
+					"smoothed_rtt": stats.rtt.as_secs_f64() * 1000.0,
+					"congestion_window": stats.cwnd,
+				}),
+			);
+		}
+
+		let lost = stats.lost_packets - prev.map_or(0, |prev| prev.lost_packets);
+		if lost > 0 {
+			// Quinn only exposes counters, so we can't log the individual packets.
+			self.write(
+				"recovery:packets_lost",
+				json!({
+					"count": lost,
+					"bytes": stats.lost_bytes - prev.map_or(0, |prev| prev.lost_bytes),
+					"congestion_events": stats.congestion_events,
+				}),
+			);
+		}
+	}
+}
+
+impl moq_transfork::Observer for Qlog {
+	fn event(&self, event: Event) {
+		let owner = |local: bool| match local {
+			true => "local",
+			false => "remote",
+		};
+
+		match event {
+			Event::StreamOpened { stream, kind, local } => {
+				let mut data = json!({
+					"stream": stream,
+					"owner": owner(local),
+				});
+
+				data["type"] = match kind {
+					StreamKind::Session => "session".into(),
+					StreamKind::Announce => "announce".into(),
+					StreamKind::Subscribe => "subscribe".into(),
+					StreamKind::Info => "info".into(),
+					StreamKind::Group { subscribe, sequence } => {
+						data["subscribe"] = subscribe.into();
+						data["group"] = sequence.into();
+						"group".into()
+					}
+				};
+
+				self.write("moq:stream_opened", data);
+			}
+			Event::StreamClosed { stream } => self.write("moq:stream_closed", json!({ "stream": stream })),
+			Event::GroupDropped {
+				subscribe,
+				sequence,
+				count,
+				code,
+				local,
+			} => self.write(
+				"moq:group_dropped",
+				json!({
+					"subscribe": subscribe,
+					"group": sequence,
+					"count": count,
+					"code": code,
+					"owner": owner(local),
+				}),
+			),
+			Event::PrioritySet { stream, priority } => self.write(
+				"moq:priority_updated",
+				json!({
+					"stream": stream,
+					"priority": priority,
+				}),
+			),
+		}
+	}
+}
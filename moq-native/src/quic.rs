@@ -4,7 +4,7 @@ use anyhow::Context;
 use clap::Parser;
 use url::Url;
 
-use crate::tls;
+use crate::{qlog, tls};
 
 use futures::future::BoxFuture;
 use futures::stream::{FuturesUnordered, StreamExt};
@@ -20,6 +20,9 @@ pub struct Args {
 
 	#[command(flatten)]
 	pub tls: tls::Args,
+
+	#[command(flatten)]
+	pub qlog: qlog::Args,
 }
 
 impl Default for Args {
@@ -27,6 +30,7 @@ impl Default for Args {
 		Self {
 			bind: "[::]:0".parse().unwrap(),
 			tls: Default::default(),
+			qlog: Default::default(),
 		}
 	}
 }
@@ -34,13 +38,21 @@ impl Default for Args {
 impl Args {
 	pub fn load(&self) -> anyhow::Result<Config> {
 		let tls = self.tls.load()?;
-		Ok(Config { bind: self.bind, tls })
+		let qlog = self.qlog.load()?;
+		Ok(Config {
+			bind: self.bind,
+			tls,
+			qlog,
+		})
 	}
 }
 
 pub struct Config {
 	pub bind: net::SocketAddr,
 	pub tls: tls::Config,
+
+	/// Write a qlog for each connection to this directory.
+	pub qlog: Option<qlog::Dir>,
 }
 
 pub struct Endpoint {
@@ -59,18 +71,10 @@ impl Endpoint {
 		transport.mtu_discovery_config(None); // Disable MTU discovery
 		let transport = Arc::new(transport);
 
-		let mut server_config = None;
-
-		if let Some(mut config) = config.tls.server {
-			config.alpn_protocols = vec![web_transport::quinn::ALPN.to_vec(), moq_transfork::ALPN.to_vec()];
-			config.key_log = Arc::new(rustls::KeyLogFile::new());
-
-			let config: quinn::crypto::rustls::QuicServerConfig = config.try_into()?;
-			let mut config = quinn::ServerConfig::with_crypto(Arc::new(config));
-			config.transport_config(transport.clone());
-
-			server_config = Some(config);
-		}
+		let server_config = match config.tls.server {
+			Some(tls) => Some(Server::config(tls, transport.clone())?),
+			None => None,
+		};
 
 		// There's a bit more boilerplate to make a generic endpoint.
 		let runtime = quinn::default_runtime().context("no async runtime")?;
@@ -84,41 +88,95 @@ impl Endpoint {
 		let server = server_config.is_some().then(|| Server {
 			quic: quic.clone(),
 			accept: Default::default(),
+			transport: transport.clone(),
+			qlog: config.qlog.clone(),
 		});
 
 		let client = Client {
 			quic,
 			config: config.tls.client,
 			transport,
+			qlog: config.qlog,
 		};
 
 		Ok(Self { client, server })
 	}
 }
 
+/// Returns the `jwt` query parameter of a connect URL, if any.
+///
+/// The WebTransport client only sends the URL path, so the token should be presented via [moq_transfork::Session::connect_token] instead.
+pub fn token(url: &Url) -> Option<String> {
+	url.query_pairs()
+		.find(|(key, _)| key == "jwt")
+		.map(|(_, value)| value.to_string())
+}
+
+/// A session accepted by the [Server].
+pub struct Accepted {
+	pub session: web_transport_quinn::Session,
+
+	/// The URL requested by a WebTransport client, or None when connecting via QUIC directly.
+	pub url: Option<Url>,
+}
+
 pub struct Server {
 	quic: quinn::Endpoint,
-	accept: FuturesUnordered<BoxFuture<'static, anyhow::Result<web_transport_quinn::Session>>>,
+	accept: FuturesUnordered<BoxFuture<'static, anyhow::Result<Accepted>>>,
+	transport: Arc<quinn::TransportConfig>,
+	qlog: Option<qlog::Dir>,
 }
 
 impl Server {
+	fn config(
+		mut tls: rustls::ServerConfig,
+		transport: Arc<quinn::TransportConfig>,
+	) -> anyhow::Result<quinn::ServerConfig> {
+		tls.alpn_protocols = vec![
+			web_transport::quinn::ALPN.to_vec(),
+			moq_transfork::ALPN.to_vec(),
+			moq_transfork::ietf::ALPN.to_vec(),
+		];
+		tls.key_log = Arc::new(rustls::KeyLogFile::new());
+
+		let tls: quinn::crypto::rustls::QuicServerConfig = tls.try_into()?;
+		let mut config = quinn::ServerConfig::with_crypto(Arc::new(tls));
+		config.transport_config(transport);
+
+		Ok(config)
+	}
+
+	/// Replace the TLS configuration used for any new connections, such as after renewing certificates.
+	///
+	/// Existing connections are unaffected.
+	pub fn set_tls(&self, tls: rustls::ServerConfig) -> anyhow::Result<()> {
+		let config = Self::config(tls, self.transport.clone())?;
+		self.quic.set_server_config(Some(config));
+		Ok(())
+	}
+
 	pub async fn accept(&mut self) -> Option<web_transport_quinn::Session> {
+		Some(self.accept_request().await?.session)
+	}
+
+	/// Accept a session, along with the URL requested by the client.
+	pub async fn accept_request(&mut self) -> Option<Accepted> {
 		loop {
 			tokio::select! {
 				res = self.quic.accept() => {
 					let conn = res?;
-					self.accept.push(Self::accept_session(conn).boxed());
+					self.accept.push(Self::accept_session(conn, self.qlog.clone()).boxed());
 				}
 				Some(res) = self.accept.next() => {
-					if let Ok(session) = res {
-						return Some(session)
+					if let Ok(accepted) = res {
+						return Some(accepted)
 					}
 				}
 			}
 		}
 	}
 
-	async fn accept_session(conn: quinn::Incoming) -> anyhow::Result<web_transport_quinn::Session> {
+	async fn accept_session(conn: quinn::Incoming, qlog: Option<qlog::Dir>) -> anyhow::Result<Accepted> {
 		let mut conn = conn.accept()?;
 
 		let handshake = conn
@@ -139,30 +197,52 @@ impl Server {
 		let span = tracing::Span::current();
 		span.record("id", conn.stable_id()); // TODO can we get this earlier?
 
-		let session = match alpn.as_bytes() {
+		if let Some(qlog) = qlog {
+			if let Err(err) = qlog.start(&conn, qlog::Vantage::Server) {
+				tracing::warn!(?err, "failed to start qlog");
+			}
+		}
+
+		let accepted = match alpn.as_bytes() {
 			web_transport::quinn::ALPN => {
 				// Wait for the CONNECT request.
 				let request = web_transport::quinn::Request::accept(conn)
 					.await
 					.context("failed to receive WebTransport request")?;
 
+				let url = request.url().clone();
+
 				// Accept the CONNECT request.
-				request
+				let session = request
 					.ok()
 					.await
-					.context("failed to respond to WebTransport request")?
+					.context("failed to respond to WebTransport request")?;
+
+				Accepted {
+					session,
+					url: Some(url),
+				}
 			}
 			// A bit of a hack to pretend like we're a WebTransport session
-			moq_transfork::ALPN => conn.into(),
+			// NOTE: The MoQ session detects moq-transport based on the first message.
+			moq_transfork::ALPN | moq_transfork::ietf::ALPN => Accepted {
+				session: conn.into(),
+				url: None,
+			},
 			_ => anyhow::bail!("unsupported ALPN: {}", alpn),
 		};
 
-		Ok(session)
+		Ok(accepted)
 	}
 
 	pub fn local_addr(&self) -> anyhow::Result<net::SocketAddr> {
 		self.quic.local_addr().context("failed to get local address")
 	}
+
+	/// Returns the qlog for an accepted session, to be passed to [moq_transfork::Session::observe].
+	pub fn qlog(&self, session: &web_transport_quinn::Session) -> Option<Arc<qlog::Qlog>> {
+		self.qlog.as_ref()?.get(session)
+	}
 }
 
 #[derive(Clone)]
@@ -170,6 +250,7 @@ pub struct Client {
 	quic: quinn::Endpoint,
 	config: rustls::ClientConfig,
 	transport: Arc<quinn::TransportConfig>,
+	qlog: Option<qlog::Dir>,
 }
 
 impl Client {
@@ -211,7 +292,8 @@ impl Client {
 		let alpn = match url.scheme() {
 			"https" => web_transport::quinn::ALPN,
 			"moqf" => moq_transfork::ALPN,
-			_ => anyhow::bail!("url scheme must be 'http', 'https', or 'moqf'"),
+			"moqt" => moq_transfork::ietf::ALPN,
+			_ => anyhow::bail!("url scheme must be 'http', 'https', 'moqf', or 'moqt'"),
 		};
 
 		// TODO support connecting to both ALPNs at the same time
@@ -227,12 +309,23 @@ impl Client {
 		let connection = self.quic.connect_with(config, ip, &host)?.await?;
 		tracing::Span::current().record("id", connection.stable_id());
 
+		if let Some(qlog) = &self.qlog {
+			if let Err(err) = qlog.start(&connection, qlog::Vantage::Client) {
+				tracing::warn!(?err, "failed to start qlog");
+			}
+		}
+
 		let session = match url.scheme() {
 			"https" => web_transport::quinn::Session::connect(connection, &url).await?,
-			"moqf" => connection.into(),
+			"moqf" | "moqt" => connection.into(),
 			_ => unreachable!(),
 		};
 
 		Ok(session)
 	}
+
+	/// Returns the qlog for a connected session, to be passed to [moq_transfork::Session::observe].
+	pub fn qlog(&self, session: &web_transport_quinn::Session) -> Option<Arc<qlog::Qlog>> {
+		self.qlog.as_ref()?.get(session)
+	}
 }
@@ -6,13 +6,15 @@ use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer, ServerName, UnixTime
 use rustls::server::{ClientHello, ResolvesServerCert};
 use rustls::sign::CertifiedKey;
 use rustls::RootCertStore;
+use serde::Deserialize;
 use std::fs;
 use std::io::{self, Cursor, Read};
 use std::path;
 use std::sync::Arc;
 
-#[derive(Parser, Clone, Default)]
+#[derive(Parser, Clone, Default, Deserialize)]
 #[group(id = "tls")]
+#[serde(default, deny_unknown_fields)]
 pub struct Args {
 	/// Use the certificates at this path, encoded as PEM.
 	///
@@ -0,0 +1,131 @@
+use std::hint::black_box;
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+use moq_proto::coding::{Decode, Encode, Str};
+use moq_proto::message;
+
+// A burst of announcements, like the replay sent when an announce stream is opened.
+fn announces(count: usize) -> Bytes {
+	let mut buf = Vec::new();
+
+	for i in 0..count {
+		message::Announce::Active(format!("room/{}/broadcast/{}.karp", i / 16, i).into()).encode(&mut buf);
+	}
+
+	buf.into()
+}
+
+fn subscribe() -> Bytes {
+	let mut buf = Vec::new();
+
+	message::Subscribe {
+		id: 42,
+		path: "room/123/broadcast/video.karp".into(),
+		priority: 0,
+		order: message::GroupOrder::Desc,
+		start: None,
+		end: None,
+	}
+	.encode(&mut buf);
+
+	buf.into()
+}
+
+fn strings(c: &mut Criterion) {
+	let mut group = c.benchmark_group("string");
+
+	for size in [32, 1024, 16 * 1024] {
+		let mut buf = Vec::new();
+		"a".repeat(size).encode(&mut buf);
+		let buf = Bytes::from(buf);
+
+		group.throughput(Throughput::Bytes(size as u64));
+
+		group.bench_with_input(BenchmarkId::new("String", size), &buf, |b, buf| {
+			b.iter(|| String::decode(&mut black_box(buf.clone())).unwrap())
+		});
+
+		group.bench_with_input(BenchmarkId::new("Str", size), &buf, |b, buf| {
+			b.iter(|| Str::decode(&mut black_box(buf.clone())).unwrap())
+		});
+	}
+
+	group.finish();
+}
+
+fn messages(c: &mut Criterion) {
+	let mut group = c.benchmark_group("subscribe");
+	let buf = subscribe();
+
+	// Decoding from a slice copies the path.
+	group.bench_function("slice", |b| {
+		b.iter(|| message::Subscribe::decode(&mut black_box(&buf[..])).unwrap())
+	});
+
+	// Decoding from Bytes references the buffer instead.
+	group.bench_function("bytes", |b| {
+		b.iter(|| message::Subscribe::decode(&mut black_box(buf.clone())).unwrap())
+	});
+
+	group.finish();
+
+	let mut group = c.benchmark_group("announce");
+	let count = 1000;
+	let buf = announces(count);
+	group.throughput(Throughput::Elements(count as u64));
+
+	group.bench_function("slice", |b| {
+		b.iter_batched(
+			|| &buf[..],
+			|mut buf| {
+				while !buf.is_empty() {
+					black_box(message::Announce::decode(&mut buf).unwrap());
+				}
+			},
+			BatchSize::SmallInput,
+		)
+	});
+
+	group.bench_function("bytes", |b| {
+		b.iter_batched(
+			|| buf.clone(),
+			|mut buf| {
+				while !buf.is_empty() {
+					black_box(message::Announce::decode(&mut buf).unwrap());
+				}
+			},
+			BatchSize::SmallInput,
+		)
+	});
+
+	group.finish();
+}
+
+fn extensions(c: &mut Criterion) {
+	let mut group = c.benchmark_group("extensions");
+
+	let mut ext = message::Extensions::default();
+	ext.set(message::TraceContext {
+		parent: "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".to_string(),
+		state: String::new(),
+	});
+
+	let mut buf = Vec::new();
+	ext.encode(&mut buf);
+	let buf = Bytes::from(buf);
+
+	group.bench_function("slice", |b| {
+		b.iter(|| message::Extensions::decode(&mut black_box(&buf[..])).unwrap())
+	});
+
+	group.bench_function("bytes", |b| {
+		b.iter(|| message::Extensions::decode(&mut black_box(buf.clone())).unwrap())
+	});
+
+	group.finish();
+}
+
+criterion_group!(benches, strings, messages, extensions);
+criterion_main!(benches);
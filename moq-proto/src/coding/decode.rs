@@ -1,8 +1,18 @@
-use std::string::FromUtf8Error;
+use std::{str::Utf8Error, string::FromUtf8Error};
 use thiserror::Error;
 
+use super::DecodeLimits;
+
 pub trait Decode: Sized {
 	fn decode<B: bytes::Buf>(buf: &mut B) -> Result<Self, DecodeError>;
+
+	/// Decode while enforcing the limits on any untrusted lengths.
+	///
+	/// Types containing a length prefix override this, and [Self::decode] uses the default limits.
+	fn decode_limits<B: bytes::Buf>(buf: &mut B, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let _ = limits;
+		Self::decode(buf)
+	}
 }
 
 /// A decode error.
@@ -14,6 +24,9 @@ pub enum DecodeError {
 	#[error("invalid string")]
 	InvalidString(#[from] FromUtf8Error),
 
+	#[error("invalid string")]
+	InvalidUtf8(#[from] Utf8Error),
+
 	#[error("invalid message: {0:?}")]
 	InvalidMessage(u64),
 
@@ -44,6 +57,30 @@ pub enum DecodeError {
 
 	#[error("invalid parameter")]
 	InvalidParameter,
+
+	#[error("path too long: {0} bytes")]
+	PathTooLong(usize),
+
+	#[error("string too long: {0} bytes")]
+	StringTooLong(usize),
+
+	#[error("too many versions: {0}")]
+	TooManyVersions(usize),
+
+	#[error("list too long: {0} elements")]
+	ListTooLong(usize),
+
+	#[error("too many extensions: {0}")]
+	TooManyExtensions(usize),
+
+	#[error("extension too large: {0} bytes")]
+	ExtensionTooLarge(usize),
+
+	#[error("frame too large: {0} bytes")]
+	FrameTooLarge(usize),
+
+	#[error("too many frames in group")]
+	TooManyFrames,
 }
 
 impl Decode for u8 {
@@ -56,24 +93,56 @@ impl Decode for u8 {
 }
 
 impl Decode for String {
-	/// Decode a string with a varint length prefix.
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let v = Vec::<u8>::decode(r)?;
-		let str = String::from_utf8(v)?;
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
 
-		Ok(str)
+	/// Decode a string with a varint length prefix, up to [DecodeLimits::max_string] bytes.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		decode_string(r, limits.max_string, DecodeError::StringTooLong)
 	}
 }
 
+/// Decode a string with a varint length prefix, returning `err` if it's longer than `max` bytes.
+///
+/// The length is checked before waiting for the rest of the string.
+pub fn decode_string<R: bytes::Buf>(
+	r: &mut R,
+	max: usize,
+	err: fn(usize) -> DecodeError,
+) -> Result<String, DecodeError> {
+	let len = usize::decode(r)?;
+	if len > max {
+		return Err(err(len));
+	}
+
+	if r.remaining() < len {
+		return Err(DecodeError::Short);
+	}
+
+	let mut v = vec![0; len];
+	r.copy_to_slice(&mut v);
+	let str = String::from_utf8(v)?;
+
+	Ok(str)
+}
+
 impl<T: Decode> Decode for Vec<T> {
 	fn decode<B: bytes::Buf>(buf: &mut B) -> Result<Self, DecodeError> {
+		Self::decode_limits(buf, &DecodeLimits::default())
+	}
+
+	fn decode_limits<B: bytes::Buf>(buf: &mut B, limits: &DecodeLimits) -> Result<Self, DecodeError> {
 		let size = usize::decode(buf)?;
+		if size > limits.max_list {
+			return Err(DecodeError::ListTooLong(size));
+		}
 
 		// Don't allocate more than 1024 elements upfront
 		let mut v = Vec::with_capacity(size.min(1024));
 
 		for _ in 0..size {
-			v.push(T::decode(buf)?);
+			v.push(T::decode_limits(buf, limits)?);
 		}
 
 		Ok(v)
@@ -102,7 +171,16 @@ impl Decode for i8 {
 
 impl Decode for bytes::Bytes {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	/// Decode a payload with a varint length prefix.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
 		let len = usize::decode(r)?;
+		if len > limits.max_frame_size {
+			return Err(DecodeError::FrameTooLarge(len));
+		}
+
 		if r.remaining() < len {
 			return Err(DecodeError::Short);
 		}
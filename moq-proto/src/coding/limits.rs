@@ -0,0 +1,166 @@
+/// Limits enforced while decoding, guarding against a peer claiming enormous lengths.
+///
+/// The defaults are generous; a public relay may want to lower them to bound per-peer memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+	/// The maximum length of a path in bytes.
+	pub max_path: usize,
+
+	/// The maximum length of any other string in bytes, such as a reason phrase.
+	pub max_string: usize,
+
+	/// The maximum number of versions offered in a setup message.
+	pub max_versions: usize,
+
+	/// The maximum number of elements in any other list, such as the sources in an announce cursor.
+	pub max_list: usize,
+
+	/// The maximum number of extensions in a setup message.
+	pub max_extensions: usize,
+
+	/// The maximum size of a single extension value in bytes.
+	pub max_extension_size: usize,
+
+	/// The maximum size of a single frame (or any other payload) in bytes.
+	pub max_frame_size: usize,
+
+	/// The maximum number of frames within a single group.
+	pub max_group_frames: usize,
+}
+
+impl Default for DecodeLimits {
+	fn default() -> Self {
+		Self {
+			max_path: 1024,
+			max_string: 1024,
+			max_versions: 64,
+			max_list: 1024,
+			max_extensions: 64,
+			max_extension_size: 4096,
+			max_frame_size: 16 * 1024 * 1024,
+			max_group_frames: 64 * 1024,
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::coding::{Decode, DecodeError, Encode};
+	use crate::message;
+
+	use bytes::{Bytes, BytesMut};
+
+	fn encoded<T: Encode>(msg: &T) -> Bytes {
+		let mut buf = BytesMut::new();
+		msg.encode(&mut buf);
+		buf.freeze()
+	}
+
+	fn limits() -> DecodeLimits {
+		DecodeLimits {
+			max_versions: 2,
+			max_list: 2,
+			max_extensions: 2,
+			max_extension_size: 4,
+			max_frame_size: 4,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn versions() {
+		let setup = message::ClientSetup {
+			versions: [message::Version::FORK_04; 3].into(),
+			extensions: Default::default(),
+		};
+
+		let res = message::ClientSetup::decode_limits(&mut encoded(&setup), &limits());
+		assert!(matches!(res, Err(DecodeError::TooManyVersions(3))));
+	}
+
+	#[test]
+	fn extensions() {
+		let mut setup = message::ClientSetup {
+			versions: [message::Version::FORK_04].into(),
+			extensions: Default::default(),
+		};
+		setup.extensions.set(message::Authorization {
+			token: "too long".to_string(),
+		});
+
+		let res = message::ClientSetup::decode_limits(&mut encoded(&setup), &limits());
+		assert!(matches!(res, Err(DecodeError::ExtensionTooLarge(9))));
+
+		// Three extensions with a single byte each.
+		let mut buf = BytesMut::new();
+		3usize.encode(&mut buf);
+		for kind in 0..3u64 {
+			kind.encode(&mut buf);
+			Bytes::from_static(b"x").encode(&mut buf);
+		}
+
+		let res = message::Extensions::decode_limits(&mut buf.freeze(), &limits());
+		assert!(matches!(res, Err(DecodeError::TooManyExtensions(3))));
+	}
+
+	#[test]
+	fn strings() {
+		// A token is only held to the extension size, which the session may raise above the default.
+		let token = "a".repeat(5000);
+		let mut setup = message::ClientSetup {
+			versions: [message::Version::FORK_05].into(),
+			extensions: Default::default(),
+		};
+		setup.extensions.set(message::Authorization { token: token.clone() });
+
+		let limits = DecodeLimits {
+			max_extension_size: 8192,
+			..Default::default()
+		};
+		let setup = message::ClientSetup::decode_limits(&mut encoded(&setup), &limits).unwrap();
+		let auth = setup.extensions.get::<message::Authorization>().unwrap().unwrap();
+		assert_eq!(auth.token, token);
+
+		// Paths are still held to the path length.
+		let filter = message::Filter::new(&"a".repeat(2000));
+		let res = message::Filter::decode_limits(&mut encoded(&filter), &DecodeLimits::default());
+		assert!(matches!(res, Err(DecodeError::PathTooLong(2000))));
+
+		// Any other string has its own limit.
+		let limits = DecodeLimits {
+			max_string: 4,
+			..Default::default()
+		};
+		let res = String::decode_limits(&mut encoded(&"hello".to_string()), &limits);
+		assert!(matches!(res, Err(DecodeError::StringTooLong(5))));
+	}
+
+	#[test]
+	fn frame() {
+		let frame = message::Frame { size: 5 };
+		let res = message::Frame::decode_limits(&mut encoded(&frame), &limits());
+		assert!(matches!(res, Err(DecodeError::FrameTooLarge(5))));
+
+		// Any other payload is held to the same limit.
+		let payload = Bytes::from_static(b"hello");
+		let res = Bytes::decode_limits(&mut encoded(&payload), &limits());
+		assert!(matches!(res, Err(DecodeError::FrameTooLarge(5))));
+	}
+
+	#[test]
+	fn list() {
+		let epoch = message::AnnounceEpoch { id: 1, sequence: 2 };
+		let cursor = message::AnnounceCursor(vec![epoch; 3]);
+
+		let res = message::Announce::decode_limits(&mut encoded(&message::Announce::Cursor(cursor.clone())), &limits());
+		assert!(matches!(res, Err(DecodeError::ListTooLong(3))));
+
+		let please = message::AnnouncePlease {
+			filter: message::Filter::Any,
+			cursor: Some(cursor),
+		};
+		let res = message::AnnouncePlease::decode_limits(&mut encoded(&please), &limits());
+		assert!(matches!(res, Err(DecodeError::ListTooLong(3))));
+	}
+}
@@ -2,12 +2,16 @@
 
 mod decode;
 mod encode;
+mod limits;
 mod size;
+mod str;
 mod varint;
 
 pub use decode::*;
 pub use encode::*;
+pub use limits::*;
 pub use size::*;
+pub use str::*;
 pub use varint::*;
 
 // Re-export the bytes crate
@@ -0,0 +1,209 @@
+use std::{
+	borrow::Borrow,
+	fmt,
+	hash::{Hash, Hasher},
+	ops::Deref,
+	str::Utf8Error,
+};
+
+use bytes::Bytes;
+
+use super::{Decode, DecodeError, DecodeLimits, Encode};
+
+/// An immutable UTF-8 string backed by [Bytes].
+///
+/// Decoding from a [Bytes] buffer is zero-copy; the string shares the buffer's allocation.
+/// Any other [bytes::Buf] is copied once, as is a [String] when converted.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Str(Bytes);
+
+impl Str {
+	pub const fn from_static(s: &'static str) -> Self {
+		Self(Bytes::from_static(s.as_bytes()))
+	}
+
+	/// Validate that the bytes are UTF-8.
+	pub fn from_utf8(bytes: Bytes) -> Result<Self, Utf8Error> {
+		std::str::from_utf8(&bytes)?;
+		Ok(Self(bytes))
+	}
+
+	pub fn as_str(&self) -> &str {
+		// SAFETY: The contents were validated on construction.
+		unsafe { std::str::from_utf8_unchecked(&self.0) }
+	}
+
+	/// Returns a slice of self that is equivalent to the given `subset`, without making a copy.
+	///
+	/// Panics if the subset is not contained within self, like [Bytes::slice_ref].
+	pub fn slice_ref(&self, subset: &str) -> Self {
+		Self(self.0.slice_ref(subset.as_bytes()))
+	}
+
+	pub fn into_bytes(self) -> Bytes {
+		self.0
+	}
+}
+
+impl Deref for Str {
+	type Target = str;
+
+	fn deref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl AsRef<str> for Str {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl Borrow<str> for Str {
+	fn borrow(&self) -> &str {
+		self.as_str()
+	}
+}
+
+// Hash like a str, otherwise maps keyed by Str couldn't be queried with a str.
+impl Hash for Str {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.as_str().hash(state)
+	}
+}
+
+impl fmt::Debug for Str {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.as_str().fmt(f)
+	}
+}
+
+impl fmt::Display for Str {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.as_str().fmt(f)
+	}
+}
+
+impl From<String> for Str {
+	fn from(s: String) -> Self {
+		Self(Bytes::from(s))
+	}
+}
+
+impl From<&str> for Str {
+	fn from(s: &str) -> Self {
+		Self(Bytes::copy_from_slice(s.as_bytes()))
+	}
+}
+
+impl From<Str> for String {
+	fn from(s: Str) -> Self {
+		s.as_str().to_string()
+	}
+}
+
+impl PartialEq<str> for Str {
+	fn eq(&self, other: &str) -> bool {
+		self.as_str() == other
+	}
+}
+
+impl PartialEq<&str> for Str {
+	fn eq(&self, other: &&str) -> bool {
+		self.as_str() == *other
+	}
+}
+
+impl PartialEq<String> for Str {
+	fn eq(&self, other: &String) -> bool {
+		self.as_str() == other
+	}
+}
+
+impl Decode for Str {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	/// Decode a path with a varint length prefix, up to [DecodeLimits::max_path] bytes.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let len = usize::decode(r)?;
+		if len > limits.max_path {
+			return Err(DecodeError::PathTooLong(len));
+		}
+
+		if r.remaining() < len {
+			return Err(DecodeError::Short);
+		}
+
+		Ok(Self::from_utf8(r.copy_to_bytes(len))?)
+	}
+}
+
+impl Encode for Str {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.as_str().encode(w)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Str {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.as_str())
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Str {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer).map(Self::from)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn zero_copy() {
+		let mut buf = Vec::new();
+		"hello".encode(&mut buf);
+		let buf = Bytes::from(buf);
+
+		let s = Str::decode(&mut buf.clone()).unwrap();
+		assert_eq!(s, "hello");
+
+		// The string points into the original buffer.
+		assert_eq!(s.as_ptr(), buf[1..].as_ptr());
+	}
+
+	#[test]
+	fn too_long() {
+		let mut buf = Vec::new();
+		"a".repeat(2000).encode(&mut buf);
+
+		// The length is checked before waiting for the rest of the string.
+		let limits = DecodeLimits::default();
+		let res = Str::decode_limits(&mut &buf[..4], &limits);
+		assert!(matches!(res, Err(DecodeError::PathTooLong(2000))));
+
+		let limits = DecodeLimits {
+			max_path: 2000,
+			..Default::default()
+		};
+		assert_eq!(Str::decode_limits(&mut &buf[..], &limits).unwrap().len(), 2000);
+	}
+
+	#[test]
+	fn borrow() {
+		let mut map = std::collections::HashMap::new();
+		map.insert(Str::from("hello"), 1);
+		assert_eq!(map.get("hello"), Some(&1));
+	}
+
+	#[test]
+	fn invalid() {
+		let mut buf = Bytes::from_static(&[2, 0xc3, 0x28]);
+		assert!(matches!(Str::decode(&mut buf), Err(DecodeError::InvalidUtf8(_))));
+	}
+}
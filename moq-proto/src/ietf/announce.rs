@@ -0,0 +1,112 @@
+use super::Parameters;
+use crate::coding::*;
+
+/// Sent by the publisher to advertise a track namespace.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Announce {
+	pub namespace: Str,
+	pub params: Parameters,
+}
+
+impl Decode for Announce {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let namespace = Str::decode_limits(r, limits)?;
+		let params = Parameters::decode_limits(r, limits)?;
+
+		Ok(Self { namespace, params })
+	}
+}
+
+impl Encode for Announce {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.namespace.encode(w);
+		self.params.encode(w);
+	}
+}
+
+/// Sent by the subscriber to accept an [Announce].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnounceOk {
+	pub namespace: Str,
+}
+
+/// Sent by the publisher when a namespace is no longer available.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unannounce {
+	pub namespace: Str,
+}
+
+/// Sent by the subscriber when it no longer accepts a namespace.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnounceCancel {
+	pub namespace: Str,
+}
+
+// These messages only contain the namespace.
+macro_rules! namespace_message {
+	($name:ident) => {
+		impl Decode for $name {
+			fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+				Self::decode_limits(r, &DecodeLimits::default())
+			}
+
+			fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+				let namespace = Str::decode_limits(r, limits)?;
+				Ok(Self { namespace })
+			}
+		}
+
+		impl Encode for $name {
+			fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+				self.namespace.encode(w);
+			}
+		}
+	};
+}
+
+namespace_message!(AnnounceOk);
+namespace_message!(Unannounce);
+namespace_message!(AnnounceCancel);
+
+/// Sent by the subscriber to reject an [Announce].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnounceError {
+	pub namespace: Str,
+	pub code: u64,
+	pub reason: String,
+}
+
+impl Decode for AnnounceError {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let namespace = Str::decode_limits(r, limits)?;
+		let code = u64::decode(r)?;
+		let reason = String::decode_limits(r, limits)?;
+
+		Ok(Self {
+			namespace,
+			code,
+			reason,
+		})
+	}
+}
+
+impl Encode for AnnounceError {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.namespace.encode(w);
+		self.code.encode(w);
+		self.reason.encode(w);
+	}
+}
@@ -0,0 +1,164 @@
+use super::*;
+use crate::coding::*;
+
+macro_rules! control_messages {
+	{$($name:ident = $val:expr,)*} => {
+		/// A message sent on the control stream, prefixed with its type.
+		#[derive(Clone, Debug)]
+		#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+		pub enum Message {
+			$($name($name)),*
+		}
+
+		impl Message {
+			/// The type sent before the message.
+			pub fn id(&self) -> u64 {
+				match self {
+					$(Self::$name(_) => $val,)*
+				}
+			}
+		}
+
+		impl Decode for Message {
+			fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+				Self::decode_limits(r, &DecodeLimits::default())
+			}
+
+			fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+				let t = u64::decode(r)?;
+
+				match t {
+					$($val => Ok(Self::$name($name::decode_limits(r, limits)?)),)*
+					_ => Err(DecodeError::InvalidMessage(t)),
+				}
+			}
+		}
+
+		impl Encode for Message {
+			fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+				self.id().encode(w);
+
+				match self {
+					$(Self::$name(m) => m.encode(w),)*
+				}
+			}
+		}
+
+		$(impl $name {
+			/// The type sent before the message.
+			pub const ID: u64 = $val;
+		}
+
+		impl From<$name> for Message {
+			fn from(m: $name) -> Self {
+				Self::$name(m)
+			}
+		})*
+	}
+}
+
+// NOTE: SUBSCRIBE_UPDATE and TRACK_STATUS are not supported.
+control_messages! {
+	Subscribe = 0x3,
+	SubscribeOk = 0x4,
+	SubscribeError = 0x5,
+	Announce = 0x6,
+	AnnounceOk = 0x7,
+	AnnounceError = 0x8,
+	Unannounce = 0x9,
+	Unsubscribe = 0xa,
+	SubscribeDone = 0xb,
+	AnnounceCancel = 0xc,
+	GoAway = 0x10,
+	ClientSetup = 0x40,
+	ServerSetup = 0x41,
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::message::Version;
+
+	fn roundtrip(msg: Message) -> Message {
+		let mut buf = BytesMut::new();
+		msg.encode(&mut buf);
+
+		let mut buf = buf.freeze();
+		let decoded = Message::decode(&mut buf).unwrap();
+		assert!(!buf.has_remaining());
+
+		decoded
+	}
+
+	#[test]
+	fn setup() {
+		let client = ClientSetup {
+			versions: [VERSION].into(),
+			role: Role::Both,
+			params: Default::default(),
+		};
+
+		let mut buf = BytesMut::new();
+		Message::from(client.clone()).encode(&mut buf);
+
+		// type (2 byte varint), 1 version, 1 parameter (role=both)
+		assert_eq!(
+			buf.as_ref(),
+			&[0x40, 0x40, 0x01, 0xc0, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0x04, 0x01, 0x00, 0x01, 0x03]
+		);
+
+		match roundtrip(client.into()) {
+			Message::ClientSetup(client) => {
+				assert_eq!(client.versions.as_slice(), &[Version::DRAFT_04]);
+				assert_eq!(client.role, Role::Both);
+			}
+			msg => panic!("unexpected message: {:?}", msg),
+		}
+
+		// The role is required.
+		let mut buf = Bytes::from_static(&[0x40, 0x41, 0x01, 0x00]);
+		assert!(matches!(Message::decode(&mut buf), Err(DecodeError::MissingParameter)));
+	}
+
+	#[test]
+	fn subscribe() {
+		let subscribe = Subscribe {
+			id: 1,
+			alias: 2,
+			namespace: "room/alice".into(),
+			name: "video".into(),
+			filter: SubscribeFilter::AbsoluteRange(Location { group: 3, object: 4 }, Location { group: 5, object: 0 }),
+			params: Default::default(),
+		};
+
+		match roundtrip(subscribe.into()) {
+			Message::Subscribe(subscribe) => {
+				assert_eq!(subscribe.namespace, "room/alice");
+				assert_eq!(subscribe.name, "video");
+				assert_eq!(
+					subscribe.filter,
+					SubscribeFilter::AbsoluteRange(Location { group: 3, object: 4 }, Location { group: 5, object: 0 })
+				);
+			}
+			msg => panic!("unexpected message: {:?}", msg),
+		}
+
+		let ok = SubscribeOk {
+			id: 1,
+			expires: 0,
+			largest: Some(Location { group: 7, object: 0 }),
+		};
+
+		match roundtrip(ok.into()) {
+			Message::SubscribeOk(ok) => assert_eq!(ok.largest, Some(Location { group: 7, object: 0 })),
+			msg => panic!("unexpected message: {:?}", msg),
+		}
+
+		// SUBSCRIBE_UPDATE is not supported.
+		let mut buf = Bytes::from_static(&[0x02, 0x00]);
+		assert!(matches!(
+			Message::decode(&mut buf),
+			Err(DecodeError::InvalidMessage(0x02))
+		));
+	}
+}
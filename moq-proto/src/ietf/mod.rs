@@ -0,0 +1,26 @@
+//! Messages for the IETF [moq-transport](https://datatracker.ietf.org/doc/draft-ietf-moq-transport/) draft.
+//!
+//! This is a compatibility layer so we can talk to third-party MoQ implementations.
+//! Only [VERSION] is supported, and only the subset of messages needed to map onto tracks, groups and frames.
+//!
+//! The control stream carries [Message]s, each prefixed with its type.
+//! Objects are delivered on unidirectional streams, each starting with a [StreamHeader].
+mod announce;
+mod control;
+mod object;
+mod setup;
+mod subscribe;
+
+pub use announce::*;
+pub use control::*;
+pub use object::*;
+pub use setup::*;
+pub use subscribe::*;
+
+use crate::message::{Extensions, Version};
+
+/// The only supported version of the IETF draft.
+pub const VERSION: Version = Version::DRAFT_04;
+
+/// Key-value parameters, encoded the same way as our extensions.
+pub type Parameters = Extensions;
@@ -0,0 +1,260 @@
+use crate::coding::*;
+
+/// The status of an object, only sent when the payload is empty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ObjectStatus {
+	Normal,
+	ObjectMissing,
+	GroupMissing,
+	EndOfGroup,
+	EndOfTrack,
+}
+
+impl Decode for ObjectStatus {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		match u64::decode(r)? {
+			0x0 => Ok(Self::Normal),
+			0x1 => Ok(Self::ObjectMissing),
+			0x2 => Ok(Self::GroupMissing),
+			0x3 => Ok(Self::EndOfGroup),
+			0x4 => Ok(Self::EndOfTrack),
+			_ => Err(DecodeError::InvalidValue),
+		}
+	}
+}
+
+impl Encode for ObjectStatus {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		let v: u64 = match self {
+			Self::Normal => 0x0,
+			Self::ObjectMissing => 0x1,
+			Self::GroupMissing => 0x2,
+			Self::EndOfGroup => 0x3,
+			Self::EndOfTrack => 0x4,
+		};
+		v.encode(w)
+	}
+}
+
+// The payload size, followed by the status if the payload is empty.
+fn decode_payload<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<(usize, ObjectStatus), DecodeError> {
+	let size = usize::decode(r)?;
+	if size > limits.max_frame_size {
+		return Err(DecodeError::FrameTooLarge(size));
+	}
+
+	let status = match size {
+		0 => ObjectStatus::decode(r)?,
+		_ => ObjectStatus::Normal,
+	};
+
+	Ok((size, status))
+}
+
+fn encode_payload<W: bytes::BufMut>(w: &mut W, size: usize, status: ObjectStatus) {
+	size.encode(w);
+
+	if size == 0 {
+		status.encode(w);
+	}
+}
+
+/// The header at the start of each unidirectional stream.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StreamHeader {
+	/// Every object in the track is sent on this stream, each prefixed by a [TrackObject].
+	Track(TrackHeader),
+
+	/// Every object in the group is sent on this stream, each prefixed by a [GroupObject].
+	Group(GroupHeader),
+}
+
+impl Decode for StreamHeader {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		match u64::decode(r)? {
+			0x50 => Ok(Self::Track(TrackHeader::decode(r)?)),
+			0x51 => Ok(Self::Group(GroupHeader::decode(r)?)),
+			t => Err(DecodeError::InvalidMessage(t)),
+		}
+	}
+}
+
+impl Encode for StreamHeader {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		match self {
+			Self::Track(header) => {
+				0x50u64.encode(w);
+				header.encode(w);
+			}
+			Self::Group(header) => {
+				0x51u64.encode(w);
+				header.encode(w);
+			}
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackHeader {
+	pub subscribe: u64,
+	pub alias: u64,
+	pub send_order: u64,
+}
+
+impl Decode for TrackHeader {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self {
+			subscribe: u64::decode(r)?,
+			alias: u64::decode(r)?,
+			send_order: u64::decode(r)?,
+		})
+	}
+}
+
+impl Encode for TrackHeader {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.subscribe.encode(w);
+		self.alias.encode(w);
+		self.send_order.encode(w);
+	}
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackObject {
+	pub group: u64,
+	pub id: u64,
+	pub size: usize,
+	pub status: ObjectStatus,
+}
+
+impl Decode for TrackObject {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let group = u64::decode(r)?;
+		let id = u64::decode(r)?;
+		let (size, status) = decode_payload(r, limits)?;
+
+		Ok(Self {
+			group,
+			id,
+			size,
+			status,
+		})
+	}
+}
+
+impl Encode for TrackObject {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.group.encode(w);
+		self.id.encode(w);
+		encode_payload(w, self.size, self.status);
+	}
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupHeader {
+	pub subscribe: u64,
+	pub alias: u64,
+	pub group: u64,
+	pub send_order: u64,
+}
+
+impl Decode for GroupHeader {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self {
+			subscribe: u64::decode(r)?,
+			alias: u64::decode(r)?,
+			group: u64::decode(r)?,
+			send_order: u64::decode(r)?,
+		})
+	}
+}
+
+impl Encode for GroupHeader {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.subscribe.encode(w);
+		self.alias.encode(w);
+		self.group.encode(w);
+		self.send_order.encode(w);
+	}
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GroupObject {
+	pub id: u64,
+	pub size: usize,
+	pub status: ObjectStatus,
+}
+
+impl Decode for GroupObject {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let id = u64::decode(r)?;
+		let (size, status) = decode_payload(r, limits)?;
+
+		Ok(Self { id, size, status })
+	}
+}
+
+impl Encode for GroupObject {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+		encode_payload(w, self.size, self.status);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn status() {
+		let mut buf = BytesMut::new();
+
+		// The status is only encoded for empty objects.
+		let objects = [
+			GroupObject {
+				id: 0,
+				size: 3,
+				status: ObjectStatus::Normal,
+			},
+			GroupObject {
+				id: 1,
+				size: 0,
+				status: ObjectStatus::EndOfGroup,
+			},
+		];
+
+		for object in &objects {
+			object.encode(&mut buf);
+		}
+
+		assert_eq!(buf.as_ref(), &[0x00, 0x03, 0x01, 0x00, 0x03]);
+
+		let mut buf = buf.freeze();
+		assert_eq!(GroupObject::decode(&mut buf).unwrap().size, 3);
+		assert_eq!(GroupObject::decode(&mut buf).unwrap().status, ObjectStatus::EndOfGroup);
+
+		let limits = DecodeLimits {
+			max_frame_size: 2,
+			..Default::default()
+		};
+		let mut buf = Bytes::from_static(&[0x00, 0x03]);
+		assert!(matches!(
+			GroupObject::decode_limits(&mut buf, &limits),
+			Err(DecodeError::FrameTooLarge(3))
+		));
+	}
+}
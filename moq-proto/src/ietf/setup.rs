@@ -0,0 +1,155 @@
+use super::Parameters;
+use crate::{
+	coding::*,
+	message::{Extension, Version, Versions},
+};
+
+/// The role of an endpoint, a required parameter in the setup messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Role {
+	Publisher,
+	Subscriber,
+	Both,
+}
+
+impl Role {
+	/// Returns true if the endpoint may publish tracks.
+	pub fn is_publisher(&self) -> bool {
+		matches!(self, Self::Publisher | Self::Both)
+	}
+
+	/// Returns true if the endpoint may subscribe to tracks.
+	pub fn is_subscriber(&self) -> bool {
+		matches!(self, Self::Subscriber | Self::Both)
+	}
+}
+
+impl Extension for Role {
+	fn id() -> u64 {
+		0x00
+	}
+}
+
+impl Decode for Role {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		match u64::decode(r)? {
+			0x01 => Ok(Self::Publisher),
+			0x02 => Ok(Self::Subscriber),
+			0x03 => Ok(Self::Both),
+			v => Err(DecodeError::InvalidRole(v)),
+		}
+	}
+}
+
+impl Encode for Role {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		let v: u64 = match self {
+			Self::Publisher => 0x01,
+			Self::Subscriber => 0x02,
+			Self::Both => 0x03,
+		};
+		v.encode(w)
+	}
+}
+
+/// Sent by the client to setup the session.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientSetup {
+	/// The list of supported versions in preferred order.
+	pub versions: Versions,
+
+	/// The role of the client.
+	pub role: Role,
+
+	/// Any other parameters.
+	pub params: Parameters,
+}
+
+impl Decode for ClientSetup {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let versions = Versions::decode_limits(r, limits)?;
+		let params = Parameters::decode_limits(r, limits)?;
+		let role = params.get::<Role>()?.ok_or(DecodeError::MissingParameter)?;
+
+		Ok(Self { versions, role, params })
+	}
+}
+
+impl Encode for ClientSetup {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.versions.encode(w);
+
+		let mut params = self.params.clone();
+		params.set(self.role);
+		params.encode(w);
+	}
+}
+
+/// Sent by the server in response to a client setup.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerSetup {
+	/// The selected version.
+	pub version: Version,
+
+	/// The role of the server.
+	pub role: Role,
+
+	/// Any other parameters.
+	pub params: Parameters,
+}
+
+impl Decode for ServerSetup {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let version = Version::decode(r)?;
+		let params = Parameters::decode_limits(r, limits)?;
+		let role = params.get::<Role>()?.ok_or(DecodeError::MissingParameter)?;
+
+		Ok(Self { version, role, params })
+	}
+}
+
+impl Encode for ServerSetup {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.version.encode(w);
+
+		let mut params = self.params.clone();
+		params.set(self.role);
+		params.encode(w);
+	}
+}
+
+/// Sent by the server to ask the client to migrate to a new session.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GoAway {
+	/// The URI of the new session, or empty to reuse the current URI.
+	pub uri: String,
+}
+
+impl Decode for GoAway {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let uri = String::decode_limits(r, limits)?;
+		Ok(Self { uri })
+	}
+}
+
+impl Encode for GoAway {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.uri.encode(w);
+	}
+}
@@ -0,0 +1,278 @@
+use super::Parameters;
+use crate::coding::*;
+
+/// A position within a track.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+	pub group: u64,
+	pub object: u64,
+}
+
+impl Decode for Location {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self {
+			group: u64::decode(r)?,
+			object: u64::decode(r)?,
+		})
+	}
+}
+
+impl Encode for Location {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.group.encode(w);
+		self.object.encode(w);
+	}
+}
+
+/// An optional [Location], prefixed with a flag indicating if content exists.
+impl Decode for Option<Location> {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		match u8::decode(r)? {
+			0 => Ok(None),
+			1 => Ok(Some(Location::decode(r)?)),
+			_ => Err(DecodeError::InvalidValue),
+		}
+	}
+}
+
+impl Encode for Option<Location> {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		match self {
+			Some(location) => {
+				1u8.encode(w);
+				location.encode(w);
+			}
+			None => 0u8.encode(w),
+		}
+	}
+}
+
+/// Which objects should be delivered for a subscription.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubscribeFilter {
+	/// Start at the beginning of the latest group.
+	LatestGroup,
+
+	/// Start at the latest object.
+	LatestObject,
+
+	/// Start at the given location, continuing indefinitely.
+	AbsoluteStart(Location),
+
+	/// Start and end at the given locations.
+	AbsoluteRange(Location, Location),
+}
+
+impl Decode for SubscribeFilter {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		match u64::decode(r)? {
+			0x1 => Ok(Self::LatestGroup),
+			0x2 => Ok(Self::LatestObject),
+			0x3 => Ok(Self::AbsoluteStart(Location::decode(r)?)),
+			0x4 => Ok(Self::AbsoluteRange(Location::decode(r)?, Location::decode(r)?)),
+			_ => Err(DecodeError::InvalidSubscribeLocation),
+		}
+	}
+}
+
+impl Encode for SubscribeFilter {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		match self {
+			Self::LatestGroup => 0x1u64.encode(w),
+			Self::LatestObject => 0x2u64.encode(w),
+			Self::AbsoluteStart(start) => {
+				0x3u64.encode(w);
+				start.encode(w);
+			}
+			Self::AbsoluteRange(start, end) => {
+				0x4u64.encode(w);
+				start.encode(w);
+				end.encode(w);
+			}
+		}
+	}
+}
+
+/// Sent by the subscriber to request a track.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Subscribe {
+	pub id: u64,
+
+	/// A shorter identifier for the track, used by objects.
+	pub alias: u64,
+
+	pub namespace: Str,
+	pub name: Str,
+
+	pub filter: SubscribeFilter,
+	pub params: Parameters,
+}
+
+impl Decode for Subscribe {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let id = u64::decode(r)?;
+		let alias = u64::decode(r)?;
+		let namespace = Str::decode_limits(r, limits)?;
+		let name = Str::decode_limits(r, limits)?;
+		let filter = SubscribeFilter::decode(r)?;
+		let params = Parameters::decode_limits(r, limits)?;
+
+		Ok(Self {
+			id,
+			alias,
+			namespace,
+			name,
+			filter,
+			params,
+		})
+	}
+}
+
+impl Encode for Subscribe {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+		self.alias.encode(w);
+		self.namespace.encode(w);
+		self.name.encode(w);
+		self.filter.encode(w);
+		self.params.encode(w);
+	}
+}
+
+/// Sent by the publisher to accept a [Subscribe].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscribeOk {
+	pub id: u64,
+
+	/// The number of milliseconds until the subscription expires, or 0 if never.
+	pub expires: u64,
+
+	/// The largest location, if any content exists.
+	pub largest: Option<Location>,
+}
+
+impl Decode for SubscribeOk {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: u64::decode(r)?,
+			expires: u64::decode(r)?,
+			largest: Option::<Location>::decode(r)?,
+		})
+	}
+}
+
+impl Encode for SubscribeOk {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+		self.expires.encode(w);
+		self.largest.encode(w);
+	}
+}
+
+/// Sent by the publisher to reject a [Subscribe].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscribeError {
+	pub id: u64,
+	pub code: u64,
+	pub reason: String,
+	pub alias: u64,
+}
+
+impl Decode for SubscribeError {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: u64::decode(r)?,
+			code: u64::decode(r)?,
+			reason: String::decode_limits(r, limits)?,
+			alias: u64::decode(r)?,
+		})
+	}
+}
+
+impl Encode for SubscribeError {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+		self.code.encode(w);
+		self.reason.encode(w);
+		self.alias.encode(w);
+	}
+}
+
+/// Sent by the publisher when a subscription has finished.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscribeDone {
+	pub id: u64,
+	pub code: u64,
+	pub reason: String,
+
+	/// The final location, if any content was delivered.
+	pub last: Option<Location>,
+}
+
+impl SubscribeDone {
+	/// The subscriber sent an [Unsubscribe].
+	pub const UNSUBSCRIBED: u64 = 0x0;
+
+	/// The publisher encountered an error.
+	pub const INTERNAL_ERROR: u64 = 0x1;
+
+	/// The track has ended.
+	pub const TRACK_ENDED: u64 = 0x3;
+}
+
+impl Decode for SubscribeDone {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		Ok(Self {
+			id: u64::decode(r)?,
+			code: u64::decode(r)?,
+			reason: String::decode_limits(r, limits)?,
+			last: Option::<Location>::decode(r)?,
+		})
+	}
+}
+
+impl Encode for SubscribeDone {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+		self.code.encode(w);
+		self.reason.encode(w);
+		self.last.encode(w);
+	}
+}
+
+/// Sent by the subscriber to cancel a subscription.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unsubscribe {
+	pub id: u64,
+}
+
+impl Decode for Unsubscribe {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self { id: u64::decode(r)? })
+	}
+}
+
+impl Encode for Unsubscribe {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+	}
+}
@@ -9,4 +9,6 @@
 //! It's currently super simple but will be expanded as Tokio becomes more of a hindrence.
 //!
 pub mod coding;
+pub mod ietf;
 pub mod message;
+pub mod wip;
@@ -10,23 +10,35 @@ enum AnnounceStatus {
 	Ended = 0,
 	Active = 1,
 	Live = 2,
+	Cursor = 3,
 }
 
 /// Sent by the publisher to announce the availability of a track.
 /// The payload contains the contents of the wildcard.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Announce {
-	Active(String),
-	Ended(String),
+	Active(Str),
+	Ended(Str),
 	Live,
+
+	/// The subscriber is caught up to this position in the publisher's announcements.
+	///
+	/// A publisher that honours [AnnouncePlease::cursor] echoes it back as the first message.
+	Cursor(AnnounceCursor),
 }
 
 impl Decode for Announce {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
 		Ok(match AnnounceStatus::decode(r)? {
-			AnnounceStatus::Active => Self::Active(String::decode(r)?),
-			AnnounceStatus::Ended => Self::Ended(String::decode(r)?),
+			AnnounceStatus::Active => Self::Active(Str::decode_limits(r, limits)?),
+			AnnounceStatus::Ended => Self::Ended(Str::decode_limits(r, limits)?),
 			AnnounceStatus::Live => Self::Live,
+			AnnounceStatus::Cursor => Self::Cursor(AnnounceCursor::decode_limits(r, limits)?),
 		})
 	}
 }
@@ -43,27 +55,107 @@ impl Encode for Announce {
 				capture.encode(w);
 			}
 			Self::Live => AnnounceStatus::Live.encode(w),
+			Self::Cursor(cursor) => {
+				AnnounceStatus::Cursor.encode(w);
+				cursor.encode(w);
+			}
 		}
 	}
 }
 
 /// Sent by the subscriber to request ANNOUNCE messages.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnnouncePlease {
 	/// A wildcard filter.
 	pub filter: Filter,
+
+	/// Resume from a previous announce stream, only receiving the changes since then.
+	///
+	/// Only encoded for [crate::message::Version::FORK_05] and later; earlier versions send the filter alone.
+	pub cursor: Option<AnnounceCursor>,
 }
 
 impl Decode for AnnouncePlease {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let filter = Filter::decode(r)?;
-		Ok(Self { filter })
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let filter = Filter::decode_limits(r, limits)?;
+		let cursor = AnnounceCursor::decode_limits(r, limits)?;
+		let cursor = (!cursor.is_empty()).then_some(cursor);
+
+		Ok(Self { filter, cursor })
 	}
 }
 
 impl Encode for AnnouncePlease {
 	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
-		self.filter.encode(w)
+		self.filter.encode(w);
+
+		match &self.cursor {
+			Some(cursor) => cursor.encode(w),
+			None => 0u64.encode(w),
+		}
+	}
+}
+
+/// A position within a publisher's announcements, one entry per announcement source.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnounceCursor(pub Vec<AnnounceEpoch>);
+
+impl AnnounceCursor {
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Returns the sequence number for the given source, if present.
+	pub fn get(&self, epoch: u64) -> Option<u64> {
+		self.0.iter().find(|e| e.id == epoch).map(|e| e.sequence)
+	}
+}
+
+impl Decode for AnnounceCursor {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		Ok(Self(Vec::decode_limits(r, limits)?))
+	}
+}
+
+impl Encode for AnnounceCursor {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.0.encode(w)
+	}
+}
+
+/// The number of changes made by a single announcement source.
+///
+/// The ID is randomly generated when the source is created, so a restarted publisher won't match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnnounceEpoch {
+	pub id: u64,
+	pub sequence: u64,
+}
+
+impl Decode for AnnounceEpoch {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		let id = u64::decode(r)?;
+		let sequence = u64::decode(r)?;
+
+		Ok(Self { id, sequence })
+	}
+}
+
+impl Encode for AnnounceEpoch {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.id.encode(w);
+		self.sequence.encode(w);
 	}
 }
 
@@ -74,6 +166,7 @@ impl Decode for AnnounceStatus {
 			0 => Ok(Self::Ended),
 			1 => Ok(Self::Active),
 			2 => Ok(Self::Live),
+			3 => Ok(Self::Cursor),
 			_ => Err(DecodeError::InvalidValue),
 		}
 	}
@@ -0,0 +1,35 @@
+use super::Extension;
+use crate::coding::*;
+
+/// A token presented by the client to authorize the session, ex. a JWT.
+///
+/// Sent as a setup extension; the server decides what the token permits.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Authorization {
+	pub token: String,
+}
+
+impl Extension for Authorization {
+	fn id() -> u64 {
+		0x61757468
+	}
+}
+
+impl Encode for Authorization {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.token.encode(w);
+	}
+}
+
+impl Decode for Authorization {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	/// The token is only bounded by [DecodeLimits::max_extension_size], as it may contain many claims.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let token = decode_string(r, limits.max_extension_size, DecodeError::ExtensionTooLarge)?;
+		Ok(Self { token })
+	}
+}
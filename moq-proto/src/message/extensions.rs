@@ -1,5 +1,4 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 
 use crate::coding::*;
 
@@ -8,21 +7,40 @@ pub trait Extension: Encode + Decode {
 }
 
 #[derive(Default, Debug, Clone)]
-pub struct Extensions(HashMap<u64, Vec<u8>>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
+pub struct Extensions(HashMap<u64, Bytes>);
 
 impl Decode for Extensions {
-	fn decode<R: bytes::Buf>(mut r: &mut R) -> Result<Self, DecodeError> {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
 		let mut map = HashMap::new();
 
 		// I hate this encoding so much; let me encode my role and get on with my life.
-		let count = u64::decode(r)?;
+		let count = usize::decode(r)?;
+		if count > limits.max_extensions {
+			return Err(DecodeError::TooManyExtensions(count));
+		}
+
 		for _ in 0..count {
 			let kind = u64::decode(r)?;
 			if map.contains_key(&kind) {
 				return Err(DecodeError::DupliateParameter);
 			}
 
-			let data = Vec::<u8>::decode(&mut r)?;
+			let size = usize::decode(r)?;
+			if size > limits.max_extension_size {
+				return Err(DecodeError::ExtensionTooLarge(size));
+			}
+
+			if r.remaining() < size {
+				return Err(DecodeError::Short);
+			}
+
+			// NOTE: This is zero-copy when decoding from Bytes.
+			let data = r.copy_to_bytes(size);
 			map.insert(kind, data);
 		}
 
@@ -42,19 +60,25 @@ impl Encode for Extensions {
 }
 
 impl Extensions {
+	/// Decode the extension if present.
+	///
+	/// The payload was already held to the session's [DecodeLimits::max_extension_size], so it's only bounded by its own size.
 	pub fn get<E: Extension>(&self) -> Result<Option<E>, DecodeError> {
 		Ok(match self.0.get(&E::id()) {
 			Some(payload) => {
-				let mut cursor = Cursor::new(payload);
-				Some(E::decode(&mut cursor)?)
+				let limits = DecodeLimits {
+					max_extension_size: payload.len(),
+					..Default::default()
+				};
+				Some(E::decode_limits(&mut payload.clone(), &limits)?)
 			}
 			None => None,
 		})
 	}
 
 	pub fn set<E: Extension>(&mut self, e: E) {
-		let mut value = Vec::new();
+		let mut value = BytesMut::new();
 		e.encode(&mut value);
-		self.0.insert(E::id(), value);
+		self.0.insert(E::id(), value.freeze());
 	}
 }
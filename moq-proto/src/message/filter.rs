@@ -1,8 +1,9 @@
 use std::fmt;
 
-use crate::coding::{Decode, DecodeError, Encode};
+use crate::coding::{decode_string, Decode, DecodeError, DecodeLimits, Encode};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Filter {
 	// Allow all paths.
 	Any,
@@ -126,7 +127,11 @@ impl Encode for Filter {
 
 impl Decode for Filter {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let pattern = String::decode(r)?;
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let pattern = decode_string(r, limits.max_path, DecodeError::PathTooLong)?;
 		Ok(Self::new(&pattern))
 	}
 }
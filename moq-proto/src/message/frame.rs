@@ -1,15 +1,23 @@
 use crate::coding::*;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Frame {
 	pub size: usize,
 }
 
 impl Decode for Frame {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		Ok(Self {
-			size: usize::decode(r)?,
-		})
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let size = usize::decode(r)?;
+		if size > limits.max_frame_size {
+			return Err(DecodeError::FrameTooLarge(size));
+		}
+
+		Ok(Self { size })
 	}
 }
 
@@ -1,6 +1,7 @@
 use crate::coding::*;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Group {
 	// The subscribe ID.
 	pub subscribe: u64,
@@ -27,6 +28,7 @@ impl Encode for Group {
 
 /// Indicates if groups should be delivered in ascending or descending order.
 #[derive(Clone, Debug, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GroupOrder {
 	Asc,
 	Desc,
@@ -53,6 +55,7 @@ impl Encode for GroupOrder {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GroupDrop {
 	pub sequence: u64,
 	pub count: u64,
@@ -2,6 +2,7 @@ use super::GroupOrder;
 use crate::coding::*;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Info {
 	pub priority: i8,
 	pub order: GroupOrder,
@@ -31,8 +32,9 @@ impl Decode for Info {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InfoRequest {
-	pub path: String,
+	pub path: Str,
 }
 
 impl Encode for InfoRequest {
@@ -43,7 +45,11 @@ impl Encode for InfoRequest {
 
 impl Decode for InfoRequest {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let path = String::decode(r)?;
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let path = Str::decode_limits(r, limits)?;
 		Ok(Self { path })
 	}
 }
@@ -2,6 +2,7 @@
 //!
 //! This module could be used directly but 99% of the time you should use the higher-level [crate::Session] API.
 mod announce;
+mod authorization;
 mod extensions;
 mod filter;
 mod frame;
@@ -11,9 +12,11 @@ mod session;
 mod setup;
 mod stream;
 mod subscribe;
+mod trace;
 mod versions;
 
 pub use announce::*;
+pub use authorization::*;
 pub use extensions::*;
 pub use filter::*;
 pub use frame::*;
@@ -23,4 +26,53 @@ pub use session::*;
 pub use setup::*;
 pub use stream::*;
 pub use subscribe::*;
+pub use trace::*;
 pub use versions::*;
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+	use super::*;
+
+	#[test]
+	fn serde() {
+		let subscribe = Subscribe {
+			id: 1,
+			path: "room/alice".into(),
+			priority: -1,
+			order: GroupOrder::Desc,
+			start: None,
+			end: None,
+		};
+
+		let json = serde_json::to_value(&subscribe).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({
+				"id": 1,
+				"path": "room/alice",
+				"priority": -1,
+				"order": "Desc",
+				"start": null,
+				"end": null,
+			})
+		);
+
+		let decoded: Subscribe = serde_json::from_value(json).unwrap();
+		assert_eq!(decoded.path, "room/alice");
+
+		let setup = ClientSetup {
+			versions: [Version::CURRENT].into(),
+			extensions: Default::default(),
+		};
+
+		let json = serde_json::to_value(&setup).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({ "versions": [0xff0bad05u64], "extensions": {} })
+		);
+
+		let announce = Announce::Active("alice".into());
+		let json = serde_json::to_string(&announce).unwrap();
+		assert_eq!(json, r#"{"Active":"alice"}"#);
+	}
+}
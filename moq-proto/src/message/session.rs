@@ -1,6 +1,7 @@
 use crate::coding::*;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SessionInfo {
 	pub bitrate: Option<u64>,
 }
@@ -3,6 +3,7 @@ use crate::coding::*;
 
 /// Sent by the client to setup the session.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClientSetup {
 	/// The list of supported versions in preferred order.
 	pub versions: Versions,
@@ -12,10 +13,14 @@ pub struct ClientSetup {
 }
 
 impl Decode for ClientSetup {
-	/// Decode a client setup message.
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let versions = Versions::decode(r)?;
-		let extensions = Extensions::decode(r)?;
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	/// Decode a client setup message.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let versions = Versions::decode_limits(r, limits)?;
+		let extensions = Extensions::decode_limits(r, limits)?;
 
 		Ok(Self { versions, extensions })
 	}
@@ -31,6 +36,7 @@ impl Encode for ClientSetup {
 
 /// Sent by the server in response to a client setup.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ServerSetup {
 	/// The list of supported versions in preferred order.
 	pub version: Version,
@@ -40,10 +46,14 @@ pub struct ServerSetup {
 }
 
 impl Decode for ServerSetup {
-	/// Decode the server setup.
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	/// Decode the server setup.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
 		let version = Version::decode(r)?;
-		let extensions = Extensions::decode(r)?;
+		let extensions = Extensions::decode_limits(r, limits)?;
 
 		Ok(Self { version, extensions })
 	}
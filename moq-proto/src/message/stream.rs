@@ -1,6 +1,7 @@
 use crate::coding::*;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ControlType {
 	Session,
 	Announce,
@@ -8,9 +9,10 @@ pub enum ControlType {
 	Info,
 }
 
-impl Decode for ControlType {
-	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let t = u64::decode(r)?;
+impl TryFrom<u64> for ControlType {
+	type Error = DecodeError;
+
+	fn try_from(t: u64) -> Result<Self, Self::Error> {
 		match t {
 			0 => Ok(Self::Session),
 			1 => Ok(Self::Announce),
@@ -21,6 +23,12 @@ impl Decode for ControlType {
 	}
 }
 
+impl Decode for ControlType {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		u64::decode(r)?.try_into()
+	}
+}
+
 impl Encode for ControlType {
 	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
 		let v: u64 = match self {
@@ -34,6 +42,7 @@ impl Encode for ControlType {
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DataType {
 	Group,
 }
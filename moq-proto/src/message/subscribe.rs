@@ -1,5 +1,5 @@
 use crate::{
-	coding::{Decode, DecodeError, Encode},
+	coding::{Decode, DecodeError, DecodeLimits, Encode, Str},
 	message::GroupOrder,
 };
 
@@ -7,9 +7,10 @@ use crate::{
 ///
 /// Objects will use the provided ID instead of the full track name, to save bytes.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Subscribe {
 	pub id: u64,
-	pub path: String,
+	pub path: Str,
 	pub priority: i8,
 	pub order: GroupOrder,
 
@@ -20,8 +21,12 @@ pub struct Subscribe {
 
 impl Decode for Subscribe {
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
 		let id = u64::decode(r)?;
-		let path = String::decode(r)?;
+		let path = Str::decode_limits(r, limits)?;
 		let priority = i8::decode(r)?;
 		let order = GroupOrder::decode(r)?;
 		let start = match u64::decode(r)? {
@@ -56,6 +61,7 @@ impl Encode for Subscribe {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SubscribeUpdate {
 	pub priority: i8,
 	pub order: GroupOrder,
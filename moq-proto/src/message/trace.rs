@@ -0,0 +1,47 @@
+use super::Extension;
+use crate::coding::*;
+
+/// W3C trace context, used to link spans across sessions.
+///
+/// Sent as a setup extension to indicate support, and after each [super::Subscribe] once both sides support it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceContext {
+	/// The `traceparent` header, or empty if there's no active trace.
+	pub parent: String,
+
+	/// The `tracestate` header, or empty.
+	pub state: String,
+}
+
+impl TraceContext {
+	pub fn is_empty(&self) -> bool {
+		self.parent.is_empty()
+	}
+}
+
+impl Extension for TraceContext {
+	fn id() -> u64 {
+		0x74726163
+	}
+}
+
+impl Encode for TraceContext {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.parent.encode(w);
+		self.state.encode(w);
+	}
+}
+
+impl Decode for TraceContext {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let parent = decode_string(r, limits.max_extension_size, DecodeError::ExtensionTooLarge)?;
+		let state = decode_string(r, limits.max_extension_size, DecodeError::ExtensionTooLarge)?;
+
+		Ok(Self { parent, state })
+	}
+}
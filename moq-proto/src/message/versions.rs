@@ -4,6 +4,7 @@ use std::{fmt, ops::Deref};
 
 /// A version number negotiated during the setup.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Version(u64);
 
 impl Version {
@@ -37,7 +38,10 @@ impl Version {
 	/// Unpublished: <https://kixelated.github.io/moq-drafts/draft-lcurley-moq-transfork.html>
 	pub const FORK_04: Version = Version(0xff0bad04);
 
-	pub const CURRENT: Version = Version::FORK_04;
+	/// Unpublished: FORK_04 with resumable announce streams, adding a cursor to ANNOUNCE_PLEASE.
+	pub const FORK_05: Version = Version(0xff0bad05);
+
+	pub const CURRENT: Version = Version::FORK_05;
 }
 
 impl From<u64> for Version {
@@ -74,13 +78,22 @@ impl fmt::Debug for Version {
 
 /// A list of versions in arbitrary order.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(transparent))]
 pub struct Versions(Vec<Version>);
 
 impl Decode for Versions {
-	/// Decode the version list.
 	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
-		let count = u64::decode(r)?;
-		let mut vs = Vec::new();
+		Self::decode_limits(r, &DecodeLimits::default())
+	}
+
+	/// Decode the version list.
+	fn decode_limits<R: bytes::Buf>(r: &mut R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+		let count = usize::decode(r)?;
+		if count > limits.max_versions {
+			return Err(DecodeError::TooManyVersions(count));
+		}
+
+		let mut vs = Vec::with_capacity(count);
 
 		for _ in 0..count {
 			let v = Version::decode(r)?;
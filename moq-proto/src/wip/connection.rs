@@ -1,43 +1,641 @@
-use std::collections::HashMap;
+use bytes::Buf;
 
-use crate::{StreamId, StreamKind};
+use crate::{
+	coding::{DecodeError, DecodeLimits},
+	message,
+};
 
-use super::{Publisher, Session, Subscriber};
+use super::{
+	Error, Publisher, PublisherEvent, PublisherState, Session, SessionEvent, Stream, StreamDir, StreamId, StreamKind,
+	Streams, Subscriber, SubscriberEvent, SubscriberState, Transmit,
+};
 
+#[derive(Clone, Debug)]
+pub enum Event {
+	Session(SessionEvent),
+	Publisher(PublisherEvent),
+	Subscriber(SubscriberEvent),
+}
+
+/// A sans-IO connection, driven by the caller.
+///
+/// Any error returned is fatal and the caller should close the connection with [Error::to_code].
+/// Errors on individual streams instead reset the stream and are surfaced as a closed event.
 pub struct Connection {
 	session: Session,
-	publisher: Publisher,
-	subscriber: Subscriber,
+	streams: Streams,
+	publisher: PublisherState,
+	subscriber: SubscriberState,
+
+	// Remote streams that can't be decoded until the version has been negotiated.
+	blocked: Vec<StreamId>,
 }
 
 impl Connection {
-	// Create a new client connection.
-	pub fn client() -> Self {
+	fn new(is_client: bool, limits: DecodeLimits) -> Self {
 		Self {
-			session: Session::new(true),
-			publisher: Publisher::default(),
-			subscriber: Subscriber::default(),
+			session: Session::new(is_client),
+			streams: Streams::new(is_client, limits),
+			publisher: PublisherState::default(),
+			subscriber: SubscriberState::default(),
+			blocked: Vec::new(),
 		}
 	}
 
+	// Create a new client connection, which immediately opens the session stream.
+	pub fn client() -> Self {
+		Self::client_with(DecodeLimits::default())
+	}
+
+	/// Create a new client connection, enforcing the limits on anything received from the server.
+	pub fn client_with(limits: DecodeLimits) -> Self {
+		let mut this = Self::new(true, limits);
+
+		let stream = this.streams.open(StreamDir::Bi, StreamKind::Session);
+		this.session.open(stream);
+
+		this
+	}
+
 	// Create a new server connection.
 	pub fn server() -> Self {
-		Self {
-			session: Session::new(false),
-			publisher: Publisher::default(),
-			subscriber: Subscriber::default(),
+		Self::server_with(DecodeLimits::default())
+	}
+
+	/// Create a new server connection, enforcing the limits on anything received from the client.
+	pub fn server_with(limits: DecodeLimits) -> Self {
+		Self::new(false, limits)
+	}
+
+	pub fn session(&self) -> &Session {
+		&self.session
+	}
+
+	pub fn publisher(&mut self) -> Publisher<'_> {
+		Publisher {
+			state: &mut self.publisher,
+			streams: &mut self.streams,
+		}
+	}
+
+	pub fn subscriber(&mut self) -> Subscriber<'_> {
+		Subscriber {
+			state: &mut self.subscriber,
+			streams: &mut self.streams,
+		}
+	}
+
+	/// The transport opened the stream requested by [Transmit::Open].
+	pub fn opened(&mut self, id: StreamId) -> Result<(), Error> {
+		self.streams.opened(id)
+	}
+
+	/// Data was received on a stream, implicitly accepting it if it was opened by the remote.
+	pub fn recv<B: Buf>(&mut self, id: StreamId, buf: &mut B) -> Result<(), Error> {
+		let stream = match self.streams.accept(id) {
+			Some(stream) => stream,
+			// The stream was already closed.
+			None => return Ok(()),
+		};
+
+		stream.recv(buf);
+
+		match self.process(id) {
+			Err(err) if self.is_session(id) => Err(err),
+			Err(err) => {
+				self.abort(id, err);
+				Ok(())
+			}
+			Ok(()) => Ok(()),
+		}
+	}
+
+	/// The remote finished its side of the stream.
+	pub fn finished(&mut self, id: StreamId) -> Result<(), Error> {
+		match self.streams.get(id) {
+			Some(stream) => stream.recv_fin(),
+			None => return Ok(()),
+		}
+
+		match self.blocked.contains(&id) {
+			// Handled once the stream is unblocked.
+			true => Ok(()),
+			false => self.process_fin(id),
+		}
+	}
+
+	/// The remote reset or stopped the stream, which is reset in return.
+	pub fn reset(&mut self, id: StreamId, code: super::ErrorCode) -> Result<(), Error> {
+		let stream = match self.streams.get(id) {
+			Some(stream) => stream,
+			None => return Ok(()),
+		};
+
+		stream.reset(code);
+		self.blocked.retain(|blocked| *blocked != id);
+
+		match stream.kind {
+			Some(StreamKind::Session) => return Err(Error::Closed),
+			Some(kind) if Self::is_publisher(&self.session, id, kind) => self.publisher.closed(id, Some(code)),
+			Some(_) => self.subscriber.closed(id, Some(code)),
+			None => {}
+		}
+
+		Ok(())
+	}
+
+	/// Return the next action the transport needs to perform.
+	pub fn poll_transmit(&mut self) -> Option<Transmit> {
+		self.streams.poll_transmit()
+	}
+
+	/// Return the next event for the application.
+	pub fn poll(&mut self) -> Option<Event> {
+		if let Some(event) = self.session.poll() {
+			return Some(Event::Session(event));
+		}
+
+		if let Some(event) = self.publisher.poll() {
+			return Some(Event::Publisher(event));
+		}
+
+		self.subscriber.poll().map(Event::Subscriber)
+	}
+
+	fn process(&mut self, id: StreamId) -> Result<(), Error> {
+		let stream = self.streams.get(id).ok_or(Error::UnknownStream)?;
+
+		let kind = match stream.kind {
+			Some(kind) => kind,
+			None => match Self::decode_kind(stream)? {
+				Some(kind) => {
+					stream.kind = Some(kind);
+					if kind == StreamKind::Session {
+						self.session.accept(stream)?;
+					}
+
+					kind
+				}
+				None => return Ok(()),
+			},
+		};
+
+		match kind {
+			StreamKind::Session => {
+				let connected = self.session.version().is_some();
+				self.session.recv(stream)?;
+
+				match self.session.version() {
+					Some(version) if !connected => self.connected(version),
+					_ => Ok(()),
+				}
+			}
+			_ if self.session.version().is_none() => {
+				if !self.blocked.contains(&id) {
+					self.blocked.push(id);
+				}
+
+				Ok(())
+			}
+			kind if Self::is_publisher(&self.session, id, kind) => self.publisher.recv(stream),
+			_ => self.subscriber.recv(stream),
+		}
+	}
+
+	// Process the remote finishing a stream, after any data has been processed.
+	fn process_fin(&mut self, id: StreamId) -> Result<(), Error> {
+		let stream = match self.streams.get(id) {
+			Some(stream) => stream,
+			None => return Ok(()),
+		};
+
+		let res = match stream.kind {
+			Some(StreamKind::Session) => return Err(Error::Closed),
+			Some(kind) if Self::is_publisher(&self.session, id, kind) => {
+				self.publisher.finished(stream);
+				Ok(())
+			}
+			Some(_) => self.subscriber.finished(stream),
+			None => Err(DecodeError::Short.into()),
+		};
+
+		if let Err(err) = res {
+			self.abort(id, err);
+		}
+
+		Ok(())
+	}
+
+	// The setup completed, so process any streams that arrived before it.
+	fn connected(&mut self, version: message::Version) -> Result<(), Error> {
+		let cursor = version >= message::Version::FORK_05;
+		self.publisher.connected(cursor);
+		self.subscriber.connected(&mut self.streams, cursor);
+
+		for id in std::mem::take(&mut self.blocked) {
+			match self.process(id) {
+				Ok(()) if self.streams.get(id).is_some_and(|stream| stream.is_finished()) => self.process_fin(id)?,
+				Ok(()) => {}
+				Err(err) => self.abort(id, err),
+			}
+		}
+
+		Ok(())
+	}
+
+	// Decode the type of a stream opened by the remote.
+	fn decode_kind(stream: &mut Stream) -> Result<Option<StreamKind>, Error> {
+		let kind = match stream.id.dir() {
+			StreamDir::Bi => stream.decode::<message::ControlType>()?.map(|kind| match kind {
+				message::ControlType::Session => StreamKind::Session,
+				message::ControlType::Announce => StreamKind::Announce,
+				message::ControlType::Subscribe => StreamKind::Subscribe,
+				message::ControlType::Info => StreamKind::Info,
+			}),
+			StreamDir::Uni => stream.decode::<message::DataType>()?.map(|kind| match kind {
+				message::DataType::Group => StreamKind::Group,
+			}),
+		};
+
+		Ok(kind)
+	}
+
+	fn is_session(&self, id: StreamId) -> bool {
+		self.session.stream() == Some(id)
+	}
+
+	// Requests are made by the subscriber, while groups are opened by the publisher.
+	fn is_publisher(session: &Session, id: StreamId, kind: StreamKind) -> bool {
+		let local = id.is_client() == session.is_client();
+
+		match kind {
+			StreamKind::Group => local,
+			_ => !local,
 		}
 	}
 
-	pub fn session(&mut self) -> &mut Session {
-		&mut self.session
+	// Reset a stream because of an error, notifying the application.
+	fn abort(&mut self, id: StreamId, err: Error) {
+		let code = err.to_code();
+
+		let stream = match self.streams.get(id) {
+			Some(stream) => stream,
+			None => return,
+		};
+
+		stream.reset(code);
+
+		if let Some(kind) = stream.kind {
+			match Self::is_publisher(&self.session, id, kind) {
+				true => self.publisher.closed(id, Some(code)),
+				false => self.subscriber.closed(id, Some(code)),
+			}
+		}
 	}
+}
+
+#[cfg(test)]
+mod test {
+	use bytes::Bytes;
+
+	use super::*;
+	use crate::{coding::Encode, wip::*};
+
+	// Deliver everything queued by one side to the other.
+	fn deliver(from: &mut Connection, to: &mut Connection) -> bool {
+		let mut progress = false;
 
-	pub fn publisher(&mut self) -> &mut Publisher {
-		&mut self.publisher
+		while let Some(transmit) = from.poll_transmit() {
+			progress = true;
+
+			match transmit {
+				Transmit::Open(id) => from.opened(id).unwrap(),
+				Transmit::Write { id, mut data, fin } => {
+					to.recv(id, &mut data).unwrap();
+					if fin {
+						to.finished(id).unwrap();
+					}
+				}
+				Transmit::Reset { id, code } => to.reset(id, code).unwrap(),
+			}
+		}
+
+		progress
+	}
+
+	fn run(client: &mut Connection, server: &mut Connection) {
+		while deliver(client, server) | deliver(server, client) {}
 	}
 
-	pub fn subscriber(&mut self) -> &mut Subscriber {
-		&mut self.subscriber
+	fn events(conn: &mut Connection) -> Vec<Event> {
+		std::iter::from_fn(|| conn.poll()).collect()
+	}
+
+	fn setup() -> (Connection, Connection) {
+		let mut client = Connection::client();
+		let mut server = Connection::server();
+		run(&mut client, &mut server);
+
+		for conn in [&mut client, &mut server] {
+			let events = events(conn);
+			assert!(matches!(
+				events[..],
+				[Event::Session(SessionEvent::Connected {
+					version: message::Version::CURRENT
+				})]
+			));
+		}
+
+		(client, server)
+	}
+
+	#[test]
+	fn version() {
+		let mut server = Connection::server();
+
+		let mut buf = Vec::new();
+		message::ControlType::Session.encode(&mut buf);
+		message::ClientSetup {
+			versions: [message::Version::DRAFT_00].into(),
+			extensions: Default::default(),
+		}
+		.encode(&mut buf);
+
+		let err = server.recv(StreamId::new(true, StreamDir::Bi, 0), &mut &buf[..]);
+		assert!(matches!(err, Err(Error::Version(_))));
+	}
+
+	#[test]
+	fn downgrade() {
+		// A client that only supports FORK_04, whose request arrives before the setup.
+		let mut server = Connection::server();
+
+		let session = StreamId::new(true, StreamDir::Bi, 0);
+		let mut buf = Vec::new();
+		message::ControlType::Session.encode(&mut buf);
+		server.recv(session, &mut &buf[..]).unwrap();
+
+		let mut buf = Vec::new();
+		message::ControlType::Announce.encode(&mut buf);
+		message::Filter::new("foo/*").encode(&mut buf);
+
+		let request = StreamId::new(true, StreamDir::Bi, 1);
+		server.recv(request, &mut &buf[..]).unwrap();
+		assert!(events(&mut server).is_empty());
+
+		let mut buf = Vec::new();
+		message::ClientSetup {
+			versions: [message::Version::FORK_04].into(),
+			extensions: Default::default(),
+		}
+		.encode(&mut buf);
+
+		server.recv(session, &mut &buf[..]).unwrap();
+		assert!(matches!(
+			&events(&mut server)[..],
+			[
+				Event::Session(SessionEvent::Connected { version: message::Version::FORK_04 }),
+				Event::Publisher(PublisherEvent::Announce(id, message::AnnouncePlease {
+					filter: message::Filter::Prefix(prefix),
+					cursor: None,
+				})),
+			] if *id == request && prefix == "foo/"
+		));
+
+		// A client that requests announcements before the server picks FORK_04.
+		let mut client = Connection::client();
+		let stream = client.subscriber().announce(message::AnnouncePlease {
+			filter: message::Filter::new("foo/*"),
+			cursor: Some(Default::default()),
+		});
+
+		let mut buf = Vec::new();
+		message::ServerSetup {
+			version: message::Version::FORK_04,
+			extensions: Default::default(),
+		}
+		.encode(&mut buf);
+
+		client
+			.recv(StreamId::new(true, StreamDir::Bi, 0), &mut &buf[..])
+			.unwrap();
+		assert!(matches!(
+			events(&mut client)[..],
+			[Event::Session(SessionEvent::Connected {
+				version: message::Version::FORK_04
+			})]
+		));
+
+		let mut sent = Vec::new();
+		while let Some(transmit) = client.poll_transmit() {
+			match transmit {
+				Transmit::Open(id) => client.opened(id).unwrap(),
+				Transmit::Write { id, data, .. } if id == stream => sent.extend_from_slice(&data),
+				_ => {}
+			}
+		}
+
+		// The filter is sent alone, without the cursor.
+		let mut expected = Vec::new();
+		message::ControlType::Announce.encode(&mut expected);
+		message::Filter::new("foo/*").encode(&mut expected);
+		assert_eq!(sent, expected);
+	}
+
+	#[test]
+	fn announce() {
+		let (mut client, mut server) = setup();
+
+		let stream = client.subscriber().announce(message::AnnouncePlease {
+			filter: message::Filter::new("foo/*"),
+			cursor: None,
+		});
+		run(&mut client, &mut server);
+
+		let id = match &events(&mut server)[..] {
+			[Event::Publisher(PublisherEvent::Announce(id, _))] => *id,
+			events => panic!("unexpected events: {:?}", events),
+		};
+
+		let mut publisher = server.publisher();
+		let mut announce = publisher.announce(id).unwrap();
+		announce.active("bar").unwrap();
+		announce.live().unwrap();
+		run(&mut client, &mut server);
+
+		let received = events(&mut client);
+		assert!(matches!(
+			&received[..],
+			[
+				Event::Subscriber(SubscriberEvent::Announced(a, message::Announce::Active(path))),
+				Event::Subscriber(SubscriberEvent::Announced(b, message::Announce::Live)),
+			] if *a == stream && *b == stream && path == "bar"
+		));
+
+		client.subscriber().announced(stream).unwrap().close(None).unwrap();
+		run(&mut client, &mut server);
+
+		assert!(matches!(
+			events(&mut server)[..],
+			[Event::Publisher(PublisherEvent::AnnounceClosed(id, None))] if id == stream
+		));
+	}
+
+	#[test]
+	fn subscribe() {
+		let (mut client, mut server) = setup();
+
+		let subscribe = client.subscriber().subscribe(SubscribeRequest {
+			path: "foo".to_string(),
+			priority: 0,
+			order: message::GroupOrder::Desc,
+		});
+		run(&mut client, &mut server);
+
+		assert!(matches!(
+			&events(&mut server)[..],
+			[Event::Publisher(PublisherEvent::Subscribe(id, msg))] if *id == subscribe && msg.path == "foo"
+		));
+
+		let mut publisher = server.publisher();
+		publisher
+			.subscribe(subscribe)
+			.unwrap()
+			.reply(message::Info {
+				priority: 0,
+				order: message::GroupOrder::Desc,
+				latest: 0,
+			})
+			.unwrap();
+		run(&mut client, &mut server);
+
+		assert!(matches!(
+			events(&mut client)[..],
+			[Event::Subscriber(SubscriberEvent::Subscribed(id, _))] if id == subscribe
+		));
+
+		let mut publisher = server.publisher();
+		let group = publisher.subscribe(subscribe).unwrap().group(GroupId(0));
+		let mut writer = publisher.group(group).unwrap();
+		writer.write_frame(Bytes::from_static(b"hello")).unwrap();
+		writer.write_frame(Bytes::new()).unwrap();
+		writer.close(None).unwrap();
+		run(&mut client, &mut server);
+
+		let received = events(&mut client);
+		assert!(matches!(
+			&received[..],
+			[
+				Event::Subscriber(SubscriberEvent::Group(a, sub, GroupId(0))),
+				Event::Subscriber(SubscriberEvent::Frame(b, first)),
+				Event::Subscriber(SubscriberEvent::Frame(c, second)),
+				Event::Subscriber(SubscriberEvent::GroupClosed(d, None)),
+			] if *sub == subscribe
+				&& *a == group && *b == group && *c == group && *d == group
+				&& first == "hello" && second.is_empty()
+		));
+
+		client
+			.subscriber()
+			.subscribed(subscribe)
+			.unwrap()
+			.close(Some(ErrorCode(1)))
+			.unwrap();
+		run(&mut client, &mut server);
+
+		assert!(matches!(
+			events(&mut server)[..],
+			[Event::Publisher(PublisherEvent::SubscribeClosed(id, Some(ErrorCode(1))))] if id == subscribe
+		));
+		assert!(server.publisher().subscribe(subscribe).is_none());
+	}
+
+	#[test]
+	fn info() {
+		let (mut client, mut server) = setup();
+
+		let stream = client.subscriber().info(message::InfoRequest { path: "foo".into() });
+		run(&mut client, &mut server);
+
+		let id = match &events(&mut server)[..] {
+			[Event::Publisher(PublisherEvent::Info(id, _))] => *id,
+			events => panic!("unexpected events: {:?}", events),
+		};
+
+		server
+			.publisher()
+			.info(id)
+			.unwrap()
+			.reply(message::Info {
+				priority: 1,
+				order: message::GroupOrder::Asc,
+				latest: 2,
+			})
+			.unwrap();
+		run(&mut client, &mut server);
+
+		assert!(matches!(
+			&events(&mut client)[..],
+			[
+				Event::Subscriber(SubscriberEvent::Info(a, message::Info { latest: 2, .. })),
+				Event::Subscriber(SubscriberEvent::InfoClosed(b, None)),
+			] if *a == stream && *b == stream
+		));
+	}
+
+	#[test]
+	fn limits() {
+		let mut client = Connection::client();
+		let mut server = Connection::server_with(DecodeLimits {
+			max_path: 4,
+			..Default::default()
+		});
+		run(&mut client, &mut server);
+		events(&mut client);
+		events(&mut server);
+
+		let subscribe = client.subscriber().subscribe(SubscribeRequest {
+			path: "too long".to_string(),
+			priority: 0,
+			order: message::GroupOrder::Desc,
+		});
+		run(&mut client, &mut server);
+
+		// The server resets the stream rather than surfacing the request.
+		assert!(events(&mut server).is_empty());
+		assert!(matches!(
+			events(&mut client)[..],
+			[Event::Subscriber(SubscriberEvent::SubscribeClosed(id, Some(code)))]
+				if id == subscribe && code == Error::from(DecodeError::PathTooLong(8)).to_code()
+		));
+	}
+
+	#[test]
+	fn truncated() {
+		let (mut client, mut server) = setup();
+
+		let subscribe = client.subscriber().subscribe(SubscribeRequest {
+			path: "foo".to_string(),
+			priority: 0,
+			order: message::GroupOrder::Desc,
+		});
+		run(&mut client, &mut server);
+		events(&mut server);
+
+		// Promise a larger frame than is written before finishing the stream.
+		let group = server.publisher().subscribe(subscribe).unwrap().group(GroupId(3));
+		let stream = server.streams.get(group).unwrap();
+		stream.encode(&message::Frame { size: 10 });
+		stream.write(b"short");
+		stream.finish();
+		run(&mut client, &mut server);
+
+		assert!(matches!(
+			events(&mut client)[..],
+			[
+				Event::Subscriber(SubscriberEvent::Group(_, _, GroupId(3))),
+				Event::Subscriber(SubscriberEvent::GroupClosed(id, Some(code))),
+			] if id == group && code == Error::WrongSize.to_code()
+		));
 	}
 }
@@ -1,14 +1,17 @@
 use derive_more::{From, Into};
 
-use crate::coding;
+use crate::{coding, message};
 
 #[derive(Clone, Debug, thiserror::Error)]
 pub enum Error {
 	#[error("decode error: {0}")]
 	Coding(#[from] coding::DecodeError),
 
-	#[error("stream closed: {0}")]
-	Closed(u8),
+	#[error("unsupported versions: {0:?}")]
+	Version(message::Versions),
+
+	#[error("session closed")]
+	Closed,
 
 	#[error("unknown stream")]
 	UnknownStream,
@@ -16,12 +19,39 @@ pub enum Error {
 	#[error("duplicate stream")]
 	DuplicateStream,
 
-	#[error("wrong stream type")]
-	WrongStreamType,
+	#[error("unexpected stream ID")]
+	UnexpectedStream,
+
+	#[error("unknown subscribe")]
+	UnknownSubscribe,
+
+	#[error("duplicate subscribe")]
+	DuplicateSubscribe,
+
+	#[error("wrong frame size")]
+	WrongSize,
+
+	#[error("protocol violation")]
+	ProtocolViolation,
+}
 
-	#[error("poisoned")]
-	Poisoned,
+impl Error {
+	/// An integer code that is sent over the wire, matching moq-transfork.
+	pub fn to_code(&self) -> ErrorCode {
+		let code = match self {
+			Self::Closed => 0,
+			Self::Coding(_) => 5,
+			Self::Version(_) => 9,
+			Self::UnknownStream | Self::DuplicateStream | Self::UnexpectedStream => 10,
+			Self::DuplicateSubscribe => 12,
+			Self::UnknownSubscribe => 13,
+			Self::WrongSize => 14,
+			Self::ProtocolViolation => 15,
+		};
+
+		ErrorCode(code)
+	}
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, From, Into, PartialOrd, Ord)]
-pub struct ErrorCode(u32);
+pub struct ErrorCode(pub u32);
@@ -1,5 +1,7 @@
 use derive_more::{From, Into};
 
+use super::StreamDir;
+
 macro_rules! create_id {
 	($($name:ident),*,) => {
 		$(
@@ -18,8 +20,31 @@ macro_rules! create_id {
 // Create a wrapper around a u64 for more type safety.
 // You can get the underlying u64 via From/Into.
 create_id! {
-	AnnounceId,
 	SubscribeId,
 	GroupId,
 	StreamId,
 }
+
+// QUIC stream IDs encode the initiator and direction in the lowest two bits.
+impl StreamId {
+	pub fn new(is_client: bool, dir: StreamDir, index: u64) -> Self {
+		let initiator = if is_client { 0 } else { 1 };
+		let dir = match dir {
+			StreamDir::Bi => 0,
+			StreamDir::Uni => 2,
+		};
+
+		Self(index << 2 | dir | initiator)
+	}
+
+	pub fn dir(&self) -> StreamDir {
+		match self.0 & 0x2 {
+			0 => StreamDir::Bi,
+			_ => StreamDir::Uni,
+		}
+	}
+
+	pub fn is_client(&self) -> bool {
+		self.0 & 0x1 == 0
+	}
+}
@@ -0,0 +1,30 @@
+//! A runtime-agnostic (sans-IO) implementation of the protocol.
+//!
+//! A [Connection] is a poll-based state machine that performs no I/O itself.
+//! The caller feeds it stream data via [Connection::recv], sends anything returned by [Connection::poll_transmit],
+//! and reacts to anything returned by [Connection::poll].
+//! This makes it possible to embed in any event loop and to test deterministically.
+//!
+//! The [quinn] module contains a driver for [quinn_proto], enabled with the `quinn` feature.
+//!
+//! WARNING: This is a work in progress and the API will change.
+mod connection;
+mod error;
+mod id;
+mod publisher;
+mod session;
+mod stream;
+mod subscriber;
+mod types;
+
+#[cfg(feature = "quinn")]
+pub mod quinn;
+
+pub use connection::*;
+pub use error::*;
+pub use id::*;
+pub use publisher::*;
+pub use session::*;
+pub use stream::*;
+pub use subscriber::*;
+pub use types::*;
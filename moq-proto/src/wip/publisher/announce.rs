@@ -1,77 +1,56 @@
-use std::collections::{BTreeSet, HashMap, VecDeque};
+use crate::message;
 
-use bytes::{Buf, BufMut};
+use super::PublisherState;
+use crate::wip::{Error, ErrorCode, StreamId, Streams};
 
-use crate::{
-	coding::{Decode, Encode},
-	message, AnnounceId, Error, StreamId,
-};
-
-#[derive(Default)]
-pub struct PublisherAnnounces {
-	lookup: HashMap<StreamId, PublisherAnnounce>,
-	ready: BTreeSet<StreamId>,
+/// A request for announcements matching a filter.
+pub struct PublisherAnnounce<'a> {
+	id: StreamId,
+	state: &'a mut PublisherState,
+	streams: &'a mut Streams,
 }
 
-impl PublisherAnnounces {
-	pub fn accept(&mut self) -> Option<&mut PublisherAnnounce> {
-		let id = self.ready.pop_first()?;
-		self.get(id)
+impl<'a> PublisherAnnounce<'a> {
+	pub(super) fn new(id: StreamId, state: &'a mut PublisherState, streams: &'a mut Streams) -> Self {
+		Self { id, state, streams }
 	}
 
-	pub fn get(&mut self, id: StreamId) -> Option<&mut PublisherAnnounce> {
-		self.lookup.get_mut(&id)
+	pub fn id(&self) -> StreamId {
+		self.id
 	}
-}
-
-pub struct PublisherAnnounce {
-	stream: StreamId,
-	request: message::AnnouncePlease,
-	events: VecDeque<message::Announce>,
-	live: bool,
-}
 
-impl PublisherAnnounce {
-	fn new(stream: StreamId, request: message::AnnouncePlease) -> Self {
-		Self {
-			stream,
-			request,
-			events: VecDeque::new(),
-			live: false,
-		}
+	fn reply(&mut self, msg: message::Announce) -> Result<(), Error> {
+		self.streams.get(self.id).ok_or(Error::UnknownStream)?.encode(&msg);
+		Ok(())
 	}
 
-	pub fn encode<B: BufMut>(&mut self, buf: &mut B) {
-		while let Some(event) = self.events.pop_front() {
-			event.encode(buf);
-		}
+	/// A broadcast matching the filter is now available.
+	pub fn active(&mut self, suffix: &str) -> Result<(), Error> {
+		self.reply(message::Announce::Active(suffix.into()))
 	}
 
-	pub fn stream(&self) -> StreamId {
-		self.stream
+	/// A previously active broadcast is no longer available.
+	pub fn ended(&mut self, suffix: &str) -> Result<(), Error> {
+		self.reply(message::Announce::Ended(suffix.into()))
 	}
 
-	pub fn info(&mut self) -> &message::AnnouncePlease {
-		&self.request
+	/// All existing broadcasts have been announced; anything that follows is a live update.
+	pub fn live(&mut self) -> Result<(), Error> {
+		self.reply(message::Announce::Live)
 	}
 
-	fn reply(&mut self, msg: message::Announce) {
-		self.events.push_back(msg);
-	}
-
-	pub fn active(&mut self, path: &str) {
-		// TODO: assert! !active
-		self.reply(message::Announce::Active(path.to_string()));
-	}
-
-	pub fn ended(&mut self, path: &str) {
-		// TODO: assert! active
-		self.reply(message::Announce::Ended(path.to_string()));
+	/// Echo the position in the announcements, used by the subscriber to resume.
+	///
+	/// This is a no-op if the negotiated version doesn't support resuming.
+	pub fn cursor(&mut self, cursor: message::AnnounceCursor) -> Result<(), Error> {
+		match self.state.cursor {
+			true => self.reply(message::Announce::Cursor(cursor)),
+			false => Ok(()),
+		}
 	}
 
-	pub fn live(&mut self) {
-		assert!(!self.live);
-		self.live = true;
-		self.reply(message::Announce::Live);
+	/// Close the stream, resetting it if there's an error code.
+	pub fn close(self, code: Option<ErrorCode>) -> Result<(), Error> {
+		self.state.close(self.streams, self.id, code)
 	}
 }
@@ -0,0 +1,32 @@
+use crate::message;
+
+use super::PublisherState;
+use crate::wip::{Error, ErrorCode, StreamId, Streams};
+
+/// A request for information about a track.
+pub struct PublisherInfo<'a> {
+	id: StreamId,
+	state: &'a mut PublisherState,
+	streams: &'a mut Streams,
+}
+
+impl<'a> PublisherInfo<'a> {
+	pub(super) fn new(id: StreamId, state: &'a mut PublisherState, streams: &'a mut Streams) -> Self {
+		Self { id, state, streams }
+	}
+
+	pub fn id(&self) -> StreamId {
+		self.id
+	}
+
+	/// Reply with the track information and finish the stream.
+	pub fn reply(self, info: message::Info) -> Result<(), Error> {
+		self.streams.get(self.id).ok_or(Error::UnknownStream)?.encode(&info);
+		self.state.close(self.streams, self.id, None)
+	}
+
+	/// Reject the request, usually because the track was not found.
+	pub fn close(self, code: ErrorCode) -> Result<(), Error> {
+		self.state.close(self.streams, self.id, Some(code))
+	}
+}
@@ -1,28 +1,216 @@
 mod announce;
 mod group;
+mod info;
 mod subscribe;
 
 pub use announce::*;
 pub use group::*;
+pub use info::*;
 pub use subscribe::*;
 
-#[derive(Default)]
-pub struct Publisher {
-	announces: PublisherAnnounces,
-	subscribes: PublisherSubscribes,
-	groups: PublisherGroups,
+use std::collections::{HashMap, VecDeque};
+
+use crate::message;
+
+use super::{Error, ErrorCode, Stream, StreamId, StreamKind, Streams, SubscribeId};
+
+#[derive(Clone, Debug)]
+pub enum PublisherEvent {
+	/// The subscriber wants to discover broadcasts matching the filter.
+	Announce(StreamId, message::AnnouncePlease),
+
+	/// The subscriber wants to receive a track.
+	Subscribe(SubscribeId, message::Subscribe),
+
+	/// The subscriber changed the priority or ordering of a subscription.
+	SubscribeUpdate(SubscribeId, message::SubscribeUpdate),
+
+	/// The subscriber wants information about a track.
+	Info(StreamId, message::InfoRequest),
+
+	/// The subscriber closed the announce stream, with an error code if it was reset.
+	AnnounceClosed(StreamId, Option<ErrorCode>),
+
+	/// The subscriber closed the subscription, with an error code if it was reset.
+	SubscribeClosed(SubscribeId, Option<ErrorCode>),
+
+	/// The subscriber stopped the group stream.
+	GroupClosed(StreamId, ErrorCode),
+}
+
+#[derive(Debug)]
+enum PublisherStream {
+	Announce,
+	Subscribe(SubscribeId),
+	Info,
+	Group,
 }
 
-impl Publisher {
-	pub fn announces(&mut self) -> &mut PublisherAnnounces {
-		&mut self.announces
+#[derive(Debug, Default)]
+pub(super) struct PublisherState {
+	streams: HashMap<StreamId, PublisherStream>,
+	subscribes: HashMap<SubscribeId, StreamId>,
+	events: VecDeque<PublisherEvent>,
+
+	// True if the negotiated version supports resuming announcements.
+	cursor: bool,
+}
+
+impl PublisherState {
+	/// The setup completed with the negotiated version.
+	pub fn connected(&mut self, cursor: bool) {
+		self.cursor = cursor;
+	}
+
+	pub fn recv(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		match self.streams.get(&stream.id) {
+			None => self.recv_request(stream),
+			Some(PublisherStream::Subscribe(id)) => {
+				let id = *id;
+
+				while let Some(update) = stream.decode()? {
+					self.events.push_back(PublisherEvent::SubscribeUpdate(id, update));
+				}
+
+				Ok(())
+			}
+			// Nothing else is expected after the request.
+			Some(_) if stream.has_data() => Err(Error::ProtocolViolation),
+			Some(_) => Ok(()),
+		}
+	}
+
+	fn recv_request(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		let id = stream.id;
+
+		let (state, event) = match stream.kind {
+			Some(StreamKind::Announce) => {
+				let request = match self.cursor {
+					true => stream.decode()?,
+					// Older versions send the filter alone.
+					false => stream
+						.decode()?
+						.map(|filter| message::AnnouncePlease { filter, cursor: None }),
+				};
+
+				match request {
+					Some(request) => (PublisherStream::Announce, PublisherEvent::Announce(id, request)),
+					None => return Ok(()),
+				}
+			}
+			Some(StreamKind::Subscribe) => match stream.decode::<message::Subscribe>()? {
+				Some(request) => {
+					let subscribe = SubscribeId(request.id);
+					if self.subscribes.contains_key(&subscribe) {
+						return Err(Error::DuplicateSubscribe);
+					}
+
+					self.subscribes.insert(subscribe, id);
+					(
+						PublisherStream::Subscribe(subscribe),
+						PublisherEvent::Subscribe(subscribe, request),
+					)
+				}
+				None => return Ok(()),
+			},
+			Some(StreamKind::Info) => match stream.decode()? {
+				Some(request) => (PublisherStream::Info, PublisherEvent::Info(id, request)),
+				None => return Ok(()),
+			},
+			_ => return Err(Error::ProtocolViolation),
+		};
+
+		self.streams.insert(id, state);
+		self.events.push_back(event);
+
+		// Decode anything that was received along with the request.
+		self.recv(stream)
+	}
+
+	/// The subscriber finished its side of the stream.
+	pub fn finished(&mut self, stream: &mut Stream) {
+		match self.streams.get(&stream.id) {
+			// The request is complete but we still need to reply.
+			Some(PublisherStream::Info) => {}
+			_ => {
+				self.closed(stream.id, None);
+				stream.finish();
+			}
+		}
+	}
+
+	/// The subscriber closed the stream, with an error code if it was reset.
+	pub fn closed(&mut self, id: StreamId, code: Option<ErrorCode>) {
+		let event = match self.streams.remove(&id) {
+			Some(PublisherStream::Announce) => PublisherEvent::AnnounceClosed(id, code),
+			Some(PublisherStream::Subscribe(subscribe)) => {
+				self.subscribes.remove(&subscribe);
+				PublisherEvent::SubscribeClosed(subscribe, code)
+			}
+			Some(PublisherStream::Group) => match code {
+				Some(code) => PublisherEvent::GroupClosed(id, code),
+				None => return,
+			},
+			Some(PublisherStream::Info) | None => return,
+		};
+
+		self.events.push_back(event);
+	}
+
+	pub fn poll(&mut self) -> Option<PublisherEvent> {
+		self.events.pop_front()
+	}
+
+	/// Close the stream locally, resetting it if there's an error code.
+	fn close(&mut self, streams: &mut Streams, id: StreamId, code: Option<ErrorCode>) -> Result<(), Error> {
+		if let Some(PublisherStream::Subscribe(subscribe)) = self.streams.remove(&id) {
+			self.subscribes.remove(&subscribe);
+		}
+
+		let stream = streams.get(id).ok_or(Error::UnknownStream)?;
+		match code {
+			Some(code) => stream.reset(code),
+			None => stream.finish(),
+		}
+
+		Ok(())
+	}
+}
+
+/// The publisher half of a [super::Connection], used to respond to [PublisherEvent]s.
+pub struct Publisher<'a> {
+	pub(super) state: &'a mut PublisherState,
+	pub(super) streams: &'a mut Streams,
+}
+
+impl Publisher<'_> {
+	/// Return the announce stream, if it's still active.
+	pub fn announce(&mut self, id: StreamId) -> Option<PublisherAnnounce<'_>> {
+		match self.state.streams.get(&id)? {
+			PublisherStream::Announce => Some(PublisherAnnounce::new(id, self.state, self.streams)),
+			_ => None,
+		}
+	}
+
+	/// Return the subscription, if it's still active.
+	pub fn subscribe(&mut self, id: SubscribeId) -> Option<PublisherSubscribe<'_>> {
+		let stream = *self.state.subscribes.get(&id)?;
+		Some(PublisherSubscribe::new(id, stream, self.state, self.streams))
 	}
 
-	pub fn subscribes(&mut self) -> &mut PublisherSubscribes {
-		&mut self.subscribes
+	/// Return the group stream, if it's still active.
+	pub fn group(&mut self, id: StreamId) -> Option<PublisherGroup<'_>> {
+		match self.state.streams.get(&id)? {
+			PublisherStream::Group => Some(PublisherGroup::new(id, self.state, self.streams)),
+			_ => None,
+		}
 	}
 
-	pub fn groups(&mut self) -> &mut PublisherGroups {
-		&mut self.groups
+	/// Return the info stream, if it's still awaiting a reply.
+	pub fn info(&mut self, id: StreamId) -> Option<PublisherInfo<'_>> {
+		match self.state.streams.get(&id)? {
+			PublisherStream::Info => Some(PublisherInfo::new(id, self.state, self.streams)),
+			_ => None,
+		}
 	}
 }
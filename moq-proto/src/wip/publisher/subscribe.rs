@@ -1,119 +1,67 @@
-use std::collections::{BTreeSet, HashMap, VecDeque};
+use crate::message;
 
-use bytes::{Buf, BufMut};
+use super::{PublisherState, PublisherStream};
+use crate::wip::{Error, ErrorCode, GroupId, StreamDir, StreamId, StreamKind, Streams, SubscribeId};
 
-use crate::{
-	coding::{Decode, Encode},
-	message::{self},
-	Error, ErrorCode, GroupId, StreamId, SubscribeId, SubscribeRequest,
-};
-
-#[derive(Default)]
-pub struct PublisherSubscribes {
-	lookup: HashMap<SubscribeId, PublisherSubscribe>,
-	ready: BTreeSet<SubscribeId>,
-	next: SubscribeId,
-}
-
-impl PublisherSubscribes {
-	pub(crate) fn create<B: Buf>(&mut self, stream: StreamId, buf: &mut B) -> Result<SubscribeId, Error> {
-		let subscribe = message::Subscribe::decode(buf)?;
-		let id = self.next;
-		self.next.increment();
-
-		let subscribe = PublisherSubscribe::new(id, stream, subscribe.into());
-		self.lookup.insert(id, subscribe);
-		self.ready.insert(id);
-
-		Ok(id)
-	}
-
-	pub fn accept(&mut self) -> Option<&mut PublisherSubscribe> {
-		let id = self.ready.pop_first()?;
-		self.lookup.get_mut(&id)
-	}
-
-	pub fn get(&mut self, id: SubscribeId) -> Option<&mut PublisherSubscribe> {
-		self.lookup.get_mut(&id)
-	}
-}
-
-struct PublisherSubscribe {
+/// An active subscription to a track.
+pub struct PublisherSubscribe<'a> {
 	id: SubscribeId,
 	stream: StreamId,
-
-	// Outbound
-	info: Option<message::Info>,
-	info_sent: bool,
-	dropped: VecDeque<(GroupId, ErrorCode)>,
-
-	// Inbound
-	request: SubscribeRequest,
-	update: Option<message::SubscribeUpdate>,
+	state: &'a mut PublisherState,
+	streams: &'a mut Streams,
 }
 
-impl PublisherSubscribe {
-	fn new(id: SubscribeId, stream: StreamId, request: SubscribeRequest) -> Self {
+impl<'a> PublisherSubscribe<'a> {
+	pub(super) fn new(
+		id: SubscribeId,
+		stream: StreamId,
+		state: &'a mut PublisherState,
+		streams: &'a mut Streams,
+	) -> Self {
 		Self {
 			id,
 			stream,
-			info: None,
-			info_sent: false,
-			dropped: VecDeque::new(),
-			request,
-			update: None,
+			state,
+			streams,
 		}
 	}
 
-	pub fn encode<B: BufMut>(&mut self, buf: &mut B) {
-		if let Some(info) = self.info.take() {
-			info.encode(buf);
-		}
-
-		loop {
-			let (id, code) = match self.dropped.pop_front() {
-				Some(id) => id,
-				None => return,
-			};
-
-			let mut msg = message::GroupDrop {
-				sequence: id.0,
-				count: 0,
-				code: code.into(),
-			};
-
-			while let Some((id, code)) = self.dropped.front().cloned() {
-				if msg.sequence + msg.count + 1 == id.0 && msg.code == code.into() {
-					msg.count += 1;
-					self.dropped.pop_front();
-				} else {
-					break;
-				}
-			}
-
-			msg.encode(buf);
-		}
+	pub fn id(&self) -> SubscribeId {
+		self.id
 	}
 
-	pub fn decode<B: Buf>(&mut self, buf: &mut B) -> Result<(), Error> {
-		let update = message::SubscribeUpdate::decode(buf)?;
-		self.update = Some(update);
-
+	/// Accept the subscription, which MUST be done before any groups are dropped.
+	pub fn reply(&mut self, info: message::Info) -> Result<(), Error> {
+		self.streams.get(self.stream).ok_or(Error::UnknownStream)?.encode(&info);
 		Ok(())
 	}
 
-	pub fn stream(&self) -> StreamId {
-		self.stream
+	/// Notify the subscriber that a range of groups will not be delivered.
+	pub fn dropped(&mut self, dropped: message::GroupDrop) -> Result<(), Error> {
+		self.streams
+			.get(self.stream)
+			.ok_or(Error::UnknownStream)?
+			.encode(&dropped);
+		Ok(())
 	}
 
-	pub fn requested(&mut self) -> &SubscribeRequest {
-		&self.request
-	}
+	/// Open a new group stream for the subscription, written to via [super::Publisher::group].
+	pub fn group(&mut self, sequence: GroupId) -> StreamId {
+		let stream = self.streams.open(StreamDir::Uni, StreamKind::Group);
+		stream.encode(&message::DataType::Group);
+		stream.encode(&message::Group {
+			subscribe: self.id.0,
+			sequence: sequence.0,
+		});
+
+		let id = stream.id;
+		self.state.streams.insert(id, PublisherStream::Group);
 
-	pub fn reply(&mut self, info: message::Info) {
-		assert!(self.info.is_none());
-		assert!(!self.info_sent);
+		id
+	}
 
-		self.info = Some(info);
+	/// Close the subscription, resetting it if there's an error code.
+	pub fn close(self, code: Option<ErrorCode>) -> Result<(), Error> {
+		self.state.close(self.streams, self.stream, code)
 	}
 }
@@ -1,196 +1,455 @@
-// WORK IN PROGRESS
-
-use std::collections::{hash_map, HashMap};
-
-use bytes::{Bytes, BytesMut};
-
-use crate::generic;
-
-#[derive(Debug, thiserror::Error)]
-pub enum Error {
-	#[error("transfork error: {0}")]
-	Generic(#[from] generic::Error),
-
-	#[error("read error: {0}")]
-	Read(#[from] quinn_proto::ReadError),
-
-	#[error("write error: {0}")]
-	Write(#[from] quinn_proto::WriteError),
-
-	#[error("readable error: {0}")]
-	Readable(#[from] quinn_proto::ReadableError),
+//! A driver that runs a [super::Connection] on top of a [quinn_proto::Connection].
+//!
+//! The caller remains responsible for driving the QUIC connection itself (timers, datagrams, etc).
+//! Any [quinn_proto::StreamEvent] should be passed to [Connection::handle], and [Connection::flush] called after
+//! making any changes via the publisher or subscriber.
+//! Streams must only be opened via the driver, otherwise it will return [Error::UnexpectedStream].
+use std::{
+	collections::{HashMap, VecDeque},
+	ops::{Deref, DerefMut},
+};
+
+use bytes::{Buf, Bytes};
+
+use super::{Error, ErrorCode, StreamDir, StreamId, Transmit};
+use crate::coding::DecodeLimits;
+
+// Data that could not be written yet due to flow control.
+#[derive(Default)]
+struct Pending {
+	chunks: VecDeque<Bytes>,
+	fin: bool,
 }
 
 pub struct Connection {
-	generic: generic::Connection,
+	inner: super::Connection,
+
+	// Local streams that could not be opened yet due to the stream limit.
+	opening_bi: VecDeque<StreamId>,
+	opening_uni: VecDeque<StreamId>,
 
-	buffer: BytesMut,
-	buffered: HashMap<quinn_proto::StreamId, Bytes>,
+	pending: HashMap<StreamId, Pending>,
 }
 
 impl Connection {
 	pub fn client() -> Self {
-		Self {
-			generic: generic::Connection::client(),
-			buffer: BytesMut::new(),
-			buffered: HashMap::new(),
-		}
+		Self::new(super::Connection::client())
+	}
+
+	/// Create a client, enforcing the limits on anything received from the server.
+	pub fn client_with(limits: DecodeLimits) -> Self {
+		Self::new(super::Connection::client_with(limits))
 	}
 
 	pub fn server() -> Self {
-		Self {
-			generic: generic::Connection::server(),
-			buffer: BytesMut::new(),
-			buffered: HashMap::new(),
-		}
+		Self::new(super::Connection::server())
 	}
 
-	pub fn poll(&mut self, quinn: &mut quinn_proto::Connection) -> Result<(), Error> {
-		while let Some(event) = quinn.poll() {
-			match event {
-				quinn_proto::Event::Stream(event) => self.poll_stream(quinn, event)?,
-				quinn_proto::Event::Connected => {
-					// Create the initial bidirectional and unidirectional streams.
-					self.open(quinn, quinn_proto::Dir::Bi);
-					self.open(quinn, quinn_proto::Dir::Uni);
-				}
-				_ => todo!(),
-			}
-		}
+	/// Create a server, enforcing the limits on anything received from the client.
+	pub fn server_with(limits: DecodeLimits) -> Self {
+		Self::new(super::Connection::server_with(limits))
+	}
 
-		while let Some(event) = self.generic.poll() {
-			match event {
-				generic::ConnectionEvent::Stream(event) => match event {
-					generic::StreamEvent::Open(dir) => self.open(quinn, dir.into()),
-					generic::StreamEvent::Encodable(id) => self.encode(quinn, id.into())?,
-				},
-			}
+	fn new(inner: super::Connection) -> Self {
+		Self {
+			inner,
+			opening_bi: VecDeque::new(),
+			opening_uni: VecDeque::new(),
+			pending: HashMap::new(),
 		}
-
-		Ok(())
 	}
 
-	fn poll_stream(
+	/// Process a stream event from the QUIC connection, then flush any resulting writes.
+	pub fn handle(
 		&mut self,
 		quinn: &mut quinn_proto::Connection,
 		event: quinn_proto::StreamEvent,
 	) -> Result<(), Error> {
 		match event {
-			quinn_proto::StreamEvent::Available { dir } => self.open(quinn, dir),
-			quinn_proto::StreamEvent::Opened { dir } => self.accept(quinn, dir),
-			quinn_proto::StreamEvent::Readable { id } => {}
-			quinn_proto::StreamEvent::Writable { id } => {
-				let mut quinn = quinn.send_stream(id);
-
-				if let hash_map::Entry::Occupied(mut entry) = self.buffered.entry(id) {
-					let written = quinn.write_chunks(&mut [entry.get().clone()])?;
-					match written.chunks {
-						0 => {
-							let _ = entry.get_mut().split_to(written.bytes);
-							return Ok(());
-						}
-						1 => entry.remove(),
-						_ => unreachable!(),
-					};
+			quinn_proto::StreamEvent::Opened { dir } => {
+				while let Some(id) = quinn.streams().accept(dir) {
+					self.read(quinn, id)?;
 				}
-
-				self.encode(quinn, id)?;
-			}
-			quinn_proto::StreamEvent::Finished { id } => {
-				todo!()
 			}
+			quinn_proto::StreamEvent::Readable { id } => self.read(quinn, id)?,
+			quinn_proto::StreamEvent::Writable { id } => self.write(quinn, id.into())?,
 			quinn_proto::StreamEvent::Stopped { id, error_code } => {
-				todo!()
+				self.pending.remove(&id.into());
+				self.inner.reset(id.into(), code(error_code))?;
 			}
-		};
+			// All data was acknowledged by the peer.
+			quinn_proto::StreamEvent::Finished { .. } => {}
+			// Retried below.
+			quinn_proto::StreamEvent::Available { .. } => {}
+		}
 
-		Ok(())
+		self.flush(quinn)
 	}
 
-	fn accept(&mut self, quinn: &mut quinn_proto::Connection, dir: quinn_proto::Dir) {
-		while let Some(id) = quinn.streams().accept(dir) {
-			self.generic.streams().accept(dir.into(), id.into());
+	/// Perform any actions queued by the state machine.
+	pub fn flush(&mut self, quinn: &mut quinn_proto::Connection) -> Result<(), Error> {
+		self.open(quinn, StreamDir::Bi)?;
+		self.open(quinn, StreamDir::Uni)?;
+
+		while let Some(transmit) = self.inner.poll_transmit() {
+			match transmit {
+				Transmit::Open(id) => {
+					match id.dir() {
+						StreamDir::Bi => self.opening_bi.push_back(id),
+						StreamDir::Uni => self.opening_uni.push_back(id),
+					};
+
+					self.open(quinn, id.dir())?;
+				}
+				Transmit::Write { id, data, fin } => {
+					let pending = self.pending.entry(id).or_default();
+					if !data.is_empty() {
+						pending.chunks.push_back(data);
+					}
+					pending.fin |= fin;
+
+					self.write(quinn, id)?;
+				}
+				Transmit::Reset { id, code } => {
+					self.pending.remove(&id);
+
+					let code = quinn_proto::VarInt::from_u32(code.0);
+
+					// Either side may have already been closed, or not exist for unidirectional streams.
+					quinn.send_stream(id.into()).reset(code).ok();
+					quinn.recv_stream(id.into()).stop(code).ok();
+				}
+			}
 		}
+
+		Ok(())
 	}
 
-	fn open(&mut self, quinn: &mut quinn_proto::Connection, dir: quinn_proto::Dir) {
-		while let Some(id) = quinn.streams().open(dir) {
-			let mut streams = self.generic.streams();
+	fn open(&mut self, quinn: &mut quinn_proto::Connection, dir: StreamDir) -> Result<(), Error> {
+		let (queue, quinn_dir) = match dir {
+			StreamDir::Bi => (&mut self.opening_bi, quinn_proto::Dir::Bi),
+			StreamDir::Uni => (&mut self.opening_uni, quinn_proto::Dir::Uni),
+		};
 
-			let mut stream = match streams.open(dir.into(), id.into()) {
-				Some(stream) => stream,
-				None => continue,
+		while let Some(id) = queue.front().copied() {
+			let actual = match quinn.streams().open(quinn_dir) {
+				Some(actual) => actual,
+				None => break,
 			};
 
-			stream.encode(&mut self.buffer);
+			// Both sides allocate stream IDs sequentially, so a mismatch means someone else opened a stream.
+			if StreamId::from(actual) != id {
+				return Err(Error::UnexpectedStream);
+			}
+
+			queue.pop_front();
+			self.inner.opened(id)?;
 		}
+
+		Ok(())
 	}
 
-	fn decode(&mut self, quinn: &mut quinn_proto::Connection, id: quinn_proto::StreamId) -> Result<(), Error> {
+	fn read(&mut self, quinn: &mut quinn_proto::Connection, id: quinn_proto::StreamId) -> Result<(), Error> {
 		let mut stream = quinn.recv_stream(id);
-		let mut chunks = stream.read(true)?;
+		let mut chunks = match stream.read(true) {
+			Ok(chunks) => chunks,
+			// We already stopped the stream.
+			Err(quinn_proto::ReadableError::ClosedStream) => return Ok(()),
+			// We only ever read in order, so this can't happen.
+			Err(quinn_proto::ReadableError::IllegalOrderedRead) => return Err(Error::ProtocolViolation),
+		};
 
-		while let Some(chunk) = chunks.next(usize::MAX)? {
-			let mut streams = self.generic.streams();
-			let mut stream = streams.get(id.into()).unwrap();
-			stream.decode(&chunk.bytes)?;
+		let mut finished = false;
+		let mut reset = None;
+
+		loop {
+			match chunks.next(usize::MAX) {
+				Ok(Some(mut chunk)) => self.inner.recv(id.into(), &mut chunk.bytes)?,
+				Ok(None) => {
+					finished = true;
+					break;
+				}
+				Err(quinn_proto::ReadError::Blocked) => break,
+				Err(quinn_proto::ReadError::Reset(error_code)) => {
+					reset = Some(code(error_code));
+					break;
+				}
+			}
+		}
+
+		// Any flow control updates are sent by the next call to poll_transmit on the QUIC connection.
+		let _ = chunks.finalize();
+
+		if finished {
+			self.inner.finished(id.into())?;
+		} else if let Some(code) = reset {
+			self.pending.remove(&id.into());
+			self.inner.reset(id.into(), code)?;
 		}
 
 		Ok(())
 	}
 
-	fn encode(&mut self, quinn: &mut quinn_proto::SendStream, id: quinn_proto::StreamId) -> Result<(), Error> {
-		let mut streams = self.generic.streams();
-		let mut stream = streams.get(id.into()).unwrap();
-
-		let mut buffer = std::mem::take(&mut self.buffer);
-		stream.encode(&mut buffer);
+	fn write(&mut self, quinn: &mut quinn_proto::Connection, id: StreamId) -> Result<(), Error> {
+		let pending = match self.pending.get_mut(&id) {
+			Some(pending) => pending,
+			None => return Ok(()),
+		};
 
-		let mut buffer = buffer.freeze();
+		let mut stream = quinn.send_stream(id.into());
 
-		let written = quinn.write_chunks(&mut [buffer.clone()])?;
-		match written.chunks {
-			0 => {
-				let buffered = buffer.split_off(written.bytes);
-				self.buffered.insert(id, buffered);
-			}
-			1 => {
-				// Reuse the buffer if we can.
-				self.buffer = buffer.try_into_mut().unwrap_or_default();
+		while let Some(chunk) = pending.chunks.front_mut() {
+			match stream.write(chunk) {
+				Ok(size) => {
+					chunk.advance(size);
+					if chunk.is_empty() {
+						pending.chunks.pop_front();
+					}
+				}
+				Err(quinn_proto::WriteError::Blocked) => return Ok(()),
+				Err(quinn_proto::WriteError::Stopped(error_code)) => {
+					self.pending.remove(&id);
+					return self.inner.reset(id, code(error_code));
+				}
+				Err(quinn_proto::WriteError::ClosedStream) => {
+					self.pending.remove(&id);
+					return Ok(());
+				}
 			}
-			_ => unreachable!(),
-		};
+		}
+
+		if pending.fin {
+			// The stream may have been stopped in the meantime.
+			stream.finish().ok();
+		}
+
+		self.pending.remove(&id);
 
 		Ok(())
 	}
 }
 
-impl From<quinn_proto::Dir> for generic::StreamDirection {
-	fn from(dir: quinn_proto::Dir) -> Self {
-		match dir {
-			quinn_proto::Dir::Bi => generic::StreamDirection::Bi,
-			quinn_proto::Dir::Uni => generic::StreamDirection::Uni,
-		}
+impl Deref for Connection {
+	type Target = super::Connection;
+
+	fn deref(&self) -> &Self::Target {
+		&self.inner
 	}
 }
 
-impl From<generic::StreamDirection> for quinn_proto::Dir {
-	fn from(dir: generic::StreamDirection) -> Self {
-		match dir {
-			generic::StreamDirection::Bi => quinn_proto::Dir::Bi,
-			generic::StreamDirection::Uni => quinn_proto::Dir::Uni,
-		}
+impl DerefMut for Connection {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.inner
 	}
 }
 
-impl From<quinn_proto::StreamId> for generic::StreamId {
+fn code(code: quinn_proto::VarInt) -> ErrorCode {
+	ErrorCode(code.into_inner().try_into().unwrap_or(u32::MAX))
+}
+
+impl From<quinn_proto::StreamId> for StreamId {
 	fn from(id: quinn_proto::StreamId) -> Self {
 		Self(id.0)
 	}
 }
 
-impl From<generic::StreamId> for quinn_proto::StreamId {
-	fn from(id: generic::StreamId) -> Self {
+impl From<StreamId> for quinn_proto::StreamId {
+	fn from(id: StreamId) -> Self {
 		Self(id.0)
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use std::{
+		net::{Ipv4Addr, SocketAddr},
+		sync::Arc,
+		time::Instant,
+	};
+
+	use bytes::BytesMut;
+
+	use super::*;
+	use crate::{
+		message,
+		wip::{Event, PublisherEvent, SessionEvent, SubscriberEvent},
+	};
+
+	// One side of a QUIC connection over an in-memory network.
+	struct Peer {
+		addr: SocketAddr,
+		endpoint: quinn_proto::Endpoint,
+		conn: Option<(quinn_proto::ConnectionHandle, quinn_proto::Connection)>,
+		moq: Connection,
+	}
+
+	impl Peer {
+		fn new(port: u16, server: Option<quinn_proto::ServerConfig>, moq: Connection) -> Self {
+			let config = Arc::new(quinn_proto::EndpointConfig::default());
+
+			Self {
+				addr: SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+				endpoint: quinn_proto::Endpoint::new(config, server.map(Arc::new), false, None),
+				conn: None,
+				moq,
+			}
+		}
+
+		// Process any QUIC events, then return the datagrams to send.
+		fn poll(&mut self, now: Instant) -> Vec<BytesMut> {
+			let (handle, conn) = match &mut self.conn {
+				Some((handle, conn)) => (*handle, conn),
+				None => return Vec::new(),
+			};
+
+			while let Some(event) = conn.poll_endpoint_events() {
+				if let Some(event) = self.endpoint.handle_event(handle, event) {
+					conn.handle_event(event);
+				}
+			}
+
+			while let Some(event) = conn.poll() {
+				match event {
+					quinn_proto::Event::Stream(event) => self.moq.handle(conn, event).unwrap(),
+					quinn_proto::Event::ConnectionLost { reason } => panic!("connection lost: {reason}"),
+					_ => {}
+				}
+			}
+
+			self.moq.flush(conn).unwrap();
+
+			let mut datagrams = Vec::new();
+			let mut buf = Vec::new();
+			while let Some(transmit) = conn.poll_transmit(now, 1, &mut buf) {
+				datagrams.push(BytesMut::from(&buf[..transmit.size]));
+				buf.clear();
+			}
+
+			datagrams
+		}
+
+		fn recv(&mut self, now: Instant, from: SocketAddr, data: BytesMut) {
+			let mut buf = Vec::new();
+
+			match self.endpoint.handle(now, from, None, None, data, &mut buf) {
+				Some(quinn_proto::DatagramEvent::ConnectionEvent(_, event)) => {
+					self.conn.as_mut().expect("no connection").1.handle_event(event)
+				}
+				Some(quinn_proto::DatagramEvent::NewConnection(incoming)) => {
+					let conn = self.endpoint.accept(incoming, now, &mut buf, None).unwrap();
+					self.conn = Some(conn);
+				}
+				Some(quinn_proto::DatagramEvent::Response(_)) => panic!("unexpected response"),
+				None => {}
+			}
+		}
+
+		fn events(&mut self) -> Vec<Event> {
+			std::iter::from_fn(|| self.moq.poll()).collect()
+		}
+	}
+
+	fn run(client: &mut Peer, server: &mut Peer) {
+		let now = Instant::now();
+
+		loop {
+			let sent = client.poll(now);
+			let received = server.poll(now);
+
+			if sent.is_empty() && received.is_empty() {
+				break;
+			}
+
+			for data in sent {
+				server.recv(now, client.addr, data);
+			}
+
+			for data in received {
+				client.recv(now, server.addr, data);
+			}
+		}
+	}
+
+	fn connect() -> (Peer, Peer) {
+		let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+		let chain = vec![cert.cert.der().clone()];
+		let key = quinn_proto::rustls::pki_types::PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+
+		let mut roots = quinn_proto::rustls::RootCertStore::empty();
+		roots.add(chain[0].clone()).unwrap();
+
+		let server_config = quinn_proto::ServerConfig::with_single_cert(chain, key.into()).unwrap();
+		let client_config = quinn_proto::ClientConfig::with_root_certificates(Arc::new(roots)).unwrap();
+
+		let mut server = Peer::new(4443, Some(server_config), Connection::server());
+		let mut client = Peer::new(4444, None, Connection::client());
+
+		let conn = client
+			.endpoint
+			.connect(Instant::now(), client_config, server.addr, "localhost")
+			.unwrap();
+		client.conn = Some(conn);
+
+		run(&mut client, &mut server);
+
+		for peer in [&mut client, &mut server] {
+			assert!(matches!(
+				peer.events()[..],
+				[Event::Session(SessionEvent::Connected {
+					version: message::Version::CURRENT
+				})]
+			));
+		}
+
+		(client, server)
+	}
+
+	#[test]
+	fn announce() {
+		let (mut client, mut server) = connect();
+
+		let stream = client.moq.subscriber().announce(message::AnnouncePlease {
+			filter: message::Filter::new("foo/*"),
+			cursor: None,
+		});
+		run(&mut client, &mut server);
+
+		let id = match &server.events()[..] {
+			[Event::Publisher(PublisherEvent::Announce(id, _))] => *id,
+			events => panic!("unexpected events: {:?}", events),
+		};
+		assert_eq!(id, stream);
+
+		let mut publisher = server.moq.publisher();
+		let mut announce = publisher.announce(id).unwrap();
+		announce.active("bar").unwrap();
+		announce.live().unwrap();
+		run(&mut client, &mut server);
+
+		let received = client.events();
+		assert!(matches!(
+			&received[..],
+			[
+				Event::Subscriber(SubscriberEvent::Announced(a, message::Announce::Active(path))),
+				Event::Subscriber(SubscriberEvent::Announced(b, message::Announce::Live)),
+			] if *a == stream && *b == stream && path == "bar"
+		));
+	}
+
+	#[test]
+	fn unexpected_stream() {
+		let (mut client, _server) = connect();
+
+		// Open a stream behind the driver's back, so it no longer matches the state machine.
+		let (_, conn) = client.conn.as_mut().unwrap();
+		conn.streams().open(quinn_proto::Dir::Bi).unwrap();
+
+		client.moq.subscriber().announce(message::AnnouncePlease {
+			filter: message::Filter::new("foo/*"),
+			cursor: None,
+		});
+
+		let (_, conn) = client.conn.as_mut().unwrap();
+		assert!(matches!(client.moq.flush(conn), Err(Error::UnexpectedStream)));
+	}
+}
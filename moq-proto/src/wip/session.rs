@@ -1,91 +1,114 @@
-use bytes::{Buf, BufMut};
+use std::collections::VecDeque;
 
-use crate::{
-	coding::{Decode, Encode},
-	message::{self},
-};
+use crate::message;
 
-use super::{Error, StreamId};
+use super::{Error, Stream, StreamId};
 
-#[derive(Debug)]
+// Our supported versions, in order of preference.
+const VERSIONS: [message::Version; 2] = [message::Version::FORK_05, message::Version::FORK_04];
+
+#[derive(Clone, Debug)]
 pub enum SessionEvent {
-	Connected(message::ClientSetup, message::ServerSetup),
+	/// The setup handshake completed using the negotiated version.
+	Connected { version: message::Version },
+
+	/// The peer sent an updated estimate of the session bitrate.
+	Info(message::SessionInfo),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Debug)]
 pub struct Session {
 	is_client: bool,
-	client: Option<message::ClientSetup>,
-	server: Option<message::ServerSetup>,
+	version: Option<message::Version>,
 	stream: Option<StreamId>,
+	events: VecDeque<SessionEvent>,
 }
 
 impl Session {
-	pub(crate) fn new(is_client: bool) -> Self {
+	pub(super) fn new(is_client: bool) -> Self {
 		Self {
 			is_client,
-			client: None,
-			server: None,
+			version: None,
 			stream: None,
+			events: VecDeque::new(),
 		}
 	}
 
-	pub(crate) fn encode<B: BufMut>(&mut self, buf: &mut B) {
-		if self.is_client && self.client.is_none() {
-			message::ControlType::Session.encode(buf);
+	/// Write the client setup to the newly opened session stream.
+	pub(super) fn open(&mut self, stream: &mut Stream) {
+		assert!(self.is_client);
 
-			let client = message::ClientSetup {
-				versions: [message::Version::CURRENT].into(),
-				extensions: Default::default(),
-			};
-			client.encode(buf);
-
-			self.client = Some(client);
-		} else if !self.is_client && self.server.is_none() {
-			// TODO utilize the client setup instead of blindly sending the current version
-			let server = message::ServerSetup {
-				version: message::Version::CURRENT,
-				extensions: Default::default(),
-			};
+		stream.encode(&message::ControlType::Session);
+		stream.encode(&message::ClientSetup {
+			versions: VERSIONS.into(),
+			extensions: Default::default(),
+		});
 
-			server.encode(buf);
-			self.server = Some(server);
-		}
+		self.stream = Some(stream.id);
 	}
 
-	pub(crate) fn open(&mut self, stream: StreamId) -> bool {
-		if self.stream.is_none() {
-			self.stream = Some(stream);
-			true
-		} else {
-			false
+	/// The remote opened the session stream.
+	pub(super) fn accept(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		if self.is_client || self.stream.is_some() {
+			return Err(Error::ProtocolViolation);
 		}
+
+		self.stream = Some(stream.id);
+		Ok(())
 	}
 
-	pub(crate) fn decode<B: Buf>(&mut self, buf: &mut B) -> Result<(), Error> {
-		if !self.is_client && self.client.is_none() {
-			let client = message::ClientSetup::decode(buf)?;
-			if !client.versions.contains(&message::Version::CURRENT) {
-				todo!("version error");
-			}
-
-			self.client = Some(client);
-		} else if self.is_client && self.server.is_none() {
-			let server = message::ServerSetup::decode(buf)?;
-			if server.version != message::Version::CURRENT {
-				todo!("version error");
-			}
-
-			self.server = Some(server);
+	pub(super) fn recv(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		if self.version.is_none() {
+			let version = match self.is_client {
+				true => match stream.decode::<message::ServerSetup>()? {
+					Some(server) if VERSIONS.contains(&server.version) => server.version,
+					Some(server) => return Err(Error::Version([server.version].into())),
+					None => return Ok(()),
+				},
+				false => match stream.decode::<message::ClientSetup>()? {
+					Some(client) => {
+						// Pick our most preferred version that the client supports.
+						let version = VERSIONS
+							.into_iter()
+							.find(|version| client.versions.contains(version))
+							.ok_or(Error::Version(client.versions))?;
+
+						stream.encode(&message::ServerSetup {
+							version,
+							extensions: Default::default(),
+						});
+
+						version
+					}
+					None => return Ok(()),
+				},
+			};
+
+			self.version = Some(version);
+			self.events.push_back(SessionEvent::Connected { version });
+		}
+
+		while let Some(info) = stream.decode::<message::SessionInfo>()? {
+			self.events.push_back(SessionEvent::Info(info));
 		}
 
 		Ok(())
 	}
 
-	pub(crate) fn accept<B: Buf>(&mut self, stream: StreamId, buf: &mut B) -> Result<(), Error> {
-		self.decode(buf)?;
-		self.stream = Some(stream);
+	pub(super) fn poll(&mut self) -> Option<SessionEvent> {
+		self.events.pop_front()
+	}
+
+	pub fn is_client(&self) -> bool {
+		self.is_client
+	}
 
-		Ok(())
+	/// The negotiated version, available once connected.
+	pub fn version(&self) -> Option<message::Version> {
+		self.version
+	}
+
+	pub fn stream(&self) -> Option<StreamId> {
+		self.stream
 	}
 }
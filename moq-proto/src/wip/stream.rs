@@ -1,119 +1,306 @@
-use bytes::{Buf, BufMut, Bytes, BytesMut};
-use derive_more::From;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 
-use crate::{
-	coding::{Decode, Encode},
-	message,
-};
+use bytes::{Buf, Bytes, BytesMut};
 
-use super::{Error, StreamId};
+use crate::coding::{Decode, DecodeError, DecodeLimits, Encode};
+
+use super::{Error, ErrorCode, StreamId};
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord)]
 pub enum StreamDir {
 	Uni,
 	Bi,
 }
-pub struct Stream {
-	id: StreamId,
-	dir: StreamDir,
-	kind: StreamKind,
+
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+	Session,
+	Announce,
+	Subscribe,
+	Info,
+	Group,
+}
+
+/// An action the transport needs to perform on behalf of the [super::Connection].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transmit {
+	/// Open the next stream, which MUST be assigned this ID, then call [super::Connection::opened].
+	Open(StreamId),
+
+	/// Write the data to the stream, finishing it afterwards if `fin` is set.
+	Write { id: StreamId, data: Bytes, fin: bool },
+
+	/// Reset the send side and stop the receive side of the stream.
+	Reset { id: StreamId, code: ErrorCode },
+}
+
+/// The buffered state of a single stream.
+#[derive(Debug)]
+pub(super) struct Stream {
+	pub id: StreamId,
+
+	// Unknown until the header has been received for remote streams.
+	pub kind: Option<StreamKind>,
+
+	// True if the transport has opened the stream.
+	opened: bool,
 
 	send: BytesMut,
-	recv: Bytes,
+	send_fin: bool,
+	send_done: bool,
+
+	recv: BytesMut,
+	recv_done: bool,
+
+	reset: Option<ErrorCode>,
+
+	// Enforced on every message, so at most a partial message is buffered.
+	limits: DecodeLimits,
 }
 
 impl Stream {
-	pub fn accept<B: Buf>(id: StreamId, dir: StreamDir, buf: &mut B) -> Result<Self, Error> {
-		assert!(buf.has_remaining(), "empty accepts not yet supported");
-
-		let kind = match dir {
-			StreamDir::Uni => match message::DataType::decode(buf)? {
-				message::DataType::Group => StreamKind::Group,
-			},
-			StreamDir::Bi => match message::ControlType::decode(buf)? {
-				message::ControlType::Announce => StreamKind::Announce,
-				message::ControlType::Subscribe => StreamKind::Subscribe,
-				message::ControlType::Session => StreamKind::Session,
-				message::ControlType::Info => unimplemented!(),
-			},
-		};
+	fn new(id: StreamId, kind: Option<StreamKind>, local: bool, limits: DecodeLimits) -> Self {
+		let uni = id.dir() == StreamDir::Uni;
 
-		let recv = buf.copy_to_bytes(buf.remaining());
-
-		Ok(Self {
+		Self {
 			id,
-			dir,
 			kind,
+			opened: !local,
 			send: BytesMut::new(),
-			recv,
-		})
+			send_fin: false,
+			// Remote unidirectional streams can't be written to, and local ones can't be read from.
+			send_done: uni && !local,
+			recv: BytesMut::new(),
+			recv_done: uni && local,
+			reset: None,
+			limits,
+		}
 	}
 
-	pub fn kind(&self) -> StreamKind {
-		self.kind
+	pub fn encode<E: Encode>(&mut self, msg: &E) {
+		msg.encode(&mut self.send);
 	}
 
-	pub fn open(id: StreamId, dir: StreamDir, kind: StreamKind) -> Self {
-		let mut send = BytesMut::new();
+	pub fn write(&mut self, data: &[u8]) {
+		self.send.extend_from_slice(data);
+	}
 
-		match kind {
-			StreamKind::Group => {
-				assert_eq!(dir, StreamDir::Uni);
-				message::DataType::Group.encode(&mut send);
-			}
-			StreamKind::Session => {
-				assert_eq!(dir, StreamDir::Bi);
-				message::ControlType::Session.encode(&mut send);
-			}
-			StreamKind::Announce => {
-				assert_eq!(dir, StreamDir::Bi);
-				message::ControlType::Announce.encode(&mut send);
-			}
-			StreamKind::Subscribe => {
-				assert_eq!(dir, StreamDir::Bi);
-				message::ControlType::Subscribe.encode(&mut send);
-			}
+	/// Finish the send side after any buffered data.
+	pub fn finish(&mut self) {
+		self.send_fin = true;
+	}
+
+	/// Abort both sides of the stream.
+	pub fn reset(&mut self, code: ErrorCode) {
+		if !self.send_done || !self.recv_done {
+			self.reset = Some(code);
 		}
 
-		Self {
-			id,
-			dir,
-			kind,
-			recv: Bytes::new(),
-			send,
+		self.send.clear();
+		self.send_fin = false;
+		self.recv.clear();
+		self.recv_done = true;
+	}
+
+	pub fn recv<B: Buf>(&mut self, buf: &mut B) {
+		// Ignore anything received after we stopped reading.
+		if self.recv_done {
+			buf.advance(buf.remaining());
+			return;
 		}
+
+		while buf.has_remaining() {
+			let chunk = buf.chunk();
+			self.recv.extend_from_slice(chunk);
+			let size = chunk.len();
+			buf.advance(size);
+		}
+	}
+
+	pub fn recv_fin(&mut self) {
+		self.recv_done = true;
+	}
+
+	/// True if nothing more will be received.
+	pub fn is_finished(&self) -> bool {
+		self.recv_done
 	}
 
-	pub fn id(&self) -> StreamId {
-		self.id
+	/// Drop any received data, used once the stream is no longer being read.
+	pub fn discard(&mut self) {
+		self.recv.clear();
+	}
+
+	/// Decode the next message if it has been fully received.
+	pub fn decode<D: Decode>(&mut self) -> Result<Option<D>, Error> {
+		let mut cursor = &self.recv[..];
+
+		match D::decode_limits(&mut cursor, &self.limits) {
+			Ok(msg) => {
+				let size = self.recv.len() - cursor.len();
+				self.recv.advance(size);
+				Ok(Some(msg))
+			}
+			Err(DecodeError::Short) => Ok(None),
+			Err(err) => Err(err.into()),
+		}
 	}
 
-	pub(crate) fn encode<B: BufMut, E: Encode>(&mut self, b: &mut B, msg: E) {
-		if !self.send.is_empty() {
-			let size = b.remaining_mut().min(self.send.len());
-			let chunk = self.send.split_to(size);
-			b.put(chunk);
+	/// Read exactly `size` bytes of raw data if they have been fully received.
+	pub fn read_exact(&mut self, size: usize) -> Option<Bytes> {
+		if self.recv.len() < size {
+			return None;
 		}
 
-		let mut buf = b.chain_mut(&mut self.send);
-		msg.encode(&mut buf);
+		Some(self.recv.split_to(size).freeze())
+	}
+
+	pub fn has_data(&self) -> bool {
+		!self.recv.is_empty()
 	}
 
-	pub(crate) fn try_decode<B: Buf, D: Decode>(&mut self, b: &mut B) -> Result<Option<D>, Error> {
-		let recv = std::mem::take(&mut self.recv);
+	fn poll_transmit(&mut self) -> Option<Transmit> {
+		// NOTE: A reset also stops the receive side, so it's sent even if we can't write.
+		if let Some(code) = self.reset.take() {
+			self.send_done = true;
+			return Some(Transmit::Reset { id: self.id, code });
+		}
+
+		if self.send_done {
+			return None;
+		}
+
+		if self.send.is_empty() && !self.send_fin {
+			return None;
+		}
 
-		let mut buf = recv.chain(b);
-		let msg = D::decode(&mut buf)?;
-		self.recv = buf.copy_to_bytes(buf.remaining());
+		let data = self.send.split().freeze();
+		let fin = self.send_fin;
+		self.send_done = fin;
 
-		Ok(Some(msg))
+		Some(Transmit::Write { id: self.id, data, fin })
+	}
+
+	fn is_done(&self) -> bool {
+		self.send_done && self.recv_done && self.reset.is_none()
 	}
 }
 
-#[derive(Clone, Debug, From, Copy, PartialEq, Eq, Hash)]
-pub enum StreamKind {
-	Session,
-	Announce,
-	Subscribe,
-	Group,
+/// Every active stream, keyed by ID.
+#[derive(Debug)]
+pub(super) struct Streams {
+	is_client: bool,
+	lookup: HashMap<StreamId, Stream>,
+
+	// Local streams waiting for the transport to open them, in order.
+	opening: VecDeque<StreamId>,
+
+	// Streams that may have something to transmit.
+	dirty: BTreeSet<StreamId>,
+
+	next_bi: u64,
+	next_uni: u64,
+
+	// The next remote stream index, used to ignore data for streams we've already closed.
+	remote_bi: u64,
+	remote_uni: u64,
+
+	limits: DecodeLimits,
+}
+
+impl Streams {
+	pub fn new(is_client: bool, limits: DecodeLimits) -> Self {
+		Self {
+			is_client,
+			lookup: HashMap::new(),
+			opening: VecDeque::new(),
+			dirty: BTreeSet::new(),
+			next_bi: 0,
+			next_uni: 0,
+			remote_bi: 0,
+			remote_uni: 0,
+			limits,
+		}
+	}
+
+	/// Queue a new local stream, which will be assigned the returned ID.
+	pub fn open(&mut self, dir: StreamDir, kind: StreamKind) -> &mut Stream {
+		let next = match dir {
+			StreamDir::Bi => &mut self.next_bi,
+			StreamDir::Uni => &mut self.next_uni,
+		};
+
+		let id = StreamId::new(self.is_client, dir, *next);
+		*next += 1;
+
+		self.opening.push_back(id);
+		self.lookup
+			.entry(id)
+			.or_insert(Stream::new(id, Some(kind), true, self.limits))
+	}
+
+	/// The transport has opened a local stream.
+	pub fn opened(&mut self, id: StreamId) -> Result<(), Error> {
+		let stream = self.lookup.get_mut(&id).ok_or(Error::UnknownStream)?;
+		stream.opened = true;
+		self.dirty.insert(id);
+
+		Ok(())
+	}
+
+	/// Return the stream, accepting it if it was opened by the remote.
+	pub fn accept(&mut self, id: StreamId) -> Option<&mut Stream> {
+		if id.is_client() == self.is_client {
+			return self.get(id);
+		}
+
+		let next = match id.dir() {
+			StreamDir::Bi => &mut self.remote_bi,
+			StreamDir::Uni => &mut self.remote_uni,
+		};
+
+		let index = id.0 >> 2;
+		if index >= *next {
+			// QUIC implicitly opens any lower streams, but we expect the transport to accept them in order.
+			*next = index + 1;
+			self.lookup.insert(id, Stream::new(id, None, false, self.limits));
+		}
+
+		self.get(id)
+	}
+
+	pub fn get(&mut self, id: StreamId) -> Option<&mut Stream> {
+		let stream = self.lookup.get_mut(&id)?;
+		self.dirty.insert(id);
+		Some(stream)
+	}
+
+	pub fn poll_transmit(&mut self) -> Option<Transmit> {
+		if let Some(id) = self.opening.pop_front() {
+			return Some(Transmit::Open(id));
+		}
+
+		while let Some(id) = self.dirty.pop_first() {
+			let stream = match self.lookup.get_mut(&id) {
+				Some(stream) if stream.opened => stream,
+				// Transmitted once the stream has been opened.
+				_ => continue,
+			};
+
+			let transmit = stream.poll_transmit();
+
+			if stream.is_done() {
+				self.lookup.remove(&id);
+			} else if transmit.is_some() {
+				// Check again in case there's more to do, like a pending FIN.
+				self.dirty.insert(id);
+			}
+
+			if transmit.is_some() {
+				return transmit;
+			}
+		}
+
+		None
+	}
 }
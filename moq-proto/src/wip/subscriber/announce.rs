@@ -1,53 +1,24 @@
-use std::collections::{HashMap, VecDeque};
-
-use bytes::{Buf, BufMut};
-
-use crate::{
-	coding::{Decode, Encode},
-	message::{self},
-	Error, Stream, StreamId,
-};
-
-pub enum SubscriberAnnounceEvent {}
-
-#[derive(Default)]
-pub struct SubscriberAnnounces {
-	lookup: HashMap<StreamId, SubscriberAnnounce>,
-}
-
-impl SubscriberAnnounces {
-	pub fn create(&mut self, stream: Stream, request: message::AnnouncePlease) -> &mut SubscriberAnnounce {
-		let id = stream.id();
-		let announce = SubscriberAnnounce::new(stream, request);
-		self.lookup.entry(id).or_insert(announce)
-	}
-
-	pub fn get(&mut self, stream: StreamId) -> Option<&mut SubscriberAnnounce> {
-		self.lookup.get_mut(&stream)
-	}
-}
-
-pub struct SubscriberAnnounce {
-	request: Option<message::AnnouncePlease>,
-	stream: Stream,
+use super::SubscriberState;
+use crate::wip::{Error, ErrorCode, StreamId, Streams};
+
+/// An active request for announcements.
+pub struct SubscriberAnnounce<'a> {
+	id: StreamId,
+	state: &'a mut SubscriberState,
+	streams: &'a mut Streams,
 }
 
-impl SubscriberAnnounce {
-	fn new(stream: Stream, request: message::AnnouncePlease) -> Self {
-		Self {
-			request: Some(request),
-			stream,
-		}
+impl<'a> SubscriberAnnounce<'a> {
+	pub(super) fn new(id: StreamId, state: &'a mut SubscriberState, streams: &'a mut Streams) -> Self {
+		Self { id, state, streams }
 	}
 
-	pub fn encode<B: BufMut>(&mut self, buf: &mut B) {
-		if let Some(request) = self.request.take() {
-			self.stream.encode(buf, request);
-		}
+	pub fn id(&self) -> StreamId {
+		self.id
 	}
 
-	/// Decode the next frame from the stream.
-	pub fn decode<B: Buf>(&mut self, buf: &mut B) -> Result<Option<message::Announce>, Error> {
-		self.stream.try_decode(buf)
+	/// Stop receiving announcements, resetting the stream if there's an error code.
+	pub fn close(self, code: Option<ErrorCode>) -> Result<(), Error> {
+		self.state.close(self.streams, self.id, code)
 	}
 }
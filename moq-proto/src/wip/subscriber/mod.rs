@@ -6,23 +6,319 @@ pub use announce::*;
 pub use group::*;
 pub use subscribe::*;
 
-#[derive(Default)]
-pub struct Subscriber {
-	announces: SubscriberAnnounces,
-	subscribes: SubscriberSubscribes,
-	groups: SubscriberGroups,
+use std::collections::{HashMap, VecDeque};
+
+use bytes::Bytes;
+
+use crate::message;
+
+use super::{
+	Error, ErrorCode, GroupId, Stream, StreamDir, StreamId, StreamKind, Streams, SubscribeId, SubscribeRequest,
+};
+
+#[derive(Clone, Debug)]
+pub enum SubscriberEvent {
+	/// The publisher announced a change to the broadcasts matching the filter.
+	Announced(StreamId, message::Announce),
+
+	/// The publisher accepted the subscription.
+	Subscribed(SubscribeId, message::Info),
+
+	/// The publisher will not deliver a range of groups.
+	Dropped(SubscribeId, message::GroupDrop),
+
+	/// The publisher replied to an info request.
+	Info(StreamId, message::Info),
+
+	/// The publisher opened a new group stream for the subscription.
+	Group(StreamId, SubscribeId, GroupId),
+
+	/// A complete frame was received on the group stream.
+	Frame(StreamId, Bytes),
+
+	/// The publisher closed the announce stream, with an error code if it was reset.
+	AnnounceClosed(StreamId, Option<ErrorCode>),
+
+	/// The publisher closed the subscription, with an error code if it was reset.
+	SubscribeClosed(SubscribeId, Option<ErrorCode>),
+
+	/// The publisher closed the info stream, with an error code if it was reset.
+	InfoClosed(StreamId, Option<ErrorCode>),
+
+	/// The publisher finished the group stream, with an error code if it was reset.
+	GroupClosed(StreamId, Option<ErrorCode>),
+}
+
+#[derive(Debug)]
+enum SubscriberStream {
+	Announce,
+	Subscribe { id: SubscribeId, info: bool },
+	Info,
+	Group { frame: Option<usize> },
+}
+
+#[derive(Debug, Default)]
+pub(super) struct SubscriberState {
+	streams: HashMap<StreamId, SubscriberStream>,
+	subscribes: HashMap<SubscribeId, StreamId>,
+	next: SubscribeId,
+	events: VecDeque<SubscriberEvent>,
+
+	// True if the negotiated version supports resuming announcements, unknown until connected.
+	cursor: Option<bool>,
+
+	// Announce requests made before the version was negotiated.
+	pending: Vec<(StreamId, message::AnnouncePlease)>,
+}
+
+impl SubscriberState {
+	/// The setup completed with the negotiated version, so write any pending requests.
+	pub fn connected(&mut self, streams: &mut Streams, cursor: bool) {
+		self.cursor = Some(cursor);
+
+		for (id, request) in std::mem::take(&mut self.pending) {
+			// Skip any requests that were closed in the meantime.
+			if !matches!(self.streams.get(&id), Some(SubscriberStream::Announce)) {
+				continue;
+			}
+
+			if let Some(stream) = streams.get(id) {
+				Self::encode_announce(stream, &request, cursor);
+			}
+		}
+	}
+
+	fn encode_announce(stream: &mut Stream, request: &message::AnnouncePlease, cursor: bool) {
+		match cursor {
+			true => stream.encode(request),
+			// Older versions send the filter alone.
+			false => stream.encode(&request.filter),
+		}
+	}
+
+	pub fn recv(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		let id = stream.id;
+
+		let state = match self.streams.get_mut(&id) {
+			Some(state) => state,
+			None if stream.kind == Some(StreamKind::Group) => return self.recv_group(stream),
+			// The stream was closed locally, so ignore anything else the publisher sends.
+			None => {
+				stream.discard();
+				return Ok(());
+			}
+		};
+
+		match state {
+			SubscriberStream::Announce => {
+				while let Some(announce) = stream.decode()? {
+					self.events.push_back(SubscriberEvent::Announced(id, announce));
+				}
+			}
+			SubscriberStream::Subscribe { id, info } => {
+				if !*info {
+					match stream.decode()? {
+						Some(msg) => self.events.push_back(SubscriberEvent::Subscribed(*id, msg)),
+						None => return Ok(()),
+					}
+
+					*info = true;
+				}
+
+				while let Some(dropped) = stream.decode()? {
+					self.events.push_back(SubscriberEvent::Dropped(*id, dropped));
+				}
+			}
+			SubscriberStream::Info => {
+				if let Some(info) = stream.decode()? {
+					self.events.push_back(SubscriberEvent::Info(id, info));
+				}
+			}
+			SubscriberStream::Group { frame } => loop {
+				let size = match *frame {
+					Some(size) => size,
+					None => match stream.decode::<message::Frame>()? {
+						Some(msg) => msg.size,
+						None => break,
+					},
+				};
+
+				match stream.read_exact(size) {
+					Some(payload) => {
+						*frame = None;
+						self.events.push_back(SubscriberEvent::Frame(id, payload));
+					}
+					None => {
+						*frame = Some(size);
+						break;
+					}
+				}
+			},
+		}
+
+		Ok(())
+	}
+
+	fn recv_group(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		let group = match stream.decode::<message::Group>()? {
+			Some(group) => group,
+			None => return Ok(()),
+		};
+
+		let subscribe = SubscribeId(group.subscribe);
+		if !self.subscribes.contains_key(&subscribe) {
+			return Err(Error::UnknownSubscribe);
+		}
+
+		self.streams.insert(stream.id, SubscriberStream::Group { frame: None });
+		self.events
+			.push_back(SubscriberEvent::Group(stream.id, subscribe, GroupId(group.sequence)));
+
+		self.recv(stream)
+	}
+
+	/// The publisher finished the stream.
+	pub fn finished(&mut self, stream: &mut Stream) -> Result<(), Error> {
+		match self.streams.get(&stream.id) {
+			// A frame was cut short.
+			Some(SubscriberStream::Group { frame: Some(_) }) => Err(Error::WrongSize),
+			Some(SubscriberStream::Group { .. }) | None if stream.has_data() => Err(Error::WrongSize),
+			_ => {
+				self.closed(stream.id, None);
+				stream.finish();
+				Ok(())
+			}
+		}
+	}
+
+	/// The publisher closed the stream, with an error code if it was reset.
+	pub fn closed(&mut self, id: StreamId, code: Option<ErrorCode>) {
+		let event = match self.streams.remove(&id) {
+			Some(SubscriberStream::Announce) => SubscriberEvent::AnnounceClosed(id, code),
+			Some(SubscriberStream::Subscribe { id: subscribe, .. }) => {
+				self.subscribes.remove(&subscribe);
+				SubscriberEvent::SubscribeClosed(subscribe, code)
+			}
+			Some(SubscriberStream::Info) => SubscriberEvent::InfoClosed(id, code),
+			Some(SubscriberStream::Group { .. }) => SubscriberEvent::GroupClosed(id, code),
+			None => return,
+		};
+
+		self.events.push_back(event);
+	}
+
+	pub fn poll(&mut self) -> Option<SubscriberEvent> {
+		self.events.pop_front()
+	}
+
+	/// Close the stream locally, resetting it if there's an error code.
+	fn close(&mut self, streams: &mut Streams, id: StreamId, code: Option<ErrorCode>) -> Result<(), Error> {
+		if let Some(SubscriberStream::Subscribe { id: subscribe, .. }) = self.streams.remove(&id) {
+			self.subscribes.remove(&subscribe);
+		}
+
+		let stream = streams.get(id).ok_or(Error::UnknownStream)?;
+		match code {
+			Some(code) => stream.reset(code),
+			None => stream.finish(),
+		}
+
+		Ok(())
+	}
+}
+
+/// The subscriber half of a [super::Connection], used to make requests and respond to [SubscriberEvent]s.
+pub struct Subscriber<'a> {
+	pub(super) state: &'a mut SubscriberState,
+	pub(super) streams: &'a mut Streams,
 }
 
-impl Subscriber {
-	pub fn announces(&mut self) -> &mut SubscriberAnnounces {
-		&mut self.announces
+impl Subscriber<'_> {
+	fn open(&mut self, kind: StreamKind) -> &mut Stream {
+		let control = match kind {
+			StreamKind::Announce => message::ControlType::Announce,
+			StreamKind::Subscribe => message::ControlType::Subscribe,
+			StreamKind::Info => message::ControlType::Info,
+			_ => unreachable!("not a subscriber stream"),
+		};
+
+		let stream = self.streams.open(StreamDir::Bi, kind);
+		stream.encode(&control);
+		stream
+	}
+
+	/// Request announcements matching the filter, returning the stream used for [SubscriberEvent::Announced].
+	///
+	/// The request is written once the version has been negotiated, as the encoding depends on it.
+	pub fn announce(&mut self, request: message::AnnouncePlease) -> StreamId {
+		let cursor = self.state.cursor;
+		let stream = self.open(StreamKind::Announce);
+		let id = stream.id;
+
+		match cursor {
+			Some(cursor) => SubscriberState::encode_announce(stream, &request, cursor),
+			None => self.state.pending.push((id, request)),
+		}
+
+		self.state.streams.insert(id, SubscriberStream::Announce);
+
+		id
+	}
+
+	/// Subscribe to a track, returning the ID used for [SubscriberEvent::Subscribed] and any groups.
+	pub fn subscribe(&mut self, request: SubscribeRequest) -> SubscribeId {
+		let subscribe = self.state.next;
+		self.state.next.increment();
+
+		let stream = self.open(StreamKind::Subscribe);
+		stream.encode(&request.into_message(subscribe.0));
+
+		let id = stream.id;
+		self.state.subscribes.insert(subscribe, id);
+		self.state.streams.insert(
+			id,
+			SubscriberStream::Subscribe {
+				id: subscribe,
+				info: false,
+			},
+		);
+
+		subscribe
+	}
+
+	/// Request information about a track, returning the stream used for [SubscriberEvent::Info].
+	pub fn info(&mut self, request: message::InfoRequest) -> StreamId {
+		let stream = self.open(StreamKind::Info);
+		stream.encode(&request);
+
+		// The request is complete.
+		stream.finish();
+
+		let id = stream.id;
+		self.state.streams.insert(id, SubscriberStream::Info);
+
+		id
+	}
+
+	/// Return the announce stream, if it's still active.
+	pub fn announced(&mut self, id: StreamId) -> Option<SubscriberAnnounce<'_>> {
+		match self.state.streams.get(&id)? {
+			SubscriberStream::Announce => Some(SubscriberAnnounce::new(id, self.state, self.streams)),
+			_ => None,
+		}
 	}
 
-	pub fn subscribes(&mut self) -> &mut SubscriberSubscribes {
-		&mut self.subscribes
+	/// Return the subscription, if it's still active.
+	pub fn subscribed(&mut self, id: SubscribeId) -> Option<SubscriberSubscribe<'_>> {
+		let stream = *self.state.subscribes.get(&id)?;
+		Some(SubscriberSubscribe::new(id, stream, self.state, self.streams))
 	}
 
-	pub fn groups(&mut self) -> &mut SubscriberGroups {
-		&mut self.groups
+	/// Return the group stream, if it's still active.
+	pub fn group(&mut self, id: StreamId) -> Option<SubscriberGroup<'_>> {
+		match self.state.streams.get(&id)? {
+			SubscriberStream::Group { .. } => Some(SubscriberGroup::new(id, self.state, self.streams)),
+			_ => None,
+		}
 	}
 }
@@ -1,6 +1,7 @@
 use crate::message;
 
 // message::Subscribe but without the ID.
+#[derive(Clone, Debug)]
 pub struct SubscribeRequest {
 	pub path: String,
 	pub priority: i8,
@@ -11,7 +12,7 @@ impl SubscribeRequest {
 	pub fn into_message(self, id: u64) -> message::Subscribe {
 		message::Subscribe {
 			id,
-			path: self.path,
+			path: self.path.into(),
 			priority: self.priority,
 			order: self.order,
 
@@ -25,7 +26,7 @@ impl SubscribeRequest {
 impl From<message::Subscribe> for SubscribeRequest {
 	fn from(msg: message::Subscribe) -> Self {
 		Self {
-			path: msg.path,
+			path: msg.path.into(),
 			priority: msg.priority,
 			order: msg.order,
 		}
@@ -0,0 +1,426 @@
+use std::{collections::HashMap, fs, path::PathBuf, time};
+
+use anyhow::Context;
+use base64::Engine;
+use clap::Parser;
+use ring::{hmac, rand::SystemRandom};
+use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
+
+use crate::cli_defaults;
+
+// Tokens are base64url encoded without padding, per RFC 7515.
+const BASE64: base64::engine::GeneralPurpose = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+#[derive(Clone, Parser, Deserialize)]
+#[serde(default = "cli_defaults", deny_unknown_fields)]
+pub struct AuthConfig {
+	/// Require a signed token for each session, verified with the keys in this JWK set.
+	/// If not provided, then any session may publish or subscribe to any path.
+	///
+	/// The token is provided via the `jwt` query parameter in the connect URL.
+	#[arg(long)]
+	#[serde(rename = "key")]
+	pub auth_key: Option<PathBuf>,
+
+	/// The JWK set itself, only available via the config file.
+	#[arg(skip)]
+	#[serde(rename = "keys", deserialize_with = "KeySet::deserialize_keys")]
+	pub auth_keys: Option<KeySet>,
+
+	/// A secret shared by every node in the cluster, which nodes present to identify themselves as peers.
+	/// Only used when authorization is disabled; otherwise nodes present a token with the `cluster` claim.
+	#[arg(long)]
+	pub cluster_secret: Option<String>,
+}
+
+impl AuthConfig {
+	pub fn load(&self) -> anyhow::Result<Option<Auth>> {
+		if let Some(keys) = &self.auth_keys {
+			return Ok(Some(Auth::new(keys.clone())?));
+		}
+
+		let Some(path) = &self.auth_key else {
+			return Ok(None);
+		};
+
+		let data = fs::read(path).with_context(|| format!("failed to read auth keys: {}", path.display()))?;
+		let keys = serde_json::from_slice(&data).context("failed to parse auth keys")?;
+
+		Ok(Some(Auth::new(keys)?))
+	}
+}
+
+#[derive(thiserror::Error, Debug, Clone)]
+pub enum AuthError {
+	#[error("missing token")]
+	Missing,
+
+	#[error("malformed token")]
+	Malformed,
+
+	#[error("unknown key id: {0:?}")]
+	UnknownKey(Option<String>),
+
+	#[error("unsupported algorithm: {0}")]
+	Algorithm(String),
+
+	#[error("invalid signature")]
+	Signature,
+
+	#[error("token expired")]
+	Expired,
+
+	#[error("token not yet valid")]
+	NotBefore,
+}
+
+impl AuthError {
+	/// An application code used to close the session.
+	pub fn to_code(&self) -> u32 {
+		match self {
+			Self::Missing => 1,
+			Self::Malformed => 2,
+			Self::UnknownKey(_) => 3,
+			Self::Algorithm(_) => 4,
+			Self::Signature => 5,
+			Self::Expired => 6,
+			Self::NotBefore => 7,
+		}
+	}
+}
+
+/// A JWK set containing symmetric (`oct`) keys.
+#[derive(Clone, Deserialize)]
+pub struct KeySet {
+	pub keys: Vec<Key>,
+}
+
+impl KeySet {
+	// The config file lists the keys directly, rather than nested in a JWK set.
+	fn deserialize_keys<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Self>, D::Error> {
+		let keys = Vec::<Key>::deserialize(deserializer)?;
+		Ok(Some(Self { keys }))
+	}
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Key {
+	pub kty: String,
+	pub kid: Option<String>,
+	pub alg: Option<String>,
+
+	/// The secret, base64url encoded.
+	pub k: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Header {
+	alg: String,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	kid: Option<String>,
+}
+
+/// The permissions granted to a session.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Claims {
+	/// The identity of the session, used to look up any per-identity limits.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub sub: Option<String>,
+
+	/// The path prefixes the session may announce. An empty prefix allows any path.
+	#[serde(default)]
+	pub publish: Vec<String>,
+
+	/// The path prefixes the session may subscribe to or discover. An empty prefix allows any path.
+	#[serde(default)]
+	pub subscribe: Vec<String>,
+
+	/// The expiration time, in seconds since the UNIX epoch.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub exp: Option<u64>,
+
+	/// The time before which the token is not valid, in seconds since the UNIX epoch.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub nbf: Option<u64>,
+
+	/// The session is another node in the cluster, permitted to use the internal namespace.
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub cluster: bool,
+}
+
+impl Claims {
+	/// Permit publishing and subscribing to any path.
+	pub fn any() -> Self {
+		Self {
+			publish: vec!["".to_string()],
+			subscribe: vec!["".to_string()],
+			..Default::default()
+		}
+	}
+
+	pub fn can_publish(&self, path: &str) -> bool {
+		self.publish.iter().any(|prefix| has_prefix(path, prefix))
+	}
+
+	pub fn can_subscribe(&self, path: &str) -> bool {
+		self.subscribe.iter().any(|prefix| has_prefix(path, prefix))
+	}
+}
+
+/// Returns true if the path starts with the prefix at a segment boundary, so `alice` doesn't match `alice2/video`.
+pub fn has_prefix(path: &str, prefix: &str) -> bool {
+	match path.strip_prefix(prefix) {
+		Some(rest) => prefix.is_empty() || prefix.ends_with('/') || rest.is_empty() || rest.starts_with('/'),
+		None => false,
+	}
+}
+
+/// Returns the prefixes with any that are covered by a shorter prefix removed, so paths aren't matched twice.
+pub fn distinct_prefixes(prefixes: &[String]) -> Vec<String> {
+	let mut sorted = prefixes.to_vec();
+	sorted.sort();
+
+	let mut distinct: Vec<String> = Vec::new();
+	for prefix in sorted {
+		if !distinct.iter().any(|existing| has_prefix(&prefix, existing)) {
+			distinct.push(prefix);
+		}
+	}
+
+	distinct
+}
+
+/// Compare two secrets without leaking how much of them matched via timing.
+pub fn secrets_equal(a: &str, b: &str) -> bool {
+	// ring only exposes a constant-time comparison for MACs, so compare a MAC of each secret instead.
+	let key = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new()).expect("failed to generate key");
+	let tag = hmac::sign(&key, b.as_bytes());
+
+	hmac::verify(&key, a.as_bytes(), tag.as_ref()).is_ok()
+}
+
+/// Verifies HMAC signed JSON Web Tokens.
+pub struct Auth {
+	// Indexed by the key ID, which is optional if there's only one key.
+	keys: HashMap<Option<String>, hmac::Key>,
+
+	// The ID of the first key, used to sign our own tokens.
+	first: Option<String>,
+}
+
+impl Auth {
+	pub fn new(set: KeySet) -> anyhow::Result<Self> {
+		let mut keys = HashMap::new();
+		let first = set.keys.first().and_then(|key| key.kid.clone());
+
+		for key in set.keys {
+			anyhow::ensure!(key.kty == "oct", "unsupported key type: {}", key.kty);
+
+			let secret = BASE64.decode(&key.k).context("invalid key encoding")?;
+			let alg = key.alg.as_deref().unwrap_or("HS256");
+			let algorithm = Self::algorithm(alg).map_err(|_| anyhow::anyhow!("unsupported key algorithm: {}", alg))?;
+
+			anyhow::ensure!(
+				keys.insert(key.kid, hmac::Key::new(algorithm, &secret)).is_none(),
+				"duplicate key id"
+			);
+		}
+
+		anyhow::ensure!(!keys.is_empty(), "no keys provided");
+
+		Ok(Self { keys, first })
+	}
+
+	fn algorithm(alg: &str) -> Result<hmac::Algorithm, AuthError> {
+		match alg {
+			"HS256" => Ok(hmac::HMAC_SHA256),
+			"HS384" => Ok(hmac::HMAC_SHA384),
+			"HS512" => Ok(hmac::HMAC_SHA512),
+			_ => Err(AuthError::Algorithm(alg.to_string())),
+		}
+	}
+
+	/// Verify the token in the `jwt` query parameter, or otherwise the one presented during the handshake.
+	///
+	/// The query parameter is used by browsers, which can't set a setup extension.
+	pub fn verify_session(&self, url: Option<&Url>, session: &moq_transfork::Session) -> Result<Claims, AuthError> {
+		let query = url.and_then(|url| url.query_pairs().find(|(key, _)| key == "jwt"));
+
+		let token = match &query {
+			Some((_, token)) => token,
+			None => session.authorization().ok_or(AuthError::Missing)?,
+		};
+
+		self.verify(token)
+	}
+
+	/// Verify a token, returning its claims.
+	pub fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+		let (message, signature) = token.rsplit_once('.').ok_or(AuthError::Malformed)?;
+		let (header, claims) = message.split_once('.').ok_or(AuthError::Malformed)?;
+
+		let header: Header = Self::decode(header)?;
+
+		let key = self.keys.get(&header.kid).ok_or(AuthError::UnknownKey(header.kid))?;
+
+		// Prevent algorithm substitution; the key determines the algorithm.
+		if Self::algorithm(&header.alg)? != key.algorithm() {
+			return Err(AuthError::Algorithm(header.alg));
+		}
+
+		let signature = BASE64.decode(signature).map_err(|_| AuthError::Malformed)?;
+		hmac::verify(key, message.as_bytes(), &signature).map_err(|_| AuthError::Signature)?;
+
+		let claims: Claims = Self::decode(claims)?;
+
+		let now = time::SystemTime::now()
+			.duration_since(time::UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs();
+
+		if claims.exp.is_some_and(|exp| now >= exp) {
+			return Err(AuthError::Expired);
+		}
+
+		if claims.nbf.is_some_and(|nbf| now < nbf) {
+			return Err(AuthError::NotBefore);
+		}
+
+		Ok(claims)
+	}
+
+	fn decode<T: serde::de::DeserializeOwned>(part: &str) -> Result<T, AuthError> {
+		let json = BASE64.decode(part).map_err(|_| AuthError::Malformed)?;
+		serde_json::from_slice(&json).map_err(|_| AuthError::Malformed)
+	}
+
+	/// Sign the claims with the first key, returning a token.
+	pub fn sign(&self, claims: &Claims) -> anyhow::Result<String> {
+		self.sign_with(self.first.as_deref(), claims)
+	}
+
+	/// Sign the claims with the given key, returning a token.
+	pub fn sign_with(&self, kid: Option<&str>, claims: &Claims) -> anyhow::Result<String> {
+		let kid = kid.map(|kid| kid.to_string());
+		let key = self.keys.get(&kid).context("unknown key id")?;
+
+		let alg = match key.algorithm() {
+			alg if alg == hmac::HMAC_SHA384 => "HS384",
+			alg if alg == hmac::HMAC_SHA512 => "HS512",
+			_ => "HS256",
+		};
+
+		let header = Header {
+			alg: alg.to_string(),
+			kid,
+		};
+
+		let message = format!(
+			"{}.{}",
+			BASE64.encode(serde_json::to_vec(&header)?),
+			BASE64.encode(serde_json::to_vec(claims)?)
+		);
+		let signature = hmac::sign(key, message.as_bytes());
+
+		Ok(format!("{}.{}", message, BASE64.encode(signature.as_ref())))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn auth() -> Auth {
+		let set = serde_json::json!({
+			"keys": [
+				{ "kty": "oct", "kid": "a", "k": BASE64.encode(b"secret a") },
+				{ "kty": "oct", "kid": "b", "alg": "HS512", "k": BASE64.encode(b"secret b") },
+			]
+		});
+
+		Auth::new(serde_json::from_value(set).unwrap()).unwrap()
+	}
+
+	#[test]
+	fn verify() {
+		let auth = auth();
+
+		let claims = Claims {
+			sub: Some("alice".to_string()),
+			publish: vec!["alice/".to_string()],
+			subscribe: vec!["".to_string()],
+			..Default::default()
+		};
+
+		for kid in ["a", "b"] {
+			let token = auth.sign_with(Some(kid), &claims).unwrap();
+			assert_eq!(auth.verify(&token).unwrap(), claims);
+		}
+
+		let claims = auth.verify(&auth.sign(&claims).unwrap()).unwrap();
+		assert!(claims.can_publish("alice/video"));
+		assert!(!claims.can_publish("bob/video"));
+		assert!(claims.can_subscribe("bob/video"));
+	}
+
+	#[test]
+	fn reject() {
+		let auth = auth();
+		let token = auth.sign(&Claims::default()).unwrap();
+
+		// Tamper with the claims.
+		let mut parts: Vec<&str> = token.split('.').collect();
+		let forged = BASE64.encode(br#"{"publish":[""]}"#);
+		parts[1] = &forged;
+		assert!(matches!(auth.verify(&parts.join(".")), Err(AuthError::Signature)));
+
+		assert!(matches!(auth.verify("garbage"), Err(AuthError::Malformed)));
+
+		let expired = Claims {
+			exp: Some(1),
+			..Default::default()
+		};
+		let token = auth.sign(&expired).unwrap();
+		assert!(matches!(auth.verify(&token), Err(AuthError::Expired)));
+	}
+
+	#[test]
+	fn secret() {
+		assert!(secrets_equal("hunter2", "hunter2"));
+		assert!(!secrets_equal("hunter2", "hunter3"));
+		assert!(!secrets_equal("hunter2", ""));
+	}
+
+	#[test]
+	fn prefixes() {
+		let prefixes = ["a/b".to_string(), "a/".to_string(), "c".to_string()];
+		assert_eq!(distinct_prefixes(&prefixes), vec!["a/".to_string(), "c".to_string()]);
+
+		// A prefix only covers another at a segment boundary.
+		let prefixes = ["alice".to_string(), "alice2".to_string(), "alice/video".to_string()];
+		assert_eq!(
+			distinct_prefixes(&prefixes),
+			vec!["alice".to_string(), "alice2".to_string()]
+		);
+	}
+
+	#[test]
+	fn boundary() {
+		let claims = Claims {
+			publish: vec!["alice".to_string()],
+			subscribe: vec!["bob/".to_string()],
+			..Default::default()
+		};
+
+		assert!(claims.can_publish("alice"));
+		assert!(claims.can_publish("alice/video"));
+		assert!(!claims.can_publish("alice2/video"));
+		assert!(!claims.can_publish("alic"));
+
+		assert!(claims.can_subscribe("bob/video"));
+		assert!(!claims.can_subscribe("bob"));
+		assert!(!claims.can_subscribe("bobby/video"));
+	}
+}
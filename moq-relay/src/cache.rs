@@ -0,0 +1,306 @@
+use std::{
+	collections::{BTreeMap, HashMap, VecDeque},
+	sync::{Arc, Mutex, Weak},
+	time,
+};
+
+use clap::Parser;
+use moq_transfork::{GroupConsumer, Track, TrackConsumer, TrackProducer};
+use serde::Deserialize;
+
+use crate::cli_defaults;
+
+#[derive(Clone, Parser, PartialEq, Deserialize)]
+#[serde(default = "cli_defaults", deny_unknown_fields)]
+pub struct CacheConfig {
+	/// Keep recent groups for this many seconds.
+	/// A track remains subscribed for this long after its last subscriber leaves, so new subscribers are served immediately.
+	#[arg(long, default_value = "30")]
+	#[serde(rename = "duration")]
+	pub cache_duration: u64,
+
+	/// The maximum size of the cached groups across all tracks, in bytes.
+	/// The oldest groups are evicted first.
+	#[arg(long, default_value = "268435456")]
+	#[serde(rename = "size")]
+	pub cache_size: u64,
+}
+
+/// Recent groups for each track path, kept by the relay even after the origin goes away.
+#[derive(Clone)]
+pub struct Cache {
+	duration: time::Duration,
+	size: u64,
+	state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+	tracks: HashMap<String, CacheTrack>,
+
+	// Every cached group in the order they were created, so the oldest can be evicted first.
+	order: VecDeque<(String, u64)>,
+
+	// The total size of the cached groups.
+	size: u64,
+
+	// Used to uniquely identify each upstream subscription.
+	next: u64,
+}
+
+#[derive(Default)]
+struct CacheTrack {
+	// The track we're forwarding from upstream, if any.
+	active: Option<(u64, TrackProducer)>,
+
+	// The track requested by each subscriber, which is dropped along with their consumer (and any clones).
+	requests: Vec<Weak<Track>>,
+
+	// Indexed by the group sequence number.
+	groups: BTreeMap<u64, CacheGroup>,
+}
+
+struct CacheGroup {
+	consumer: GroupConsumer,
+	created: time::Instant,
+	size: u64,
+}
+
+impl Cache {
+	pub fn new(config: CacheConfig) -> Self {
+		Self {
+			duration: time::Duration::from_secs(config.cache_duration),
+			size: config.cache_size,
+			state: Default::default(),
+		}
+	}
+
+	/// How long groups are cached, and tracks remain subscribed after they're unused.
+	pub fn duration(&self) -> time::Duration {
+		self.duration
+	}
+
+	/// Returns a new consumer for the track if we're already subscribed upstream.
+	///
+	/// The request counts towards [Self::requested] until the consumer is dropped.
+	/// NOTE: Like any track, the consumer starts at the latest group; older groups are only available via [Self::group].
+	pub fn subscribe(&self, request: &Track) -> Option<TrackConsumer> {
+		let mut state = self.state.lock().unwrap();
+		let track = state.tracks.get_mut(&request.path)?;
+		let (_, producer) = track.active.as_ref()?;
+
+		let info = Arc::new(request.clone());
+		track.requests.push(Arc::downgrade(&info));
+
+		Some(producer.subscribe_as(info))
+	}
+
+	/// Returns the aggregate of every active request for the track: the highest priority and its order.
+	pub fn requested(&self, path: &str) -> Option<Track> {
+		let mut state = self.state.lock().unwrap();
+		let requests = &mut state.tracks.get_mut(path)?.requests;
+		requests.retain(|request| request.strong_count() > 0);
+
+		requests
+			.iter()
+			.filter_map(Weak::upgrade)
+			.reduce(|best, request| match request.priority > best.priority {
+				true => request,
+				false => best,
+			})
+			.map(|best| best.as_ref().clone())
+	}
+
+	/// Serve any new subscriptions using this track, returning an ID used to [Self::remove] it.
+	pub fn insert(&self, track: TrackProducer) -> u64 {
+		let mut state = self.state.lock().unwrap();
+
+		let id = state.next;
+		state.next += 1;
+
+		let path = track.path.clone();
+		let cached = state.tracks.entry(path).or_default();
+		cached.active = Some((id, track));
+		cached.requests.clear();
+
+		id
+	}
+
+	/// Stop serving new subscriptions using the track, keeping any cached groups until they expire.
+	pub fn remove(&self, path: &str, id: u64) {
+		let mut state = self.state.lock().unwrap();
+
+		let Some(track) = state.tracks.get_mut(path) else {
+			return;
+		};
+
+		if track.active.as_ref().is_some_and(|(active, _)| *active == id) {
+			track.active = None;
+			track.requests.clear();
+		}
+
+		if track.active.is_none() && track.groups.is_empty() {
+			state.tracks.remove(path);
+		}
+	}
+
+	/// Cache a group, evicting any old groups if needed.
+	pub fn insert_group(&self, path: &str, group: GroupConsumer) {
+		let mut guard = self.state.lock().unwrap();
+		let state = &mut *guard;
+
+		let sequence = group.sequence;
+		let cached = CacheGroup {
+			consumer: group,
+			created: time::Instant::now(),
+			size: 0,
+		};
+
+		let track = state.tracks.entry(path.to_string()).or_default();
+		if let Some(replaced) = track.groups.insert(sequence, cached) {
+			// The replaced group no longer counts towards the size, and the new one is evicted last.
+			state.size -= replaced.size;
+			state.order.retain(|(p, s)| *s != sequence || p != path);
+		}
+
+		state.order.push_back((path.to_string(), sequence));
+
+		self.evict(state);
+	}
+
+	/// Increase the size of a cached group, evicting any old groups if needed.
+	pub fn grow_group(&self, path: &str, sequence: u64, size: usize) {
+		let mut state = self.state.lock().unwrap();
+
+		let Some(group) = state
+			.tracks
+			.get_mut(path)
+			.and_then(|track| track.groups.get_mut(&sequence))
+		else {
+			// Already evicted.
+			return;
+		};
+
+		group.size += size as u64;
+		state.size += size as u64;
+
+		self.evict(&mut state);
+	}
+
+	/// Returns the cached group with the given sequence number.
+	pub fn group(&self, path: &str, sequence: u64) -> Option<GroupConsumer> {
+		let state = self.state.lock().unwrap();
+		let group = state.tracks.get(path)?.groups.get(&sequence)?;
+		Some(group.consumer.clone())
+	}
+
+	/// Returns the most recent cached group.
+	pub fn latest(&self, path: &str) -> Option<GroupConsumer> {
+		let state = self.state.lock().unwrap();
+		let (_, group) = state.tracks.get(path)?.groups.last_key_value()?;
+		Some(group.consumer.clone())
+	}
+
+	/// Periodically evict any expired groups, as otherwise they're only evicted when new groups are cached.
+	pub async fn run(self) {
+		let mut interval = tokio::time::interval(time::Duration::from_secs(1));
+
+		loop {
+			interval.tick().await;
+			self.evict(&mut self.state.lock().unwrap());
+		}
+	}
+
+	/// Returns the number of cached groups and their total size.
+	pub fn usage(&self) -> (usize, u64) {
+		let state = self.state.lock().unwrap();
+		(state.order.len(), state.size)
+	}
+
+	// Remove the oldest groups until we're under the size limit and none have expired.
+	fn evict(&self, state: &mut CacheState) {
+		let now = time::Instant::now();
+
+		while let Some((path, sequence)) = state.order.front().cloned() {
+			let track = state.tracks.get_mut(&path).expect("missing cached track");
+			let group = track.groups.get(&sequence).expect("missing cached group");
+
+			if state.size <= self.size && now.duration_since(group.created) < self.duration {
+				break;
+			}
+
+			state.order.pop_front();
+			state.size -= group.size;
+			track.groups.remove(&sequence);
+
+			if track.active.is_none() && track.groups.is_empty() {
+				state.tracks.remove(&path);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use moq_transfork::{Group, Track};
+
+	#[test]
+	fn evict() {
+		let cache = Cache::new(CacheConfig {
+			cache_duration: 60,
+			cache_size: 100,
+		});
+
+		let (track, _) = Track::new("demo/video").produce();
+		let id = cache.insert(track);
+		assert!(cache.subscribe(&Track::new("demo/video")).is_some());
+
+		for sequence in 0..3 {
+			let (_, group) = Group::new(sequence).produce();
+			cache.insert_group("demo/video", group);
+			cache.grow_group("demo/video", sequence, 40);
+		}
+
+		// The oldest group was evicted to stay within the size limit.
+		assert!(cache.group("demo/video", 0).is_none());
+		assert!(cache.group("demo/video", 1).is_some());
+		assert_eq!(cache.latest("demo/video").unwrap().sequence, 2);
+		assert_eq!(cache.usage(), (2, 80));
+
+		// The groups outlive the upstream subscription.
+		cache.remove("demo/video", id);
+		assert!(cache.subscribe(&Track::new("demo/video")).is_none());
+		assert!(cache.group("demo/video", 2).is_some());
+	}
+
+	#[test]
+	fn replace() {
+		let cache = Cache::new(CacheConfig {
+			cache_duration: 60,
+			cache_size: 100,
+		});
+
+		for sequence in 0..2 {
+			let (_, group) = Group::new(sequence).produce();
+			cache.insert_group("demo/video", group);
+			cache.grow_group("demo/video", sequence, 40);
+		}
+
+		// Caching the same sequence again replaces the group and its size.
+		let (_, group) = Group::new(0).produce();
+		cache.insert_group("demo/video", group);
+		assert_eq!(cache.usage(), (2, 40));
+
+		cache.grow_group("demo/video", 0, 30);
+		assert_eq!(cache.usage(), (2, 70));
+
+		// The replacement is now the newest group, so it's evicted last.
+		let (_, group) = Group::new(2).produce();
+		cache.insert_group("demo/video", group);
+		cache.grow_group("demo/video", 2, 40);
+		assert!(cache.group("demo/video", 1).is_none());
+		assert!(cache.group("demo/video", 0).is_some());
+		assert_eq!(cache.usage(), (2, 70));
+	}
+}
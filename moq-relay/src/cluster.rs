@@ -1,21 +1,118 @@
-use std::collections::HashMap;
+use std::{
+	collections::{BTreeMap, HashMap},
+	hash::{BuildHasher, RandomState},
+	sync::{Arc, Mutex},
+	time,
+};
 
 use anyhow::Context;
 use clap::Parser;
 use moq_native::quic;
-use moq_transfork::{Announced, AnnouncedProducer, Error, Filter, Router, RouterConsumer, RouterProducer};
+use moq_transfork::{
+	Announced, AnnouncedProducer, Error, Filter, GroupConsumer, GroupProducer, Router, RouterConsumer, RouterProducer,
+	Session, Track, TrackConsumer, TrackProducer,
+};
+use serde::Deserialize;
+use tokio::sync::watch;
 use tracing::Instrument;
 use url::Url;
 
-use crate::Origins;
+use crate::{cli_defaults, secrets_equal, Cache, Metrics, Origin, OriginPolicy, Origins};
 
-#[derive(Clone, Parser)]
+/// The namespace used by the cluster to discover other nodes, hidden from users.
+pub const INTERNAL: &str = "internal/";
+
+// The delay before reconnecting to a remote after the first failure, doubled after each consecutive failure.
+const BACKOFF_MIN: time::Duration = time::Duration::from_secs(1);
+const BACKOFF_MAX: time::Duration = time::Duration::from_secs(30);
+
+// Give up on connecting to a remote after this long, rather than waiting for the QUIC idle timeout.
+const CONNECT_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+// A connection must be healthy for this long before the backoff is reset, so a flapping remote still backs off.
+const BACKOFF_RESET: time::Duration = time::Duration::from_secs(10);
+
+// How long a subscription waits for another origin after its origin fails, before giving up.
+const FAILOVER_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+/// Whether local or remote origins are used when both announce the same path.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OriginPrefer {
+	/// Prefer sessions connected directly to this node.
+	#[default]
+	Local,
+
+	/// Prefer other nodes in the cluster.
+	Remote,
+}
+
+/// The state of our connection to another node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteState {
+	/// Establishing the session.
+	Connecting,
+
+	/// The session is established and the remote's tracks are routable.
+	Healthy,
+
+	/// The session failed, and we're waiting to reconnect.
+	Failed,
+}
+
+impl RemoteState {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Self::Connecting => "connecting",
+			Self::Healthy => "healthy",
+			Self::Failed => "failed",
+		}
+	}
+}
+
+/// The health of our connection to another node.
+#[derive(Clone, Debug)]
+pub struct RemoteStatus {
+	pub state: RemoteState,
+
+	/// When the state last changed.
+	pub since: time::Instant,
+
+	/// The number of consecutive failures.
+	pub failures: u32,
+
+	/// The most recent failure, even if we've since reconnected.
+	pub error: Option<String>,
+}
+
+impl RemoteStatus {
+	fn new() -> Self {
+		Self {
+			state: RemoteState::Connecting,
+			since: time::Instant::now(),
+			failures: 0,
+			error: None,
+		}
+	}
+
+	fn set(&mut self, state: RemoteState) {
+		self.state = state;
+		self.since = time::Instant::now();
+	}
+}
+
+/// The health of every remote, indexed by hostname.
+pub type RemoteHealth = Arc<Mutex<BTreeMap<String, RemoteStatus>>>;
+
+#[derive(Clone, Parser, PartialEq, Deserialize)]
+#[serde(default = "cli_defaults", deny_unknown_fields)]
 pub struct ClusterConfig {
 	/// Announce our tracks and discover other origins via this server.
 	/// If not provided, then clustering is disabled.
 	///
 	/// Peers will connect to use via this hostname.
 	#[arg(long)]
+	#[serde(rename = "root")]
 	pub cluster_root: Option<String>,
 
 	/// Our unique name which we advertise to other origins.
@@ -23,7 +120,16 @@ pub struct ClusterConfig {
 	///
 	/// Peers will connect to use via this hostname.
 	#[arg(long)]
+	#[serde(rename = "node")]
 	pub cluster_node: Option<String>,
+
+	/// Which origin owns a path when multiple sessions announce it.
+	#[arg(long, value_enum, default_value_t)]
+	pub origin_policy: OriginPolicy,
+
+	/// Whether local or remote origins are used when both announce the same path.
+	#[arg(long, value_enum, default_value_t)]
+	pub origin_prefer: OriginPrefer,
 }
 
 #[derive(Clone)]
@@ -31,52 +137,305 @@ pub struct Cluster {
 	config: ClusterConfig,
 	client: quic::Client,
 
+	// Presented to other nodes when authorization is enabled, as every node shares the same keys.
+	token: watch::Receiver<Option<String>>,
+
 	// Tracks announced by local clients (users).
 	pub locals: Origins,
 
 	// Tracks announced by remote servers (cluster).
 	pub remotes: Origins,
 
+	// The internal namespace, only announced to other nodes.
+	pub internal: Origins,
+
+	// Recent groups for each track, also used to share upstream subscriptions.
+	pub cache: Cache,
+
+	// Served by the `/metrics` endpoint.
+	pub metrics: Metrics,
+
+	// The state of our connection to each remote.
+	health: RemoteHealth,
+
 	// Used to route incoming requests to the origins above.
 	pub router: RouterConsumer,
 }
 
 impl Cluster {
-	pub fn new(config: ClusterConfig, client: quic::Client) -> Self {
+	pub fn new(
+		config: ClusterConfig,
+		cache: Cache,
+		client: quic::Client,
+		token: watch::Receiver<Option<String>>,
+	) -> Self {
 		let (producer, consumer) = Router { capacity: 1024 }.produce();
 
+		let locals = Origins::new(config.origin_policy);
+		let remotes = Origins::new(config.origin_policy);
+		let health = RemoteHealth::default();
+		let metrics = Metrics::new(
+			config.cluster_node.clone(),
+			locals.clone(),
+			remotes.clone(),
+			cache.clone(),
+			health.clone(),
+		);
+
 		let this = Cluster {
 			config,
 			client,
+			token,
 			router: consumer,
-			locals: Origins::new(),
-			remotes: Origins::new(),
+			locals,
+			remotes,
+			internal: Origins::internal(),
+			cache,
+			metrics,
+			health,
 		};
 
 		tokio::spawn(this.clone().run_router(producer).in_current_span());
+		tokio::spawn(this.cache.clone().run());
 
 		this
 	}
 
+	/// The hostname of this node, if it's part of a cluster.
+	pub fn node(&self) -> Option<&str> {
+		self.config.cluster_node.as_deref()
+	}
+
+	/// Returns true if the token is the secret shared by the cluster, identifying another node.
+	///
+	/// Only used when authorization is disabled, in which case we present the same secret to other nodes.
+	pub fn is_peer(&self, token: Option<&str>) -> bool {
+		match (token, self.token.borrow().as_deref()) {
+			(Some(token), Some(secret)) => secrets_equal(token, secret),
+			_ => false,
+		}
+	}
+
+	/// Returns the health of our connection to each remote, indexed by hostname.
+	pub fn health(&self) -> BTreeMap<String, RemoteStatus> {
+		self.health.lock().unwrap().clone()
+	}
+
+	// Update the health of a remote, inserting it if needed.
+	fn update_health<F: FnOnce(&mut RemoteStatus)>(&self, host: &str, f: F) {
+		let mut health = self.health.lock().unwrap();
+		f(health.entry(host.to_string()).or_insert_with(RemoteStatus::new));
+	}
+
+	/// Returns the session that owns the path, based on the configured policy.
+	pub fn route(&self, path: &str) -> Option<Session> {
+		let (first, second) = match self.config.origin_prefer {
+			OriginPrefer::Local => (&self.locals, &self.remotes),
+			OriginPrefer::Remote => (&self.remotes, &self.locals),
+		};
+
+		first.route(path).or_else(|| second.route(path))
+	}
+
 	// This is the GUTS of the entire relay.
 	// We route any incoming track requests to the appropriate session.
 	async fn run_router(self, mut router: RouterProducer) {
 		while let Some(req) = router.requested().await {
-			let origin = if let Some(origin) = self.locals.route(&req.track.path).clone() {
-				origin
-			} else if let Some(origin) = self.remotes.route(&req.track.path).clone() {
-				origin
-			} else {
+			// Serve an existing subscription without a round trip to the origin.
+			if let Some(track) = self.cache.subscribe(&req.track) {
+				req.serve(track);
+				continue;
+			}
+
+			let Some(origin) = self.route(&req.track.path) else {
 				req.close(Error::NotFound);
 				continue;
 			};
 
-			let track = origin.subscribe(req.track.clone());
-			req.serve(track)
+			// Serve our own copy of the track, so the subscription can move to another origin.
+			let (track, _) = req.track.clone().produce();
+			let id = self.cache.insert(track.clone());
+			tokio::spawn(self.clone().run_track(track, origin, id).instrument(req.span.clone()));
+
+			// The first subscriber is served like any other, so its priority is counted too.
+			match self.cache.subscribe(&req.track) {
+				Some(track) => req.serve(track),
+				None => req.close(Error::NotFound),
+			}
+		}
+	}
+
+	async fn run_track(self, track: TrackProducer, origin: Session, id: u64) {
+		let res = self.forward(&track, origin).await;
+
+		// Any cached groups are kept, but new subscriptions will subscribe to the origin again.
+		self.cache.remove(&track.path, id);
+
+		if let Err(err) = res {
+			track.close(err);
+		}
+	}
+
+	// Forward the track from its origin until it's been unused for the cache duration.
+	// If the owner of the path changes, or the origin goes away, then the subscription moves over to the new owner.
+	async fn forward(&self, track: &TrackProducer, mut origin: Session) -> Result<(), Error> {
+		let mut locals = self.locals.changes();
+		let mut remotes = self.remotes.changes();
+		let mut demand = track.demand();
+
+		// The latest group sequence number, used to drop any older groups from a new origin.
+		let mut latest = None;
+
+		// The aggregate of every subscriber's request, sent upstream.
+		let mut requested = self.requested(track);
+
+		// Set when the request changes, subscribing again before the current subscription is dropped.
+		let mut next = None;
+
+		'subscribe: loop {
+			let upstream = next.take().unwrap_or_else(|| origin.subscribe(requested.clone()));
+			let forward = Self::forward_track(&self.cache, upstream, track.clone(), &mut latest);
+			tokio::pin!(forward);
+
+			let res = loop {
+				tokio::select! {
+					res = &mut forward => break res,
+					Ok(()) = locals.changed() => {},
+					Ok(()) = remotes.changed() => {},
+					Ok(()) = demand.changed() => {
+						let update = self.requested(track);
+						if update != requested {
+							tracing::debug!(path = ?track.path, priority = update.priority, order = ?update.order, "updating subscription");
+
+							// The session deduplicates subscriptions to the same path, so this only sends an update.
+							next = Some(origin.subscribe(update.clone()));
+							requested = update;
+
+							continue 'subscribe;
+						}
+					},
+					_ = self.idle(track) => return Ok(()),
+				}
+
+				match self.route(&track.path) {
+					Some(next) if next != origin => {
+						tracing::info!(path = ?track.path, "moving subscription to new owner");
+						break Ok(());
+					}
+					_ => {}
+				}
+			};
+
+			// Wait for a new owner if the origin failed, as it may not have been removed yet.
+			let deadline = tokio::time::Instant::now() + FAILOVER_TIMEOUT;
+
+			let next = loop {
+				match self.route(&track.path) {
+					Some(next) if next != origin => break Some(next),
+					// The origin ended the track, so we're done.
+					_ if res.is_ok() => break None,
+					_ => {}
+				}
+
+				tokio::select! {
+					Ok(()) = locals.changed() => {},
+					Ok(()) = remotes.changed() => {},
+					_ = tokio::time::sleep_until(deadline) => break None,
+					_ = self.idle(track) => return Ok(()),
+				}
+			};
+
+			match next {
+				Some(next) => {
+					tracing::info!(path = ?track.path, ?res, "subscribing to new origin");
+					origin = next;
+				}
+				None => return res,
+			}
+		}
+	}
+
+	// Returns the aggregate of every subscriber's request, or the original request if there are none.
+	fn requested(&self, track: &TrackProducer) -> Track {
+		self.cache
+			.requested(&track.path)
+			.unwrap_or_else(|| track.info.as_ref().clone())
+	}
+
+	// Resolves once the track has had no subscribers for the cache duration.
+	async fn idle(&self, track: &TrackProducer) {
+		loop {
+			track.unsubscribed().await;
+
+			tokio::select! {
+				_ = tokio::time::sleep(self.cache.duration()) => return,
+				_ = track.subscribed() => {},
+			}
+		}
+	}
+
+	// Copy each group from the upstream track into the cache, updating the latest sequence number.
+	//
+	// Sequence numbers are forwarded unchanged, so any group older than one already forwarded (ex. from the previous origin) is dropped.
+	async fn forward_track(
+		cache: &Cache,
+		mut upstream: TrackConsumer,
+		mut track: TrackProducer,
+		latest: &mut Option<u64>,
+	) -> Result<(), Error> {
+		while let Some(group) = upstream.next_group().await? {
+			if latest.is_some_and(|latest| group.sequence <= latest) {
+				tracing::debug!(path = ?track.path, sequence = group.sequence, ?latest, "dropping old group");
+				continue;
+			}
+
+			*latest = Some(group.sequence);
+
+			let mut downstream = track.create_group(group.sequence);
+			cache.insert_group(&track.path, downstream.subscribe());
+
+			let cache = cache.clone();
+			let path = track.path.clone();
+			tokio::spawn(
+				async move {
+					if let Err(err) = Self::forward_frames(&cache, &path, group, &mut downstream).await {
+						downstream.close(err);
+					}
+				}
+				.in_current_span(),
+			);
 		}
+
+		Ok(())
 	}
 
-	pub async fn run(self) -> anyhow::Result<()> {
+	async fn forward_frames(
+		cache: &Cache,
+		path: &str,
+		mut upstream: GroupConsumer,
+		downstream: &mut GroupProducer,
+	) -> Result<(), Error> {
+		while let Some(mut frame) = upstream.next_frame().await? {
+			let mut copy = downstream.create_frame(frame.size);
+			cache.grow_group(path, downstream.sequence, frame.size);
+
+			// Close the partial frame on error, otherwise it would look complete.
+			loop {
+				match frame.read().await {
+					Ok(Some(chunk)) => copy.write(chunk),
+					Ok(None) => break,
+					Err(err) => {
+						copy.close(err.clone());
+						return Err(err);
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	pub async fn run(self) {
 		let root = self.config.cluster_root.clone();
 		let node = self.config.cluster_node.as_ref().map(|node| node.to_string());
 
@@ -85,44 +444,38 @@ impl Cluster {
 		// If we're a node, then we need to announce ourselves as an origin.
 		let mut myself = AnnouncedProducer::new();
 		if let Some(node) = self.config.cluster_node.as_ref() {
-			let origin = format!("internal/origins/{}", node);
+			let origin = format!("{}origins/{}", INTERNAL, node);
 			myself.announce(origin);
 		}
 
-		let filter = Filter::Prefix("internal/origins/".into());
+		let filter = Filter::Prefix(format!("{}origins/", INTERNAL));
 
-		// If we're using a root node, then we have to connect to it.
 		let mut announced = match root.as_ref() {
-			Some(root) if Some(root) != node.as_ref() => {
-				tracing::info!(?root, "connecting to root");
-
-				// Connect to the root node.
-				let root = Url::parse(&format!("https://{}", root)).context("invalid root URL")?;
-				let root = self.client.connect(root).await.context("failed to connect to root")?;
+			// If we're using a root node, then we have to connect to it.
+			Some(host) if Some(host) != node.as_ref() => {
+				// The origins discovered via the root, kept across reconnects.
+				let discovered = AnnouncedProducer::new();
+				let announced = discovered.subscribe(filter.clone());
 
-				let mut root = moq_transfork::Session::connect(root)
-					.await
-					.context("failed to establish root session")?;
+				let root = self.clone().run_root(host.clone(), myself, filter, discovered);
+				tokio::spawn(root.in_current_span());
 
-				// Announce ourselves as an origin to the root node.
-				root.announce(myself.subscribe(Filter::Any));
-
-				// Subscribe to available origins.
-				root.announced(filter)
+				announced
 			}
 			// Otherwise, we're the root node but we still want to connect to other nodes.
 			_ => {
-				// Announce ourselves as an origin to all connected clients.
-				// Technically, we should only announce to cluster clients (not end users), but who cares.
-				let mut locals = self.locals.clone();
+				// Announce ourselves as an origin to all connected nodes.
+				let mut internal = self.internal.clone();
 				tokio::spawn(async move {
 					// Run this in a background task so we don't block the main loop.
 					// (it will never exit)
-					locals.announce(myself.subscribe(Filter::Any), None).await
+					internal
+						.announce(myself.subscribe(Filter::Any), Origin::default())
+						.await
 				});
 
 				// Subscribe to the available origins.
-				self.locals.announced(filter)
+				self.internal.announced(filter)
 			}
 		};
 
@@ -143,23 +496,7 @@ impl Cluster {
 
 					tracing::info!(?host, "discovered origin");
 
-					let mut this = self.clone();
-					let remote = host.clone();
-
-					let handle = tokio::spawn(
-						async move {
-							loop {
-								if let Err(err) = this.run_remote(&remote).await {
-									tracing::error!(?err, "remote error, retrying");
-								}
-
-								// TODO smarter backoff
-								tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-							}
-						}
-						.in_current_span(),
-					);
-
+					let handle = tokio::spawn(self.clone().run_reconnect(host.clone()).in_current_span());
 					remotes.insert(host, handle);
 				}
 				Announced::Ended(am) => {
@@ -167,6 +504,7 @@ impl Cluster {
 					if let Some(handle) = remotes.remove(&host) {
 						tracing::warn!(?host, "terminating remote");
 						handle.abort();
+						self.health.lock().unwrap().remove(&host);
 					}
 				}
 				Announced::Live => {
@@ -174,8 +512,111 @@ impl Cluster {
 				}
 			}
 		}
+	}
 
-		Ok(())
+	// Connect to the root for as long as we're running, backing off after each failure.
+	async fn run_root(
+		mut self,
+		host: String,
+		myself: AnnouncedProducer,
+		filter: Filter,
+		discovered: AnnouncedProducer,
+	) {
+		loop {
+			self.update_health(&host, |status| status.set(RemoteState::Connecting));
+
+			let res = self
+				.run_root_session(&host, &myself, filter.clone(), discovered.clone())
+				.await;
+			let delay = self.failed(&host, res);
+			tokio::time::sleep(delay).await;
+		}
+	}
+
+	#[tracing::instrument("root", skip_all, err, fields(%host))]
+	async fn run_root_session(
+		&mut self,
+		host: &str,
+		myself: &AnnouncedProducer,
+		filter: Filter,
+		discovered: AnnouncedProducer,
+	) -> anyhow::Result<()> {
+		let url = Url::parse(&format!("https://{}", host)).context("invalid root URL")?;
+
+		// Connect to the root node.
+		let conn = tokio::time::timeout(CONNECT_TIMEOUT, self.client.connect(url))
+			.await
+			.context("timed out connecting to root")?
+			.context("failed to connect to root")?;
+		let qlog = self.client.qlog(&conn);
+
+		// Count the bytes transferred to the root for as long as we're connected.
+		let _connection = self.metrics.connection(&conn);
+
+		let token = self.token.borrow().clone();
+		let mut session = moq_transfork::Session::connect_token(conn, token.as_deref())
+			.await
+			.context("failed to establish root session")?;
+
+		session.observe(self.metrics.observer(qlog));
+		self.update_health(host, |status| status.set(RemoteState::Healthy));
+
+		// Announce ourselves as an origin to the root node.
+		session.announce(myself.subscribe(Filter::Any));
+
+		// Subscribe to available origins, resuming from the previous session if possible.
+		let _announced = session.announced_into(filter, discovered);
+
+		Err(session.closed().await.into())
+	}
+
+	// Connect to a remote until it leaves the cluster, backing off after each failure.
+	async fn run_reconnect(mut self, host: String) {
+		loop {
+			self.update_health(&host, |status| status.set(RemoteState::Connecting));
+
+			let res = self.run_remote(&host).await;
+			let delay = self.failed(&host, res);
+			tokio::time::sleep(delay).await;
+		}
+	}
+
+	// Record that the connection to a node ended, returning how long to wait before reconnecting.
+	fn failed(&self, host: &str, res: anyhow::Result<()>) -> time::Duration {
+		// The session only ends on error, as the node never stops announcing.
+		let err = match res {
+			Ok(()) => anyhow::anyhow!("session closed"),
+			Err(err) => err,
+		};
+
+		let mut failures = 0;
+		self.update_health(host, |status| {
+			// Only reset the backoff if the connection was healthy for a while.
+			if status.state == RemoteState::Healthy && status.since.elapsed() >= BACKOFF_RESET {
+				status.failures = 0;
+			}
+
+			status.failures += 1;
+			status.error = Some(format!("{:#}", err));
+			status.set(RemoteState::Failed);
+
+			failures = status.failures;
+		});
+
+		let delay = Self::backoff(failures);
+		tracing::warn!(?host, ?err, ?failures, ?delay, "connection failed, reconnecting");
+
+		delay
+	}
+
+	// Exponential backoff with jitter, so remotes don't reconnect in lockstep.
+	fn backoff(failures: u32) -> time::Duration {
+		let exponent = failures.saturating_sub(1).min(16);
+		let delay = BACKOFF_MIN.saturating_mul(1 << exponent).min(BACKOFF_MAX);
+
+		// Wait for a random duration between half and all of the delay.
+		let jitter = RandomState::new().hash_one(failures) as f64 / u64::MAX as f64;
+		delay.mul_f64(0.5 + jitter / 2.0)
 	}
 
 	#[tracing::instrument("remote", skip_all, err, fields(%host))]
@@ -183,22 +624,190 @@ impl Cluster {
 		let url = Url::parse(&format!("https://{}", host)).context("invalid node URL")?;
 
 		// Connect to the remote node.
-		let conn = self.client.connect(url).await.context("failed to connect to remote")?;
+		let conn = tokio::time::timeout(CONNECT_TIMEOUT, self.client.connect(url))
+			.await
+			.context("timed out connecting to remote")?
+			.context("failed to connect to remote")?;
+		let qlog = self.client.qlog(&conn);
+		let _connection = self.metrics.connection(&conn);
 
-		let mut session = moq_transfork::Session::connect(conn)
+		let token = self.token.borrow().clone();
+		let mut session = moq_transfork::Session::connect_token(conn, token.as_deref())
 			.await
 			.context("failed to establish session")?;
 
+		session.observe(self.metrics.observer(qlog));
+		self.update_health(host, |status| status.set(RemoteState::Healthy));
+
 		session.route(self.router.clone());
 
-		// NOTE: We only announce local tracks to remote nodes.
-		// Otherwise there would be conflicts and we wouldn't know which node is the origin.
-		session.announce(self.locals.announced(Filter::Any));
+		// NOTE: We don't announce our tracks to the remote, as it discovers them by connecting to us.
+		// Otherwise it would add them to its own locals and route them back to us.
 
 		// Add any tracks to the list of remotes for routing.
+		// NOTE: They're removed when the session fails, or if we're aborted.
 		let all = session.announced(Filter::Any);
-		self.remotes.announce(all, Some(session.clone())).await;
+		let origin = Origin {
+			session: Some(session.clone()),
+			node: Some(host.to_string()),
+			namespace: false,
+		};
+		self.remotes.announce(all, origin).await;
 
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{test, CacheConfig};
+	use moq_transfork::{Event, Observer};
+	use std::time::Duration;
+
+	// Records the track priority of each group stream.
+	#[derive(Default)]
+	struct Priorities(Mutex<Vec<i8>>);
+
+	impl Observer for Priorities {
+		fn event(&self, event: Event) {
+			if let Event::PrioritySet { priority, .. } = event {
+				self.0.lock().unwrap().push((priority >> 24) as i8);
+			}
+		}
+	}
+
+	impl Priorities {
+		// Produce groups until one is sent upstream with the expected track priority.
+		async fn wait(&self, track: &mut TrackProducer, expected: i8) {
+			tokio::time::timeout(Duration::from_secs(5), async {
+				while self.0.lock().unwrap().last() != Some(&expected) {
+					track.append_group();
+					tokio::time::sleep(Duration::from_millis(10)).await;
+				}
+			})
+			.await
+			.unwrap_or_else(|_| panic!("expected priority {}: {:?}", expected, self.0.lock().unwrap()));
+		}
+	}
+
+	#[test]
+	fn backoff() {
+		for failures in 1..=40 {
+			let expected = BACKOFF_MIN.saturating_mul(1 << (failures - 1).min(16)).min(BACKOFF_MAX);
+
+			let delay = Cluster::backoff(failures);
+			assert!(delay >= expected / 2 && delay <= expected, "{:?} {:?}", delay, expected);
+		}
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn root_backoff() {
+		let config = ClusterConfig {
+			cluster_root: Some("invalid root".to_string()),
+			..cli_defaults()
+		};
+		let client = test::endpoint().client;
+		let cluster = Cluster::new(config, Cache::new(cli_defaults()), client, watch::channel(None).1);
+
+		let run = tokio::spawn(cluster.clone().run());
+		tokio::time::sleep(BACKOFF_MAX * 2).await;
+
+		// The root is retried with a backoff instead of failing the cluster.
+		assert!(!run.is_finished());
+
+		let status = cluster.health().remove("invalid root").unwrap();
+		assert_eq!(status.state, RemoteState::Failed);
+		assert!(status.failures > 1);
+		assert!(status.error.unwrap().contains("invalid root URL"));
+	}
+
+	#[tokio::test]
+	async fn priority() {
+		let endpoint = test::endpoint();
+		let cluster = Cluster::new(
+			cli_defaults(),
+			Cache::new(cli_defaults()),
+			endpoint.client.clone(),
+			watch::channel(None).1,
+		);
+
+		let (accepted, client) = test::connect(endpoint).await;
+		let (relay, origin) = tokio::join!(Session::accept(accepted.session), Session::connect(client));
+		let (relay, mut origin) = (relay.unwrap(), origin.unwrap());
+
+		let priorities = Arc::new(Priorities::default());
+		origin.observe(priorities.clone());
+
+		let (mut track, consumer) = Track::new("demo/video").produce();
+		origin.publish(consumer).unwrap();
+
+		// Route the track to the origin, as if it was announced.
+		let mut announced = AnnouncedProducer::new();
+		announced.announce("demo/video");
+
+		let mut locals = cluster.locals.clone();
+		let consumer = announced.subscribe(Filter::Any);
+		let origin = Origin {
+			session: Some(relay),
+			..Default::default()
+		};
+		tokio::spawn(async move { locals.announce(consumer, origin).await });
+
+		while cluster.route("demo/video").is_none() {
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+
+		let subscribe = |priority| {
+			let track = Track::build().path("demo/video").priority(priority).into();
+			cluster.router.subscribe(track)
+		};
+
+		// A published track is served with its own priority until the subscription is updated.
+		let _low = subscribe(1).await.unwrap();
+		priorities.wait(&mut track, 0).await;
+
+		// The highest priority of any subscriber is used upstream.
+		let high = subscribe(5).await.unwrap();
+		priorities.wait(&mut track, 5).await;
+
+		// The remaining subscriber's priority is used once the other is dropped.
+		drop(high);
+		priorities.wait(&mut track, 1).await;
+	}
+
+	#[tokio::test]
+	async fn forward_sequence() {
+		let cache = Cache::new(CacheConfig {
+			cache_duration: 60,
+			cache_size: 1024,
+		});
+
+		let (track, mut downstream) = Track::new("demo/video").produce();
+		cache.insert(track.clone());
+
+		// The previous origin already forwarded group 5, so an older group from the new origin is dropped.
+		let mut latest = Some(5);
+		let (mut producer, upstream) = Track::new("demo/video").produce();
+		producer.create_group(3);
+		drop(producer);
+
+		Cluster::forward_track(&cache, upstream, track.clone(), &mut latest)
+			.await
+			.unwrap();
+		assert_eq!(latest, Some(5));
+		assert!(cache.group("demo/video", 3).is_none());
+
+		// Newer groups are forwarded with the same sequence number.
+		let (mut producer, upstream) = Track::new("demo/video").produce();
+		producer.create_group(7);
+		drop(producer);
+
+		Cluster::forward_track(&cache, upstream, track, &mut latest)
+			.await
+			.unwrap();
+		assert_eq!(latest, Some(7));
+		assert!(cache.group("demo/video", 7).is_some());
+		assert_eq!(downstream.next_group().await.unwrap().unwrap().sequence, 7);
+	}
+}
@@ -0,0 +1,311 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use anyhow::Context;
+use clap::Parser;
+use moq_native::quic;
+use serde::Deserialize;
+use tokio::sync::watch;
+
+use crate::{Auth, AuthConfig, CacheConfig, Claims, ClusterConfig, LimitsConfig};
+
+#[derive(Parser, Clone, Deserialize)]
+#[serde(default = "cli_defaults", deny_unknown_fields)]
+pub struct Config {
+	/// Load the configuration from this TOML file instead of the command line.
+	///
+	/// Send SIGHUP to reload the TLS certificates, authorization keys, and limits without dropping sessions.
+	#[arg(long, exclusive = true)]
+	#[serde(skip)]
+	pub config: Option<PathBuf>,
+
+	/// Listen on this address, both TCP and UDP.
+	#[arg(long, default_value = "[::]:443")]
+	pub bind: String,
+
+	/// The TLS configuration.
+	#[command(flatten)]
+	pub tls: moq_native::tls::Args,
+
+	/// The qlog configuration.
+	#[command(flatten)]
+	pub qlog: moq_native::qlog::Args,
+
+	/// Log configuration.
+	#[command(flatten)]
+	pub log: moq_native::log::Args,
+
+	/// Cluster configuration.
+	#[command(flatten)]
+	pub cluster: ClusterConfig,
+
+	/// Cache configuration.
+	#[command(flatten)]
+	pub cache: CacheConfig,
+
+	/// Authorization configuration.
+	#[command(flatten)]
+	pub auth: AuthConfig,
+
+	/// Per-session limits.
+	#[command(flatten)]
+	pub limits: LimitsConfig,
+}
+
+impl Config {
+	/// Parse the command line arguments, loading the configuration file if provided.
+	pub fn load() -> anyhow::Result<Self> {
+		Self::parse().reload()
+	}
+
+	/// Load the configuration file again, otherwise returning the command line arguments.
+	pub fn reload(&self) -> anyhow::Result<Self> {
+		let Some(path) = &self.config else {
+			return Ok(self.clone());
+		};
+
+		let data = fs::read_to_string(path).with_context(|| format!("failed to read config: {}", path.display()))?;
+		let mut config = Self::from_toml(&data).with_context(|| format!("invalid config: {}", path.display()))?;
+		config.config = Some(path.clone());
+
+		Ok(config)
+	}
+
+	/// Parse a TOML configuration, using the command line defaults for anything missing.
+	pub fn from_toml(data: &str) -> anyhow::Result<Self> {
+		let config: Self = toml_edit::de::from_str(data)?;
+
+		anyhow::ensure!(
+			config.log.verbose == 0 || config.log.quiet == 0,
+			"log.verbose and log.quiet are mutually exclusive"
+		);
+		anyhow::ensure!(
+			config.auth.auth_key.is_none() || config.auth.auth_keys.is_none(),
+			"auth.key and auth.keys are mutually exclusive"
+		);
+
+		Ok(config)
+	}
+
+	// Returns the name of each setting that changed but can't be reloaded.
+	fn restart_required(&self, other: &Self) -> Vec<&'static str> {
+		let mut changed = Vec::new();
+
+		if self.bind != other.bind {
+			changed.push("bind");
+		}
+
+		if self.tls.root != other.tls.root || self.tls.disable_verify != other.tls.disable_verify {
+			changed.push("tls");
+		}
+
+		if self.qlog.dir != other.qlog.dir {
+			changed.push("qlog");
+		}
+
+		if self.log.verbose != other.log.verbose || self.log.quiet != other.log.quiet {
+			changed.push("log");
+		}
+
+		if self.cluster != other.cluster {
+			changed.push("cluster");
+		}
+
+		if self.cache != other.cache {
+			changed.push("cache");
+		}
+
+		changed
+	}
+}
+
+/// The command line defaults, used for anything missing from the configuration file.
+pub fn cli_defaults<T: Parser>() -> T {
+	T::parse_from(["moq-relay"])
+}
+
+/// Loads the settings that can change at runtime, applying them on [Self::reload].
+pub struct Reloader {
+	// The configuration currently running.
+	config: Config,
+
+	auth: watch::Sender<Option<Arc<Auth>>>,
+	token: watch::Sender<Option<String>>,
+	fingerprints: watch::Sender<Vec<String>>,
+	limits: watch::Sender<Arc<LimitsConfig>>,
+}
+
+impl Reloader {
+	/// Load the authorization keys, using the fingerprints of the TLS certificates already loaded.
+	pub fn new(config: Config, fingerprints: Vec<String>) -> anyhow::Result<Self> {
+		let (auth, token) = Self::load_auth(&config)?;
+
+		Ok(Self {
+			auth: watch::Sender::new(auth),
+			token: watch::Sender::new(token),
+			fingerprints: watch::Sender::new(fingerprints),
+			limits: watch::Sender::new(Arc::new(config.limits.clone())),
+			config,
+		})
+	}
+
+	// Load the authorization keys, returning the token presented to other nodes in the cluster.
+	fn load_auth(config: &Config) -> anyhow::Result<(Option<Arc<Auth>>, Option<String>)> {
+		let auth = config.auth.load()?.map(Arc::new);
+
+		let token = match &auth {
+			// Other nodes share the same keys, so sign a token granting ourselves full access.
+			Some(auth) => Some(auth.sign(&Claims {
+				cluster: true,
+				..Claims::any()
+			})?),
+			// Otherwise other nodes share the same secret.
+			None => config.auth.cluster_secret.clone(),
+		};
+
+		let clustered = config.cluster.cluster_root.is_some() || config.cluster.cluster_node.is_some();
+		anyhow::ensure!(
+			!clustered || token.is_some(),
+			"clustering requires either --auth-key or --cluster-secret to identify other nodes"
+		);
+
+		Ok((auth, token))
+	}
+
+	/// The authorization keys used to verify new sessions, if enabled.
+	pub fn auth(&self) -> watch::Receiver<Option<Arc<Auth>>> {
+		self.auth.subscribe()
+	}
+
+	/// The token presented to other nodes in the cluster.
+	pub fn token(&self) -> watch::Receiver<Option<String>> {
+		self.token.subscribe()
+	}
+
+	/// The fingerprints of our TLS certificates.
+	pub fn fingerprints(&self) -> watch::Receiver<Vec<String>> {
+		self.fingerprints.subscribe()
+	}
+
+	/// The limits applied to new sessions.
+	pub fn limits(&self) -> watch::Receiver<Arc<LimitsConfig>> {
+		self.limits.subscribe()
+	}
+
+	/// Reload the configuration, applying the TLS certificates, authorization keys, and limits to any new sessions.
+	///
+	/// Nothing is applied unless the entire configuration is valid.
+	pub fn reload(&mut self, server: &quic::Server) -> anyhow::Result<()> {
+		let config = self.config.reload()?;
+
+		let tls = config.tls.load()?;
+		let server_tls = tls.server.context("missing TLS certificates")?;
+		let (auth, token) = Self::load_auth(&config)?;
+
+		let ignored = self.config.restart_required(&config);
+		if !ignored.is_empty() {
+			tracing::warn!(?ignored, "some changes require a restart");
+		}
+
+		server.set_tls(server_tls)?;
+		self.fingerprints.send_replace(tls.fingerprints);
+		self.auth.send_replace(auth);
+		self.token.send_replace(token);
+		self.limits.send_replace(Arc::new(config.limits.clone()));
+
+		// Only update the settings we applied, so we keep warning about the others.
+		self.config.tls.cert = config.tls.cert;
+		self.config.tls.key = config.tls.key;
+		self.config.tls.self_sign = config.tls.self_sign;
+		self.config.auth = config.auth;
+		self.config.limits = config.limits;
+
+		tracing::info!("reloaded config");
+
+		Ok(())
+	}
+}
+
+/// Receives a signal whenever the configuration should be reloaded (SIGHUP).
+pub struct Hangup {
+	#[cfg(unix)]
+	signal: tokio::signal::unix::Signal,
+}
+
+impl Hangup {
+	pub fn new() -> anyhow::Result<Self> {
+		Ok(Self {
+			#[cfg(unix)]
+			signal: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?,
+		})
+	}
+
+	pub async fn recv(&mut self) {
+		#[cfg(unix)]
+		self.signal.recv().await;
+
+		#[cfg(not(unix))]
+		std::future::pending::<()>().await;
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{OriginPolicy, OriginPrefer};
+
+	#[test]
+	fn parse() {
+		let config = Config::from_toml(
+			r#"
+			bind = "[::]:4443"
+
+			[tls]
+			self_sign = ["localhost"]
+
+			[cluster]
+			node = "relay1.example.com"
+			origin_policy = "oldest"
+
+			[cache]
+			duration = 10
+
+			[limits]
+			subscribe = 100
+			ingest = 10000000
+
+			[limits.identities.alice]
+			subscribe = 1000
+
+			[[auth.keys]]
+			kty = "oct"
+			kid = "a"
+			k = "c2VjcmV0"
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(config.bind, "[::]:4443");
+		assert_eq!(config.tls.self_sign, vec!["localhost".to_string()]);
+		assert_eq!(config.cluster.cluster_node.as_deref(), Some("relay1.example.com"));
+		assert_eq!(config.cluster.origin_policy, OriginPolicy::Oldest);
+		assert_eq!(config.cluster.origin_prefer, OriginPrefer::Local);
+		assert_eq!(config.cache.cache_duration, 10);
+		assert_eq!(config.cache.cache_size, 268435456);
+		assert!(config.auth.load().unwrap().is_some());
+		assert_eq!(config.limits.resolve(None).subscribe, Some(100));
+		assert_eq!(config.limits.resolve(Some("alice")).subscribe, Some(1000));
+		assert_eq!(config.limits.resolve(Some("alice")).ingest, Some(10000000));
+
+		// Anything missing uses the command line defaults.
+		let defaults = Config::from_toml("").unwrap();
+		assert_eq!(defaults.bind, "[::]:443");
+		assert!(defaults.restart_required(&config).contains(&"bind"));
+		assert!(!defaults.restart_required(&config).contains(&"tls"));
+
+		assert!(Config::from_toml("unknown = 1").is_err());
+		assert!(Config::from_toml("[cluster]\norigin_policy = \"random\"").is_err());
+		assert!(Config::from_toml("[limits.identities.alice]\nunknown = 1").is_err());
+		assert!(Config::from_toml("[auth]\nkey = \"keys.json\"\nkeys = []").is_err());
+		assert!(Config::from_toml("[log]\nverbose = 1\nquiet = 1").is_err());
+	}
+}
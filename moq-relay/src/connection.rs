@@ -1,33 +1,298 @@
-use moq_transfork::Filter;
+use std::sync::Arc;
 
-use crate::Cluster;
+use futures::future::try_join_all;
+use moq_native::qlog::Qlog;
+use moq_transfork::{Announced, AnnouncedConsumer, Error, Filter, Router, RouterConsumer};
+use tracing::Instrument;
+use url::Url;
+use web_transport::quinn as web_transport_quinn;
+
+use crate::{distinct_prefixes, Auth, Claims, Cluster, Limiter, LimitsConfig, Origin, SessionKind};
 
 pub struct Connection {
 	id: u64,
-	session: web_transport::Session,
+	session: web_transport_quinn::Session,
 	cluster: Cluster,
+	qlog: Option<Arc<Qlog>>,
+
+	// The URL requested by the client, which may contain a token.
+	url: Option<Url>,
+
+	// Used to verify the session's token, or None if authorization is disabled.
+	auth: Option<Arc<Auth>>,
+
+	// The limits for each session, based on its identity.
+	limits: Arc<LimitsConfig>,
 }
 
 impl Connection {
-	pub fn new(id: u64, session: web_transport::Session, cluster: Cluster) -> Self {
-		Self { id, session, cluster }
+	pub fn new(
+		id: u64,
+		session: web_transport_quinn::Session,
+		cluster: Cluster,
+		qlog: Option<Arc<Qlog>>,
+		url: Option<Url>,
+		auth: Option<Arc<Auth>>,
+		limits: Arc<LimitsConfig>,
+	) -> Self {
+		Self {
+			id,
+			session,
+			cluster,
+			qlog,
+			url,
+			auth,
+			limits,
+		}
 	}
 
 	#[tracing::instrument("session", skip_all, err, fields(id = self.id))]
-	pub async fn run(mut self) -> anyhow::Result<()> {
+	pub async fn run(self) -> anyhow::Result<()> {
+		let conn = self.session.clone();
 		let mut session = moq_transfork::Session::accept(self.session).await?;
 
-		// Route any subscriptions to the cluster
-		session.route(self.cluster.router);
+		// The limits aren't known until the session is authorized, but subscriptions must be counted from the start.
+		let limiter = Arc::new(Limiter::new(self.cluster.metrics.observer(self.qlog)));
+		session.observe(limiter.clone());
+
+		let claims = match &self.auth {
+			Some(auth) => match auth.verify_session(self.url.as_ref(), &session) {
+				Ok(claims) => claims,
+				Err(err) => {
+					tracing::warn!(?err, "unauthorized");
+					session.close(Error::App(err.to_code()));
+					return Err(err.into());
+				}
+			},
+			// Without authorization, other nodes identify themselves with the secret shared by the cluster.
+			None => Claims {
+				cluster: self.cluster.is_peer(session.authorization()),
+				..Claims::any()
+			},
+		};
+
+		tracing::info!(sub = ?claims.sub, publish = ?claims.publish, subscribe = ?claims.subscribe, cluster = claims.cluster, "authorized");
+
+		// Other nodes are never limited, as they relay tracks on behalf of many sessions.
+		// They're only trusted if they presented a signed token with the cluster claim, or the shared cluster secret.
+		if !claims.cluster {
+			limiter.set(self.limits.resolve(claims.sub.as_deref()));
+		}
+
+		let limited = !limiter.limits().is_unlimited();
+		if limited {
+			tokio::spawn(Self::run_limits(session.clone(), conn, limiter.clone()).in_current_span());
+		}
+
+		let _session = self.cluster.metrics.session(match claims.cluster {
+			true => SessionKind::Cluster,
+			false => SessionKind::User,
+		});
+
+		// Route any permitted subscriptions to the cluster.
+		// NOTE: The router never serves the internal namespace, so there's nothing to hide.
+		match self.auth.is_some() || limited {
+			true => session.route(Self::authorize(
+				self.cluster.router.clone(),
+				claims.clone(),
+				limiter.clone(),
+			)),
+			false => session.route(self.cluster.router.clone()),
+		};
+
+		// Only other nodes are told about the internal namespace.
+		if claims.cluster {
+			session.announce(self.cluster.internal.announced(Filter::Any));
+		}
 
-		// TODO things will get weird if locals and remotes announce the same path.
-		session.announce(self.cluster.locals.announced(Filter::Any));
-		session.announce(self.cluster.remotes.announced(Filter::Any));
+		// Only announce the paths that the session may subscribe to.
+		// A path announced by both locals and remotes is routed to one of them, based on the configured preference.
+		// Other nodes are only told about our local tracks, otherwise they would route them back to us.
+		for filter in Self::filters(&claims.subscribe) {
+			session.announce(self.cluster.locals.announced(filter.clone()));
 
-		// Add any announcements to the cluster, indicating we're the origin.
-		let all = session.announced(Filter::Any);
-		self.cluster.locals.announce(all, Some(session.clone())).await;
+			if !claims.cluster {
+				session.announce(self.cluster.remotes.announced(filter));
+			}
+		}
+
+		// Only discover the paths that the session may publish, indicating we're the origin.
+		// Other nodes only publish internal paths; we discover their tracks by connecting to them as a remote.
+		// Any internal paths are ignored by locals.
+		let (origins, node) = match claims.cluster {
+			true => (self.cluster.internal.clone(), None),
+			false => (
+				self.cluster.locals.clone(),
+				self.cluster.node().map(|node| node.to_string()),
+			),
+		};
+
+		let origin = Origin {
+			session: Some(session.clone()),
+			node,
+			namespace: session.is_ietf(),
+		};
+
+		let mut announced = Vec::new();
+		for filter in Self::filters(&claims.publish) {
+			let all = session.announced(filter);
+			let mut origins = origins.clone();
+			let origin = origin.clone();
+			let limiter = limited.then(|| limiter.clone());
+			announced.push(async move { origins.announce_limited(all, origin, limiter).await });
+		}
+
+		// Reject the session if it announces anything else, rather than silently ignoring it.
+		let restricted = !claims.publish.iter().any(String::is_empty);
+		let publishing = !announced.is_empty();
+
+		tokio::select! {
+			res = try_join_all(announced), if publishing => if let Err(err) = res {
+				tracing::warn!(?err, "limit exceeded");
+				session.close(Error::App(err.to_code()));
+				return Err(err.into());
+			},
+			path = Self::unauthorized(session.announced(Filter::Any), &claims), if restricted => {
+				tracing::warn!(?path, "unauthorized announce");
+				session.close(Error::Unauthorized);
+				return Err(Error::Unauthorized.into());
+			}
+			// Otherwise there's nothing to wait on, but the session is still active until it's closed.
+			_ = session.closed(), if !publishing => {}
+		}
 
 		Ok(())
 	}
+
+	// Returns the first announced path that isn't permitted by the claims, or waits until the session is closed.
+	async fn unauthorized(mut announced: AnnouncedConsumer, claims: &Claims) -> String {
+		while let Some(announced) = announced.next().await {
+			if let Announced::Active(am) = announced {
+				if !claims.can_publish(am.full()) {
+					return am.to_full();
+				}
+			}
+		}
+
+		std::future::pending().await
+	}
+
+	// Close the session if it exceeds the bitrate limits.
+	async fn run_limits(session: moq_transfork::Session, conn: web_transport_quinn::Session, limiter: Arc<Limiter>) {
+		tokio::select! {
+			err = limiter.run(conn) => {
+				tracing::warn!(?err, "limit exceeded");
+				session.close(Error::App(err.to_code()));
+			}
+			_ = session.closed() => {}
+		}
+	}
+
+	// Returns the filters matching the claimed prefixes, only at a segment boundary like [Claims::can_publish].
+	fn filters(prefixes: &[String]) -> Vec<Filter> {
+		let mut filters = Vec::new();
+
+		for prefix in distinct_prefixes(prefixes) {
+			match prefix.as_str() {
+				"" => filters.push(Filter::Any),
+				prefix if prefix.ends_with('/') => filters.push(Filter::Prefix(prefix.to_string())),
+				prefix => {
+					filters.push(Filter::Exact(prefix.to_string()));
+					filters.push(Filter::Prefix(format!("{}/", prefix)));
+				}
+			}
+		}
+
+		filters
+	}
+
+	// Returns a router that rejects any subscriptions not permitted by the claims or that exceed the limits.
+	fn authorize(upstream: RouterConsumer, claims: Claims, limiter: Arc<Limiter>) -> RouterConsumer {
+		let (mut producer, consumer) = Router { capacity: 1024 }.produce();
+
+		tokio::spawn(
+			async move {
+				while let Some(req) = producer.requested().await {
+					if !claims.can_subscribe(&req.track.path) {
+						tracing::warn!(path = ?req.track.path, "unauthorized subscribe");
+						req.close(Error::Unauthorized);
+						continue;
+					}
+
+					if let Err(err) = limiter.subscribe() {
+						tracing::warn!(path = ?req.track.path, ?err, "limit exceeded");
+						req.close(Error::App(err.to_code()));
+						continue;
+					}
+
+					let upstream = upstream.clone();
+					let span = req.span.clone();
+
+					tokio::spawn(
+						async move {
+							match upstream.subscribe(req.track.clone()).await {
+								Ok(track) => req.serve(track),
+								Err(err) => req.close(err),
+							}
+						}
+						.instrument(span),
+					);
+				}
+			}
+			.in_current_span(),
+		);
+
+		consumer
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::time::Duration;
+
+	use super::*;
+	use crate::{cli_defaults, test, Cache};
+
+	#[tokio::test]
+	async fn subscribe_only() {
+		let keys = serde_json::json!({ "keys": [{ "kty": "oct", "k": "c2VjcmV0" }] });
+		let auth = Auth::new(serde_json::from_value(keys).unwrap()).unwrap();
+
+		let claims = Claims {
+			subscribe: vec!["".to_string()],
+			..Default::default()
+		};
+		let token = auth.sign(&claims).unwrap();
+
+		let endpoint = test::endpoint();
+		let cluster = Cluster::new(
+			cli_defaults(),
+			Cache::new(cli_defaults()),
+			endpoint.client.clone(),
+			tokio::sync::watch::channel(None).1,
+		);
+
+		let (accepted, client) = test::connect(endpoint).await;
+		let conn = Connection::new(
+			0,
+			accepted.session,
+			cluster,
+			None,
+			accepted.url,
+			Some(Arc::new(auth)),
+			Default::default(),
+		);
+
+		let run = tokio::spawn(conn.run());
+		let client = moq_transfork::Session::connect_token(client, Some(&token))
+			.await
+			.unwrap();
+
+		// The session is still active, even though it doesn't publish anything.
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		assert!(!run.is_finished());
+
+		client.close(Error::Cancel);
+		run.await.unwrap().unwrap();
+	}
 }
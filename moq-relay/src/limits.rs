@@ -0,0 +1,372 @@
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex, OnceLock,
+	},
+	time,
+};
+
+use clap::Parser;
+use moq_transfork::{Event, Observer, StreamKind};
+use serde::Deserialize;
+use web_transport::quinn as web_transport_quinn;
+
+// The bitrate is averaged over this window, so short bursts (ex. keyframes) are permitted.
+const BITRATE_WINDOW: time::Duration = time::Duration::from_secs(5);
+
+// How often to sample the connection stats.
+const BITRATE_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+#[derive(Clone, Default, Parser, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+	/// The maximum number of tracks each session may announce.
+	#[arg(long)]
+	#[serde(rename = "announce")]
+	pub limit_announce: Option<u64>,
+
+	/// The maximum number of concurrent subscriptions for each session.
+	#[arg(long)]
+	#[serde(rename = "subscribe")]
+	pub limit_subscribe: Option<u64>,
+
+	/// The maximum number of new subscriptions per second for each session, allowing a burst of up to one second.
+	#[arg(long)]
+	#[serde(rename = "subscribe_rate")]
+	pub limit_subscribe_rate: Option<u64>,
+
+	/// The maximum bitrate received from each session, in bits per second.
+	#[arg(long)]
+	#[serde(rename = "ingest")]
+	pub limit_ingest: Option<u64>,
+
+	/// The maximum bitrate sent to each session, in bits per second.
+	#[arg(long)]
+	#[serde(rename = "egress")]
+	pub limit_egress: Option<u64>,
+
+	/// Limits for sessions with the given identity (the token's `sub` claim), instead of the above.
+	/// Only supported via the config file.
+	#[arg(skip)]
+	#[serde(rename = "identities")]
+	pub limit_identities: HashMap<String, Limits>,
+}
+
+impl LimitsConfig {
+	/// Returns the limits for a session, using any overrides for its identity.
+	pub fn resolve(&self, identity: Option<&str>) -> Limits {
+		let global = Limits {
+			announce: self.limit_announce,
+			subscribe: self.limit_subscribe,
+			subscribe_rate: self.limit_subscribe_rate,
+			ingest: self.limit_ingest,
+			egress: self.limit_egress,
+		};
+
+		match identity.and_then(|identity| self.limit_identities.get(identity)) {
+			Some(limits) => limits.or(global),
+			None => global,
+		}
+	}
+}
+
+/// The limits for a single session, where None is unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+	pub announce: Option<u64>,
+	pub subscribe: Option<u64>,
+	pub subscribe_rate: Option<u64>,
+	pub ingest: Option<u64>,
+	pub egress: Option<u64>,
+}
+
+impl Limits {
+	/// Use the other limits for anything not set.
+	pub fn or(self, other: Self) -> Self {
+		Self {
+			announce: self.announce.or(other.announce),
+			subscribe: self.subscribe.or(other.subscribe),
+			subscribe_rate: self.subscribe_rate.or(other.subscribe_rate),
+			ingest: self.ingest.or(other.ingest),
+			egress: self.egress.or(other.egress),
+		}
+	}
+
+	pub fn is_unlimited(&self) -> bool {
+		*self == Self::default()
+	}
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitError {
+	#[error("too many announced tracks")]
+	Announce,
+
+	#[error("too many subscriptions")]
+	Subscribe,
+
+	#[error("too many new subscriptions")]
+	SubscribeRate,
+
+	#[error("ingest bitrate exceeded")]
+	Ingest,
+
+	#[error("egress bitrate exceeded")]
+	Egress,
+}
+
+impl LimitError {
+	/// An application code used to close the session or subscription, following [crate::AuthError].
+	pub fn to_code(&self) -> u32 {
+		match self {
+			Self::Announce => 8,
+			Self::Subscribe => 9,
+			Self::SubscribeRate => 10,
+			Self::Ingest => 11,
+			Self::Egress => 12,
+		}
+	}
+}
+
+/// Enforces the limits for a single session.
+///
+/// The session's events are observed to count its subscriptions, as the router isn't told when they end.
+pub struct Limiter {
+	// Set once the session is authorized, otherwise unlimited.
+	limits: OnceLock<Limits>,
+
+	// Forwarded every event, as a session only supports one observer.
+	observer: Arc<dyn Observer>,
+
+	// The subscribe streams opened by the remote.
+	subscriptions: Mutex<HashSet<u64>>,
+
+	// The number of tracks currently announced.
+	announced: AtomicU64,
+
+	// Used to limit the rate of new subscriptions.
+	bucket: Mutex<Bucket>,
+}
+
+impl Limiter {
+	pub fn new(observer: Arc<dyn Observer>) -> Self {
+		Self {
+			limits: Default::default(),
+			observer,
+			subscriptions: Default::default(),
+			announced: Default::default(),
+			bucket: Default::default(),
+		}
+	}
+
+	/// Start enforcing the limits, which can only be done once.
+	pub fn set(&self, limits: Limits) {
+		self.limits.set(limits).ok();
+	}
+
+	pub fn limits(&self) -> Limits {
+		self.limits.get().copied().unwrap_or_default()
+	}
+
+	/// Called when the remote requests a subscription, returning an error if it should be rejected.
+	pub fn subscribe(&self) -> Result<(), LimitError> {
+		let limits = self.limits();
+
+		// The subscription's stream was already opened, so it's included in the count.
+		if let Some(max) = limits.subscribe {
+			if self.subscriptions.lock().unwrap().len() as u64 > max {
+				return Err(LimitError::Subscribe);
+			}
+		}
+
+		if let Some(rate) = limits.subscribe_rate {
+			if !self.bucket.lock().unwrap().take(rate, time::Instant::now()) {
+				return Err(LimitError::SubscribeRate);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Called before announcing a track, returning an error if there are too many.
+	pub fn announce(&self) -> Result<(), LimitError> {
+		let max = self.limits().announce.unwrap_or(u64::MAX);
+
+		self.announced
+			.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+				(count < max).then_some(count + 1)
+			})
+			.map(|_| ())
+			.map_err(|_| LimitError::Announce)
+	}
+
+	/// Called when announced tracks are removed.
+	pub fn unannounce(&self, count: usize) {
+		self.announced.fetch_sub(count as u64, Ordering::Relaxed);
+	}
+
+	/// Periodically sample the bitrate of the connection, returning an error if it's exceeded.
+	///
+	/// This never returns if the bitrate is unlimited.
+	pub async fn run(&self, conn: web_transport_quinn::Session) -> LimitError {
+		let limits = self.limits();
+		if limits.ingest.is_none() && limits.egress.is_none() {
+			return std::future::pending().await;
+		}
+
+		let mut ingest = Bitrate::default();
+		let mut egress = Bitrate::default();
+		let mut interval = tokio::time::interval(BITRATE_INTERVAL);
+
+		loop {
+			interval.tick().await;
+
+			let now = time::Instant::now();
+			let stats = conn.stats();
+
+			let exceeded = |bitrate: &mut Bitrate, bytes, max: Option<u64>| {
+				let current = bitrate.sample(bytes, now);
+				max.is_some_and(|max| current.is_some_and(|current| current > max))
+			};
+
+			// Includes the QUIC overhead, as that's what the relay actually pays for.
+			if exceeded(&mut ingest, stats.udp_rx.bytes, limits.ingest) {
+				return LimitError::Ingest;
+			}
+
+			if exceeded(&mut egress, stats.udp_tx.bytes, limits.egress) {
+				return LimitError::Egress;
+			}
+		}
+	}
+}
+
+impl Observer for Limiter {
+	fn event(&self, event: Event) {
+		match &event {
+			Event::StreamOpened {
+				stream,
+				kind: StreamKind::Subscribe,
+				local: false,
+			} => {
+				self.subscriptions.lock().unwrap().insert(*stream);
+			}
+			Event::StreamClosed { stream } => {
+				self.subscriptions.lock().unwrap().remove(stream);
+			}
+			_ => {}
+		}
+
+		self.observer.event(event);
+	}
+}
+
+// A token bucket that holds up to one second of tokens, starting full.
+#[derive(Default)]
+struct Bucket {
+	// The number of tokens available as of the last update, or None if never used.
+	state: Option<(f64, time::Instant)>,
+}
+
+impl Bucket {
+	// Returns true if a token was available, refilled at the given rate per second.
+	fn take(&mut self, rate: u64, now: time::Instant) -> bool {
+		let capacity = rate as f64;
+		let tokens = match self.state {
+			Some((tokens, updated)) => (tokens + now.duration_since(updated).as_secs_f64() * capacity).min(capacity),
+			None => capacity,
+		};
+
+		let available = tokens >= 1.0;
+		self.state = Some((if available { tokens - 1.0 } else { tokens }, now));
+
+		available
+	}
+}
+
+// Computes the average bitrate over the window from a running total of bytes.
+#[derive(Default)]
+struct Bitrate {
+	samples: VecDeque<(time::Instant, u64)>,
+}
+
+impl Bitrate {
+	// Returns the bitrate in bits per second, or None until a full window has been sampled.
+	fn sample(&mut self, bytes: u64, now: time::Instant) -> Option<u64> {
+		self.samples.push_back((now, bytes));
+
+		// Keep exactly one sample older than the window.
+		while self
+			.samples
+			.get(1)
+			.is_some_and(|(when, _)| now.duration_since(*when) >= BITRATE_WINDOW)
+		{
+			self.samples.pop_front();
+		}
+
+		let (start, start_bytes) = *self.samples.front()?;
+		let elapsed = now.duration_since(start);
+		if elapsed < BITRATE_WINDOW {
+			return None;
+		}
+
+		Some(((bytes - start_bytes) as f64 * 8.0 / elapsed.as_secs_f64()) as u64)
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn resolve() {
+		let mut config = LimitsConfig {
+			limit_subscribe: Some(10),
+			limit_ingest: Some(1_000_000),
+			..Default::default()
+		};
+
+		config.limit_identities.insert(
+			"alice".to_string(),
+			Limits {
+				subscribe: Some(100),
+				..Default::default()
+			},
+		);
+
+		let alice = config.resolve(Some("alice"));
+		assert_eq!(alice.subscribe, Some(100));
+		assert_eq!(alice.ingest, Some(1_000_000));
+		assert_eq!(alice.announce, None);
+
+		assert_eq!(config.resolve(Some("bob")).subscribe, Some(10));
+		assert_eq!(config.resolve(None).subscribe, Some(10));
+		assert!(LimitsConfig::default().resolve(Some("alice")).is_unlimited());
+	}
+
+	#[test]
+	fn rate() {
+		let start = time::Instant::now();
+		let secs = |secs: f64| start + time::Duration::from_secs_f64(secs);
+
+		// A burst of up to one second is permitted, then tokens are refilled over time.
+		let mut bucket = Bucket::default();
+		assert!(bucket.take(2, secs(0.0)));
+		assert!(bucket.take(2, secs(0.0)));
+		assert!(!bucket.take(2, secs(0.0)));
+		assert!(!bucket.take(2, secs(0.25)));
+		assert!(bucket.take(2, secs(0.5)));
+
+		// The bitrate is only known after a full window.
+		let mut bitrate = Bitrate::default();
+		for i in 0..5 {
+			assert_eq!(bitrate.sample(i * 1000, secs(i as f64)), None);
+		}
+		assert_eq!(bitrate.sample(5000, secs(5.0)), Some(8000));
+
+		// Older samples are discarded.
+		assert_eq!(bitrate.sample(11000, secs(6.0)), Some(16000));
+	}
+}
@@ -1,42 +1,35 @@
+mod auth;
+mod cache;
 mod cluster;
+mod config;
 mod connection;
+mod limits;
+mod metrics;
 mod origins;
 mod web;
 
+#[cfg(test)]
+mod test;
+
+pub use auth::*;
+pub use cache::*;
 pub use cluster::*;
+pub use config::*;
 pub use connection::*;
+pub use limits::*;
+pub use metrics::*;
 pub use origins::*;
 pub use web::*;
 
 use anyhow::Context;
-use clap::Parser;
 use moq_native::quic;
 
-#[derive(Parser, Clone)]
-pub struct Config {
-	/// Listen on this address, both TCP and UDP.
-	#[arg(long, default_value = "[::]:443")]
-	pub bind: String,
-
-	/// The TLS configuration.
-	#[command(flatten)]
-	pub tls: moq_native::tls::Args,
-
-	/// Log configuration.
-	#[command(flatten)]
-	pub log: moq_native::log::Args,
-
-	/// Cluster configuration.
-	#[command(flatten)]
-	pub cluster: ClusterConfig,
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	let config = Config::parse();
+	let config = Config::load()?;
 	config.log.init();
 
-	let bind = tokio::net::lookup_host(config.bind)
+	let bind = tokio::net::lookup_host(&config.bind)
 		.await
 		.context("invalid bind address")?
 		.next()
@@ -47,18 +40,35 @@ async fn main() -> anyhow::Result<()> {
 		anyhow::bail!("missing TLS certificates");
 	}
 
-	let quic = quic::Endpoint::new(quic::Config { bind, tls: tls.clone() })?;
+	let fingerprints = tls.fingerprints.clone();
+
+	let quic = quic::Endpoint::new(quic::Config {
+		bind,
+		tls,
+		qlog: config.qlog.load()?,
+	})?;
 	let mut server = quic.server.context("missing TLS certificate")?;
 
-	let cluster = Cluster::new(config.cluster.clone(), quic.client);
-	let cloned = cluster.clone();
-	tokio::spawn(async move { cloned.run().await.expect("cluster failed") });
+	// Anything that can change when the config is reloaded.
+	let mut reloader = Reloader::new(config.clone(), fingerprints)?;
+	let mut hangup = Hangup::new()?;
+	let auth = reloader.auth();
+	let limits = reloader.limits();
+
+	let cluster = Cluster::new(
+		config.cluster.clone(),
+		Cache::new(config.cache.clone()),
+		quic.client,
+		reloader.token(),
+	);
+	tokio::spawn(cluster.clone().run());
 
 	// Create a web server too.
 	let web = Web::new(WebConfig {
 		bind,
-		tls,
+		fingerprints: reloader.fingerprints(),
 		cluster: cluster.clone(),
+		auth: reloader.auth(),
 	});
 
 	tokio::spawn(async move {
@@ -69,12 +79,32 @@ async fn main() -> anyhow::Result<()> {
 
 	let mut conn_id = 0;
 
-	while let Some(conn) = server.accept().await {
-		let session = Connection::new(conn_id, conn.into(), cluster.clone());
+	loop {
+		let accepted = tokio::select! {
+			Some(accepted) = server.accept_request() => accepted,
+			_ = hangup.recv() => {
+				// Existing sessions are unaffected, even if the reload fails.
+				if let Err(err) = reloader.reload(&server) {
+					tracing::error!(?err, "failed to reload config");
+				}
+
+				continue;
+			}
+			else => break,
+		};
+
+		let conn = accepted.session;
+		let qlog = server.qlog(&conn);
+		let connection = cluster.metrics.connection(&conn);
+		let auth = auth.borrow().clone();
+		let limits = limits.borrow().clone();
+
+		let session = Connection::new(conn_id, conn, cluster.clone(), qlog, accepted.url, auth, limits);
 		conn_id += 1;
 
 		tokio::spawn(async move {
 			session.run().await.ok();
+			drop(connection);
 		});
 	}
 
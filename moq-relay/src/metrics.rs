@@ -0,0 +1,443 @@
+use std::{
+	collections::HashMap,
+	fmt::Write,
+	sync::{
+		atomic::{AtomicI64, AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+};
+
+use moq_native::qlog::Qlog;
+use moq_transfork::{Event, Observer, StreamKind};
+use web_transport::quinn as web_transport_quinn;
+
+use crate::{Cache, Origins, RemoteHealth, RemoteState};
+
+/// Whether a session is an end user or another node in the cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionKind {
+	User,
+	Cluster,
+}
+
+/// Relay-wide metrics, served in the Prometheus text format by the `/metrics` endpoint.
+#[derive(Clone)]
+pub struct Metrics {
+	// Used to label every metric.
+	node: Option<String>,
+
+	locals: Origins,
+	remotes: Origins,
+	cache: Cache,
+	health: RemoteHealth,
+
+	state: Arc<MetricsState>,
+}
+
+#[derive(Default)]
+struct MetricsState {
+	sessions_user: AtomicI64,
+	sessions_cluster: AtomicI64,
+
+	// Active subscriptions; upstream are opened by us, downstream by the remote.
+	subscriptions_upstream: AtomicI64,
+	subscriptions_downstream: AtomicI64,
+
+	// Groups received from upstream and sent downstream.
+	groups_received: AtomicU64,
+	groups_sent: AtomicU64,
+
+	// Groups dropped by us or by the remote.
+	dropped_local: AtomicU64,
+	dropped_remote: AtomicU64,
+
+	// Bytes transferred by connections that have since closed.
+	bytes_sent: AtomicU64,
+	bytes_received: AtomicU64,
+
+	// Open connections, indexed by ID, sampled whenever the metrics are rendered.
+	connections: Mutex<HashMap<usize, web_transport_quinn::Session>>,
+}
+
+impl Metrics {
+	pub fn new(node: Option<String>, locals: Origins, remotes: Origins, cache: Cache, health: RemoteHealth) -> Self {
+		Self {
+			node,
+			locals,
+			remotes,
+			cache,
+			health,
+			state: Default::default(),
+		}
+	}
+
+	/// Count the session until the returned guard is dropped.
+	pub fn session(&self, kind: SessionKind) -> SessionGuard {
+		self.sessions(kind).fetch_add(1, Ordering::Relaxed);
+
+		SessionGuard {
+			metrics: self.clone(),
+			kind,
+		}
+	}
+
+	fn sessions(&self, kind: SessionKind) -> &AtomicI64 {
+		match kind {
+			SessionKind::User => &self.state.sessions_user,
+			SessionKind::Cluster => &self.state.sessions_cluster,
+		}
+	}
+
+	/// Count the bytes transferred by the connection until the returned guard is dropped.
+	pub fn connection(&self, conn: &web_transport_quinn::Session) -> ConnectionGuard {
+		let id = conn.stable_id();
+		self.state.connections.lock().unwrap().insert(id, conn.clone());
+
+		ConnectionGuard {
+			metrics: self.clone(),
+			id,
+		}
+	}
+
+	/// Returns an observer that counts the session's events, forwarding them to the qlog if provided.
+	pub fn observer(&self, qlog: Option<Arc<Qlog>>) -> Arc<dyn Observer> {
+		Arc::new(SessionObserver {
+			metrics: self.clone(),
+			qlog,
+			subscriptions: Default::default(),
+		})
+	}
+
+	/// Render every metric in the Prometheus text format.
+	pub fn render(&self) -> String {
+		let state = &self.state;
+		let mut out = Metric::new(self.node.as_deref());
+
+		out.header("moq_relay_sessions", "gauge", "The number of connected sessions.");
+		out.sample(
+			"moq_relay_sessions",
+			&[("kind", "user")],
+			state.sessions_user.load(Ordering::Relaxed),
+		);
+		out.sample(
+			"moq_relay_sessions",
+			&[("kind", "cluster")],
+			state.sessions_cluster.load(Ordering::Relaxed),
+		);
+
+		out.header("moq_relay_announced_tracks", "gauge", "The number of announced tracks.");
+		out.sample(
+			"moq_relay_announced_tracks",
+			&[("origin", "local")],
+			self.locals.count(),
+		);
+		out.sample(
+			"moq_relay_announced_tracks",
+			&[("origin", "remote")],
+			self.remotes.count(),
+		);
+
+		let (groups, bytes) = self.cache.usage();
+		out.header("moq_relay_cache_groups", "gauge", "The number of cached groups.");
+		out.sample("moq_relay_cache_groups", &[], groups);
+		out.header("moq_relay_cache_bytes", "gauge", "The size of the cached groups.");
+		out.sample("moq_relay_cache_bytes", &[], bytes);
+
+		out.header(
+			"moq_relay_subscriptions",
+			"gauge",
+			"The number of active subscriptions.",
+		);
+		out.sample(
+			"moq_relay_subscriptions",
+			&[("direction", "upstream")],
+			state.subscriptions_upstream.load(Ordering::Relaxed),
+		);
+		out.sample(
+			"moq_relay_subscriptions",
+			&[("direction", "downstream")],
+			state.subscriptions_downstream.load(Ordering::Relaxed),
+		);
+
+		out.header("moq_relay_groups_total", "counter", "The number of groups relayed.");
+		out.sample(
+			"moq_relay_groups_total",
+			&[("direction", "received")],
+			state.groups_received.load(Ordering::Relaxed),
+		);
+		out.sample(
+			"moq_relay_groups_total",
+			&[("direction", "sent")],
+			state.groups_sent.load(Ordering::Relaxed),
+		);
+
+		out.header(
+			"moq_relay_groups_dropped_total",
+			"counter",
+			"The number of groups dropped instead of delivered.",
+		);
+		out.sample(
+			"moq_relay_groups_dropped_total",
+			&[("by", "local")],
+			state.dropped_local.load(Ordering::Relaxed),
+		);
+		out.sample(
+			"moq_relay_groups_dropped_total",
+			&[("by", "remote")],
+			state.dropped_remote.load(Ordering::Relaxed),
+		);
+
+		// Include the bytes transferred by any open connections.
+		let mut sent = state.bytes_sent.load(Ordering::Relaxed);
+		let mut received = state.bytes_received.load(Ordering::Relaxed);
+
+		for conn in state.connections.lock().unwrap().values() {
+			let stats = conn.stats();
+			sent += stats.udp_tx.bytes;
+			received += stats.udp_rx.bytes;
+		}
+
+		out.header(
+			"moq_relay_bytes_total",
+			"counter",
+			"The number of bytes transferred over QUIC.",
+		);
+		out.sample("moq_relay_bytes_total", &[("direction", "sent")], sent);
+		out.sample("moq_relay_bytes_total", &[("direction", "received")], received);
+
+		let health = self.health.lock().unwrap();
+
+		out.header(
+			"moq_relay_cluster_remote",
+			"gauge",
+			"The state of each connection to another node, set to 1 for the current state.",
+		);
+		for (host, status) in health.iter() {
+			for state in [RemoteState::Connecting, RemoteState::Healthy, RemoteState::Failed] {
+				out.sample(
+					"moq_relay_cluster_remote",
+					&[("remote", host), ("state", state.as_str())],
+					(state == status.state) as u8,
+				);
+			}
+		}
+
+		out.header(
+			"moq_relay_cluster_remote_failures",
+			"gauge",
+			"The number of consecutive failed connections to another node.",
+		);
+		for (host, status) in health.iter() {
+			out.sample(
+				"moq_relay_cluster_remote_failures",
+				&[("remote", host)],
+				status.failures,
+			);
+		}
+
+		drop(health);
+
+		out.finish()
+	}
+}
+
+/// Decrements the number of sessions when dropped.
+pub struct SessionGuard {
+	metrics: Metrics,
+	kind: SessionKind,
+}
+
+impl Drop for SessionGuard {
+	fn drop(&mut self) {
+		self.metrics.sessions(self.kind).fetch_sub(1, Ordering::Relaxed);
+	}
+}
+
+/// Stops sampling the connection when dropped, keeping the final byte counts.
+pub struct ConnectionGuard {
+	metrics: Metrics,
+	id: usize,
+}
+
+impl Drop for ConnectionGuard {
+	fn drop(&mut self) {
+		let state = &self.metrics.state;
+
+		if let Some(conn) = state.connections.lock().unwrap().remove(&self.id) {
+			let stats = conn.stats();
+			state.bytes_sent.fetch_add(stats.udp_tx.bytes, Ordering::Relaxed);
+			state.bytes_received.fetch_add(stats.udp_rx.bytes, Ordering::Relaxed);
+		}
+	}
+}
+
+// Counts the events for a single session.
+struct SessionObserver {
+	metrics: Metrics,
+	qlog: Option<Arc<Qlog>>,
+
+	// The open subscribe streams, and whether we opened them.
+	subscriptions: Mutex<HashMap<u64, bool>>,
+}
+
+impl SessionObserver {
+	fn subscriptions(&self, local: bool) -> &AtomicI64 {
+		match local {
+			true => &self.metrics.state.subscriptions_upstream,
+			false => &self.metrics.state.subscriptions_downstream,
+		}
+	}
+}
+
+impl Observer for SessionObserver {
+	fn event(&self, event: Event) {
+		let state = &self.metrics.state;
+
+		match &event {
+			Event::StreamOpened {
+				stream,
+				kind: StreamKind::Subscribe,
+				local,
+			} => {
+				self.subscriptions.lock().unwrap().insert(*stream, *local);
+				self.subscriptions(*local).fetch_add(1, Ordering::Relaxed);
+			}
+			Event::StreamOpened {
+				kind: StreamKind::Group { .. },
+				local,
+				..
+			} => {
+				match local {
+					true => state.groups_sent.fetch_add(1, Ordering::Relaxed),
+					false => state.groups_received.fetch_add(1, Ordering::Relaxed),
+				};
+			}
+			Event::StreamClosed { stream } => {
+				if let Some(local) = self.subscriptions.lock().unwrap().remove(stream) {
+					self.subscriptions(local).fetch_sub(1, Ordering::Relaxed);
+				}
+			}
+			Event::GroupDropped { count, local, .. } => {
+				match local {
+					true => state.dropped_local.fetch_add(*count, Ordering::Relaxed),
+					false => state.dropped_remote.fetch_add(*count, Ordering::Relaxed),
+				};
+			}
+			_ => {}
+		}
+
+		if let Some(qlog) = &self.qlog {
+			qlog.event(event);
+		}
+	}
+}
+
+impl Drop for SessionObserver {
+	fn drop(&mut self) {
+		// Any subscriptions still open were closed along with the session.
+		let remaining: Vec<bool> = self
+			.subscriptions
+			.get_mut()
+			.unwrap()
+			.drain()
+			.map(|(_, local)| local)
+			.collect();
+		for local in remaining {
+			self.subscriptions(local).fetch_sub(1, Ordering::Relaxed);
+		}
+	}
+}
+
+// Writes metrics in the Prometheus text format, labeling each sample with the node.
+struct Metric {
+	node: String,
+	out: String,
+}
+
+impl Metric {
+	fn new(node: Option<&str>) -> Self {
+		Self {
+			node: escape(node.unwrap_or_default()),
+			out: String::new(),
+		}
+	}
+
+	fn header(&mut self, name: &str, kind: &str, help: &str) {
+		writeln!(self.out, "# HELP {} {}", name, help).unwrap();
+		writeln!(self.out, "# TYPE {} {}", name, kind).unwrap();
+	}
+
+	fn sample<V: std::fmt::Display>(&mut self, name: &str, labels: &[(&str, &str)], value: V) {
+		write!(self.out, "{}{{node=\"{}\"", name, self.node).unwrap();
+		for (key, value) in labels {
+			write!(self.out, ",{}=\"{}\"", key, escape(value)).unwrap();
+		}
+		writeln!(self.out, "}} {}", value).unwrap();
+	}
+
+	fn finish(self) -> String {
+		self.out
+	}
+}
+
+// Escape a label value, per the Prometheus text format.
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{CacheConfig, RemoteStatus};
+
+	#[test]
+	fn render() {
+		let health = RemoteHealth::default();
+		let metrics = Metrics::new(
+			Some("relay\"1".to_string()),
+			Origins::default(),
+			Origins::default(),
+			Cache::new(CacheConfig {
+				cache_duration: 30,
+				cache_size: 1024,
+			}),
+			health.clone(),
+		);
+
+		let observer = metrics.observer(None);
+		observer.event(Event::StreamOpened {
+			stream: 4,
+			kind: StreamKind::Subscribe,
+			local: false,
+		});
+
+		let session = metrics.session(SessionKind::User);
+		health.lock().unwrap().insert(
+			"relay2".to_string(),
+			RemoteStatus {
+				state: RemoteState::Healthy,
+				since: std::time::Instant::now(),
+				failures: 2,
+				error: None,
+			},
+		);
+
+		let out = metrics.render();
+		assert!(out.contains("# TYPE moq_relay_sessions gauge\n"));
+		assert!(out.contains("moq_relay_sessions{node=\"relay\\\"1\",kind=\"user\"} 1\n"));
+		assert!(out.contains("moq_relay_subscriptions{node=\"relay\\\"1\",direction=\"downstream\"} 1\n"));
+		assert!(out.contains("moq_relay_cluster_remote{node=\"relay\\\"1\",remote=\"relay2\",state=\"healthy\"} 1\n"));
+		assert!(
+			out.contains("moq_relay_cluster_remote{node=\"relay\\\"1\",remote=\"relay2\",state=\"connecting\"} 0\n")
+		);
+		assert!(out.contains("moq_relay_cluster_remote_failures{node=\"relay\\\"1\",remote=\"relay2\"} 2\n"));
+
+		// Dropping the session closes any remaining subscriptions.
+		drop(observer);
+		drop(session);
+
+		let out = metrics.render();
+		assert!(out.contains("moq_relay_sessions{node=\"relay\\\"1\",kind=\"user\"} 0\n"));
+		assert!(out.contains("moq_relay_subscriptions{node=\"relay\\\"1\",direction=\"downstream\"} 0\n"));
+	}
+}
@@ -1,60 +1,188 @@
 use std::{
-	collections::{hash_map, HashMap},
-	sync::{Arc, Mutex},
+	collections::{hash_map, HashMap, HashSet},
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
 };
 
 use moq_transfork::{Announced, AnnouncedConsumer, AnnouncedProducer, Filter, Session};
+use serde::Deserialize;
+use tokio::sync::watch;
+
+use crate::{LimitError, Limiter, INTERNAL};
+
+/// Where announced tracks can be routed.
+#[derive(Clone, Default, PartialEq)]
+pub struct Origin {
+	/// The session that announced the tracks, or None if they're produced by this relay.
+	pub session: Option<Session>,
+
+	/// The cluster node that produces the tracks, if known.
+	pub node: Option<String>,
+
+	/// The announced paths are namespaces that own any track within them, as announced by IETF moq-transport sessions.
+	pub namespace: bool,
+}
+
+/// Which origin owns a path when multiple sessions announce it.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OriginPolicy {
+	/// The first session to announce the path, falling back to the next oldest when it leaves.
+	Oldest,
+
+	/// The most recent session to announce the path, so a reconnecting publisher takes over immediately.
+	#[default]
+	Newest,
+
+	/// Ignore any duplicate announcements while the path is owned.
+	Reject,
+}
+
+// An origin that announced a path, identified by its announce loop.
+#[derive(Clone)]
+struct Route {
+	id: u64,
+	origin: Origin,
+}
 
 #[derive(Clone)]
 pub struct Origins {
 	// Tracks announced by clients.
 	unique: AnnouncedProducer,
 
-	// Active routes based on path.
-	routes: Arc<Mutex<HashMap<String, Vec<Option<Session>>>>>,
+	// If true, only paths within the internal namespace are accepted, otherwise they're ignored.
+	internal: bool,
+
+	// Decides which origin owns each path.
+	policy: OriginPolicy,
+
+	// Active routes based on path, in the order they were announced.
+	routes: Arc<Mutex<HashMap<String, Vec<Route>>>>,
+
+	// Used to uniquely identify each announce loop.
+	next: Arc<AtomicU64>,
+
+	// Notified whenever the owner of any path changes.
+	changed: Arc<watch::Sender<()>>,
 }
 
 impl Default for Origins {
 	fn default() -> Self {
-		Self::new()
+		Self::new(OriginPolicy::default())
 	}
 }
 
 impl Origins {
-	pub fn new() -> Self {
+	pub fn new(policy: OriginPolicy) -> Self {
+		// We're always caught up, as every announcement is made locally.
+		let mut unique = AnnouncedProducer::new();
+		unique.live();
+
 		Self {
-			unique: AnnouncedProducer::new(),
+			unique,
+			internal: false,
+			policy,
 			routes: Default::default(),
+			next: Default::default(),
+			changed: Arc::new(watch::channel(()).0),
+		}
+	}
+
+	/// Only accept paths within the internal namespace, used by the cluster.
+	pub fn internal() -> Self {
+		Self {
+			internal: true,
+			..Self::default()
 		}
 	}
 
 	// Route any announcements from the cluster.
-	pub async fn announce(&mut self, mut announced: AnnouncedConsumer, origin: Option<Session>) {
+	//
+	// Any remaining routes are removed when the announcements end or this future is dropped.
+	pub async fn announce(&mut self, announced: AnnouncedConsumer, origin: Origin) {
+		// Without a limiter, there's nothing to exceed.
+		self.announce_limited(announced, origin, None).await.ok();
+	}
+
+	/// Route any announcements from a session, returning an error if it announces too many tracks.
+	pub async fn announce_limited(
+		&mut self,
+		mut announced: AnnouncedConsumer,
+		origin: Origin,
+		limiter: Option<Arc<Limiter>>,
+	) -> Result<(), LimitError> {
+		let mut active = Announcing {
+			origins: self.clone(),
+			id: self.next.fetch_add(1, Ordering::Relaxed),
+			paths: HashSet::new(),
+			limiter,
+		};
+
 		while let Some(announced) = announced.next().await {
 			match announced {
-				Announced::Active(am) => self.announce_track(am.to_full(), origin.clone()),
-				Announced::Ended(am) => self.unannounce_track(am.full(), &origin),
+				Announced::Active(am) | Announced::Ended(am) if am.full().starts_with(INTERNAL) != self.internal => {
+					// Ignore paths outside of our namespace.
+				}
+				Announced::Active(am) => {
+					if active.paths.contains(am.full()) {
+						continue;
+					}
+
+					if let Some(limiter) = &active.limiter {
+						limiter.announce()?;
+					}
+
+					if self.announce_track(am.full(), active.id, origin.clone()) {
+						active.paths.insert(am.to_full());
+					} else if let Some(limiter) = &active.limiter {
+						limiter.unannounce(1);
+					}
+				}
+				Announced::Ended(am) => {
+					if active.paths.remove(am.full()) {
+						self.unannounce_track(am.full(), active.id);
+
+						if let Some(limiter) = &active.limiter {
+							limiter.unannounce(1);
+						}
+					}
+				}
 				Announced::Live => {
 					// Ignore.
 				}
 			}
 		}
-	}
 
-	fn announce_track(&mut self, path: String, origin: Option<Session>) {
-		tracing::info!(?path, "announced origin");
+		Ok(())
+	}
 
+	// Returns false if the announcement was rejected.
+	fn announce_track(&mut self, path: &str, id: u64, origin: Origin) -> bool {
 		let mut routes = self.routes.lock().unwrap();
-		match routes.entry(path.clone()) {
-			hash_map::Entry::Occupied(mut entry) => entry.get_mut().push(origin),
+		let entry = match routes.entry(path.to_string()) {
+			hash_map::Entry::Occupied(entry) if self.policy == OriginPolicy::Reject => {
+				tracing::warn!(?path, owner = ?entry.get()[0].origin.node, "rejected duplicate origin");
+				return false;
+			}
+			hash_map::Entry::Occupied(entry) => entry.into_mut(),
 			hash_map::Entry::Vacant(entry) => {
-				entry.insert(vec![origin]);
 				self.unique.announce(path);
+				entry.insert(Vec::new())
 			}
-		}
+		};
+
+		tracing::info!(?path, node = ?origin.node, "announced origin");
+
+		let before = self.owner(entry).map(|route| route.id);
+		entry.push(Route { id, origin });
+		self.update_owner(path, before, entry);
+
+		true
 	}
 
-	fn unannounce_track(&mut self, path: &str, origin: &Option<Session>) {
+	fn unannounce_track(&mut self, path: &str, id: u64) {
 		tracing::info!(?path, "unannounced origin");
 
 		let mut routes = self.routes.lock().unwrap();
@@ -63,9 +191,9 @@ impl Origins {
 			hash_map::Entry::Vacant(_) => return,
 		};
 
-		// Technically this is wrong, as it will remove more than one None value.
-		// But currently there can only be one None that will never be removed, so it's fine.
-		entry.retain(|s| s != origin);
+		let before = self.owner(entry).map(|route| route.id);
+		entry.retain(|route| route.id != id);
+		self.update_owner(path, before, entry);
 
 		if entry.is_empty() {
 			routes.remove(path);
@@ -73,15 +201,213 @@ impl Origins {
 		}
 	}
 
+	// Returns the route that owns the path, based on the policy.
+	fn owner<'a>(&self, routes: &'a [Route]) -> Option<&'a Route> {
+		match self.policy {
+			OriginPolicy::Oldest | OriginPolicy::Reject => routes.first(),
+			OriginPolicy::Newest => routes.last(),
+		}
+	}
+
+	// Notify any subscriptions if the owner of the path changed.
+	fn update_owner(&self, path: &str, before: Option<u64>, routes: &[Route]) {
+		let after = self.owner(routes);
+		if before == after.map(|route| route.id) {
+			return;
+		}
+
+		if before.is_some() {
+			match after {
+				Some(route) => tracing::info!(?path, node = ?route.origin.node, "origin changed"),
+				None => tracing::info!(?path, "origin removed"),
+			}
+		}
+
+		self.changed.send_modify(|_| {});
+	}
+
+	/// Returns the number of announced paths.
+	pub fn count(&self) -> usize {
+		self.routes.lock().unwrap().len()
+	}
+
 	pub fn announced(&self, filter: Filter) -> AnnouncedConsumer {
 		self.unique.subscribe(filter)
 	}
 
+	/// Returns a receiver notified whenever the owner of any path changes.
+	pub fn changes(&self) -> watch::Receiver<()> {
+		self.changed.subscribe()
+	}
+
 	pub fn route(&self, path: &str) -> Option<Session> {
-		// Return the session that most recently announced the path.
+		self.origin(path)?.session
+	}
+
+	/// Returns the origin that owns the path, if it's announced.
+	///
+	/// Otherwise the track is owned by the origin of its namespace (everything before the last `/`, if any), if it was announced as one.
+	pub fn origin(&self, path: &str) -> Option<Origin> {
 		let routes = self.routes.lock().unwrap();
 
-		let available = routes.get(path)?;
-		available.iter().find(|route| route.is_some()).cloned().unwrap()
+		if let Some(route) = routes.get(path).and_then(|routes| self.owner(routes)) {
+			return Some(route.origin.clone());
+		}
+
+		let (namespace, _) = path.rsplit_once('/').unwrap_or(("", path));
+		let route = self.owner(routes.get(namespace)?)?;
+		route.origin.namespace.then(|| route.origin.clone())
+	}
+}
+
+// Removes the routes for any paths still announced by an origin when dropped.
+struct Announcing {
+	origins: Origins,
+	id: u64,
+	paths: HashSet<String>,
+
+	// Counts the announced paths towards the session's limit.
+	limiter: Option<Arc<Limiter>>,
+}
+
+impl Drop for Announcing {
+	fn drop(&mut self) {
+		if let Some(limiter) = &self.limiter {
+			limiter.unannounce(self.paths.len());
+		}
+
+		for path in self.paths.drain() {
+			self.origins.unannounce_track(&path, self.id);
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use futures::FutureExt;
+
+	#[test]
+	fn internal() {
+		let mut producer = AnnouncedProducer::new();
+		producer.announce("internal/origins/a");
+		producer.announce("demo/bbb");
+
+		let locals = Origins::default();
+		let internal = Origins::internal();
+
+		// The announce loops never exit while the producer exists, so only poll them once.
+		let mut announcing_locals = locals.clone();
+		let mut announcing_internal = internal.clone();
+		let mut local_task = Box::pin(announcing_locals.announce(producer.subscribe(Filter::Any), Origin::default()));
+		let mut internal_task =
+			Box::pin(announcing_internal.announce(producer.subscribe(Filter::Any), Origin::default()));
+		assert!((&mut local_task).now_or_never().is_none());
+		assert!((&mut internal_task).now_or_never().is_none());
+
+		let mut announced = locals.announced(Filter::Any);
+		assert!(
+			matches!(announced.next().now_or_never(), Some(Some(Announced::Active(am))) if am.full() == "demo/bbb")
+		);
+		assert!(matches!(announced.next().now_or_never(), Some(Some(Announced::Live))));
+		assert!(announced.next().now_or_never().is_none());
+
+		let mut announced = internal.announced(Filter::Any);
+		assert!(
+			matches!(announced.next().now_or_never(), Some(Some(Announced::Active(am))) if am.full() == "internal/origins/a")
+		);
+		assert!(matches!(announced.next().now_or_never(), Some(Some(Announced::Live))));
+		assert!(announced.next().now_or_never().is_none());
+	}
+
+	#[test]
+	fn cancel() {
+		let mut producer = AnnouncedProducer::new();
+		producer.announce("demo/bbb");
+
+		let origins = Origins::default();
+		let mut announcing = origins.clone();
+		let mut task = Box::pin(announcing.announce(producer.subscribe(Filter::Any), Origin::default()));
+		assert!((&mut task).now_or_never().is_none());
+		assert_eq!(origins.count(), 1);
+
+		// Dropping the announce loop removes its routes, even though the path was never unannounced.
+		drop(task);
+		assert_eq!(origins.count(), 0);
+	}
+
+	#[test]
+	fn policy() {
+		let mut producer = AnnouncedProducer::new();
+		producer.announce("demo/bbb");
+
+		let origin = |node: &str| Origin {
+			session: None,
+			node: Some(node.to_string()),
+			namespace: false,
+		};
+		let owner = |origins: &Origins| origins.origin("demo/bbb").and_then(|origin| origin.node);
+
+		for (policy, first, second) in [
+			(OriginPolicy::Oldest, Some("a"), Some("b")),
+			(OriginPolicy::Newest, Some("b"), Some("b")),
+			(OriginPolicy::Reject, Some("a"), None),
+		] {
+			let origins = Origins::new(policy);
+			let mut changes = origins.changes();
+
+			let mut announcing_a = origins.clone();
+			let mut announcing_b = origins.clone();
+			let mut a = Box::pin(announcing_a.announce(producer.subscribe(Filter::Any), origin("a")));
+			let mut b = Box::pin(announcing_b.announce(producer.subscribe(Filter::Any), origin("b")));
+			assert!((&mut a).now_or_never().is_none());
+			assert!((&mut b).now_or_never().is_none());
+
+			assert_eq!(owner(&origins).as_deref(), first, "{:?}", policy);
+			assert!(changes.has_changed().unwrap());
+			changes.mark_unchanged();
+
+			// The remaining origin takes over when the oldest leaves, unless it was rejected.
+			drop(a);
+			assert_eq!(owner(&origins).as_deref(), second, "{:?}", policy);
+			assert_eq!(changes.has_changed().unwrap(), first != second, "{:?}", policy);
+			assert_eq!(origins.count(), second.is_some() as usize);
+		}
+	}
+
+	#[test]
+	fn namespace() {
+		let mut producer = AnnouncedProducer::new();
+		producer.announce("demo/bbb");
+
+		let origins = Origins::default();
+		let mut announcing = origins.clone();
+		let ietf = Origin {
+			node: Some("ietf".to_string()),
+			namespace: true,
+			..Default::default()
+		};
+		let mut task = Box::pin(announcing.announce(producer.subscribe(Filter::Any), ietf));
+		assert!((&mut task).now_or_never().is_none());
+
+		// Any track within the namespace is routed to the session that announced it.
+		let node = |path: &str| origins.origin(path).and_then(|origin| origin.node);
+		assert_eq!(node("demo/bbb").as_deref(), Some("ietf"));
+		assert_eq!(node("demo/bbb/video").as_deref(), Some("ietf"));
+		assert_eq!(node("demo/bbb/video/hd"), None);
+		assert_eq!(node("demo/other"), None);
+
+		// Tracks without a `/` are within the empty namespace.
+		producer.announce("");
+		assert!((&mut task).now_or_never().is_none());
+		assert_eq!(node("clock").as_deref(), Some("ietf"));
+
+		// A track path only routes itself.
+		drop(task);
+		let mut announcing = origins.clone();
+		let mut task = Box::pin(announcing.announce(producer.subscribe(Filter::Any), Origin::default()));
+		assert!((&mut task).now_or_never().is_none());
+		assert!(origins.origin("demo/bbb").is_some());
+		assert!(origins.origin("demo/bbb/video").is_none());
 	}
 }
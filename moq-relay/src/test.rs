@@ -0,0 +1,30 @@
+//! Helpers shared by the unit tests, which need real sessions over localhost.
+
+use moq_native::{quic, tls};
+use url::Url;
+use web_transport::quinn as web_transport_quinn;
+
+/// Returns an endpoint on localhost with a self-signed certificate, which its own client doesn't verify.
+pub fn endpoint() -> quic::Endpoint {
+	let tls = tls::Args {
+		self_sign: vec!["localhost".to_string()],
+		disable_verify: true,
+		..Default::default()
+	};
+
+	quic::Endpoint::new(quic::Config {
+		bind: "127.0.0.1:0".parse().unwrap(),
+		tls: tls.load().unwrap(),
+		qlog: None,
+	})
+	.unwrap()
+}
+
+/// Connect the endpoint to itself, returning the accepted session and the client session.
+pub async fn connect(endpoint: quic::Endpoint) -> (quic::Accepted, web_transport_quinn::Session) {
+	let mut server = endpoint.server.unwrap();
+	let url = Url::parse(&format!("https://127.0.0.1:{}", server.local_addr().unwrap().port())).unwrap();
+
+	let (accepted, client) = tokio::join!(server.accept_request(), endpoint.client.connect(url));
+	(accepted.unwrap(), client.unwrap())
+}
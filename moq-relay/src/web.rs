@@ -1,30 +1,42 @@
 use std::{
+	cmp::Ordering,
+	collections::{hash_map, BTreeMap, HashMap},
+	convert::Infallible,
+	hash::{BuildHasher, RandomState},
 	net,
-	pin::Pin,
-	task::{ready, Context, Poll},
+	str::FromStr,
+	sync::Arc,
+	time,
 };
 
 use axum::{
 	body::Body,
-	extract::Path,
-	http::{Method, StatusCode},
-	response::{IntoResponse, Response},
+	extract::{Path, Query},
+	http::{header, HeaderMap, HeaderName, Method, StatusCode},
+	response::{
+		sse::{Event, KeepAlive, Sse},
+		IntoResponse, Response,
+	},
 	routing::get,
-	Router,
+	Json, Router,
 };
-use bytes::Bytes;
-use futures::FutureExt;
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
 use hyper_serve::accept::DefaultAcceptor;
-use moq_transfork::Filter;
-use std::future::Future;
+use moq_transfork::{Announced, AnnouncedConsumer, Filter};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
 use tower_http::cors::{Any, CorsLayer};
 
-use crate::Cluster;
+use crate::{Auth, AuthError, Cache, Claims, Cluster};
 
 pub struct WebConfig {
 	pub bind: net::SocketAddr,
-	pub tls: moq_native::tls::Config,
+	pub fingerprints: watch::Receiver<Vec<String>>,
 	pub cluster: Cluster,
+
+	// Used to verify the token of each request, or None if authorization is disabled.
+	pub auth: watch::Receiver<Option<Arc<Auth>>>,
 }
 
 // Run a HTTP server using Axum
@@ -36,34 +48,51 @@ pub struct Web {
 
 impl Web {
 	pub fn new(config: WebConfig) -> Self {
-		// Get the first certificate's fingerprint.
-		// TODO serve all of them so we can support multiple signature algorithms.
-		let fingerprint = config.tls.fingerprints.first().expect("missing certificate").clone();
-
 		let app = Router::new()
-			.route("/fingerprint", get(fingerprint))
+			.route(
+				"/fingerprint",
+				get({
+					let fingerprints = config.fingerprints.clone();
+					move || serve_fingerprint(fingerprints)
+				}),
+			)
+			.route(
+				"/metrics",
+				get({
+					let cluster = config.cluster.clone();
+					move || serve_metrics(cluster)
+				}),
+			)
 			.route(
 				"/announced",
 				get({
 					let cluster = config.cluster.clone();
-					move || serve_announced(Path("".to_string()), cluster.clone())
+					let auth = config.auth.clone();
+					move |token, headers| serve_announced(Path("".to_string()), token, headers, cluster, auth)
 				}),
 			)
 			.route(
 				"/announced/{*prefix}",
 				get({
 					let cluster = config.cluster.clone();
-					move |path| serve_announced(path, cluster)
+					let auth = config.auth.clone();
+					move |path, token, headers| serve_announced(path, token, headers, cluster, auth)
 				}),
 			)
 			.route(
 				"/fetch/{*path}",
 				get({
 					let cluster = config.cluster.clone();
-					move |path| serve_fetch(path, cluster)
+					let auth = config.auth.clone();
+					move |path, query, token, headers| serve_fetch(path, query, token, headers, cluster, auth)
 				}),
 			)
-			.layer(CorsLayer::new().allow_origin(Any).allow_methods([Method::GET]));
+			.layer(
+				CorsLayer::new()
+					.allow_origin(Any)
+					.allow_methods([Method::GET])
+					.expose_headers(Any),
+			);
 
 		let server = hyper_serve::bind(config.bind);
 
@@ -76,8 +105,60 @@ impl Web {
 	}
 }
 
-/// Serve the announced tracks for a given prefix.
-async fn serve_announced(Path(path): Path<String>, cluster: Cluster) -> impl IntoResponse {
+/// Serve the fingerprint of the current TLS certificate, which changes when the certificates are reloaded.
+// TODO serve all of them so we can support multiple signature algorithms.
+async fn serve_fingerprint(fingerprints: watch::Receiver<Vec<String>>) -> String {
+	fingerprints.borrow().first().expect("missing certificate").clone()
+}
+
+/// Serve the metrics in the Prometheus text format.
+async fn serve_metrics(cluster: Cluster) -> impl IntoResponse {
+	let headers = [(header::CONTENT_TYPE, "text/plain; version=0.0.4")];
+	(headers, cluster.metrics.render())
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+	/// The token, for clients that can't set the `Authorization` header (ex. `EventSource`).
+	jwt: Option<String>,
+}
+
+/// Verify the token in the `jwt` query parameter, or otherwise the `Authorization: Bearer` header.
+///
+/// Any path is permitted if authorization is disabled.
+fn authorize(
+	auth: &watch::Receiver<Option<Arc<Auth>>>,
+	query: &TokenQuery,
+	headers: &HeaderMap,
+) -> Result<Claims, AuthError> {
+	let Some(auth) = auth.borrow().clone() else {
+		return Ok(Claims::any());
+	};
+
+	let bearer = headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+
+	let token = query.jwt.as_deref().or(bearer).ok_or(AuthError::Missing)?;
+	auth.verify(token)
+}
+
+/// Serve the announced tracks for a given prefix, only including those the token may subscribe to.
+///
+/// Returns a JSON snapshot by default, or streams each change as a Server-Sent Event if requested via the `Accept` header.
+async fn serve_announced(
+	Path(path): Path<String>,
+	Query(token): Query<TokenQuery>,
+	headers: HeaderMap,
+	cluster: Cluster,
+	auth: watch::Receiver<Option<Arc<Auth>>>,
+) -> Response {
+	let claims = match authorize(&auth, &token, &headers) {
+		Ok(claims) => claims,
+		Err(err) => return (StatusCode::UNAUTHORIZED, err.to_string()).into_response(),
+	};
+
 	// Make anything without a / prefix private.
 	let filter = if path.is_empty() {
 		Filter::Any
@@ -90,118 +171,508 @@ async fn serve_announced(Path(path): Path<String>, cluster: Cluster) -> impl Int
 		Filter::Prefix(path)
 	};
 
-	let mut local = cluster.locals.announced(filter.clone());
-	let mut remote = cluster.remotes.announced(filter);
+	let events = AnnouncedEvents::new(cluster, filter, claims);
+
+	let stream = headers
+		.get_all(header::ACCEPT)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.any(|value| value.contains("text/event-stream"));
+
+	match stream {
+		true => serve_announced_events(events).into_response(),
+		false => serve_announced_snapshot(events).await.into_response(),
+	}
+}
 
-	let mut tracks = Vec::new();
+/// Returns every announced track once the cluster is caught up.
+async fn serve_announced_snapshot(mut events: AnnouncedEvents) -> Json<AnnouncedSnapshot> {
+	let mut tracks = BTreeMap::new();
 
-	while let Some(Some(local)) = local.next().now_or_never() {
-		if let moq_transfork::Announced::Active(track) = local {
-			tracks.push(track.to_full());
+	while let Some(event) = events.next().await {
+		match event {
+			AnnouncedEvent::Active(info) => {
+				tracks.insert(info.path.clone(), info);
+			}
+			AnnouncedEvent::Ended(path) => {
+				tracks.remove(&path);
+			}
+			AnnouncedEvent::Live => break,
 		}
 	}
 
-	while let Some(Some(remote)) = remote.next().now_or_never() {
-		if let moq_transfork::Announced::Active(track) = remote {
-			tracks.push(track.to_full());
+	Json(AnnouncedSnapshot {
+		tracks: tracks.into_values().collect(),
+	})
+}
+
+/// Streams each change to the announced tracks, as they happen.
+fn serve_announced_events(events: AnnouncedEvents) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+	let stream = futures::stream::unfold(events, |mut events| async move {
+		let event = match events.next().await? {
+			AnnouncedEvent::Active(info) => Event::default().event("active").json_data(info),
+			AnnouncedEvent::Ended(path) => Event::default().event("ended").json_data(AnnouncedEnded { path }),
+			// Browsers ignore events without any data.
+			AnnouncedEvent::Live => Ok(Event::default().event("live").data("{}")),
+		};
+
+		// Serializing the info can't fail.
+		let event = event.expect("failed to serialize event");
+		Some((Ok(event), events))
+	});
+
+	Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[derive(Serialize)]
+struct AnnouncedSnapshot {
+	tracks: Vec<AnnouncedInfo>,
+}
+
+#[derive(Serialize)]
+struct AnnouncedInfo {
+	/// The full path of the track.
+	path: String,
+
+	/// True if the track was announced by another node in the cluster.
+	remote: bool,
+
+	/// The cluster node that produces the track, if known.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	node: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AnnouncedEnded {
+	/// The full path of the track.
+	path: String,
+}
+
+enum AnnouncedEvent {
+	Active(AnnouncedInfo),
+	Ended(String),
+	Live,
+}
+
+/// Merges the local and remote announcements, deduplicating any paths announced by both.
+struct AnnouncedEvents {
+	cluster: Cluster,
+	local: AnnouncedConsumer,
+	remote: AnnouncedConsumer,
+
+	// Any paths the token may not subscribe to are skipped.
+	claims: Claims,
+
+	// The number of sources announcing each path.
+	active: HashMap<String, usize>,
+
+	// The number of sources that are caught up.
+	live: usize,
+}
+
+impl AnnouncedEvents {
+	fn new(cluster: Cluster, filter: Filter, claims: Claims) -> Self {
+		Self {
+			local: cluster.locals.announced(filter.clone()),
+			remote: cluster.remotes.announced(filter),
+			cluster,
+			claims,
+			active: HashMap::new(),
+			live: 0,
 		}
 	}
 
-	tracks.join("\n")
+	async fn next(&mut self) -> Option<AnnouncedEvent> {
+		loop {
+			let announced = tokio::select! {
+				Some(announced) = self.local.next() => announced,
+				Some(announced) = self.remote.next() => announced,
+				else => return None,
+			};
+
+			match announced {
+				Announced::Active(am) | Announced::Ended(am) if !self.claims.can_subscribe(am.full()) => continue,
+				Announced::Active(am) => {
+					let count = self.active.entry(am.full().to_string()).or_default();
+					*count += 1;
+
+					if *count == 1 {
+						return Some(AnnouncedEvent::Active(self.info(am.to_full())));
+					}
+				}
+				Announced::Ended(am) => {
+					let hash_map::Entry::Occupied(mut entry) = self.active.entry(am.full().to_string()) else {
+						continue;
+					};
+
+					*entry.get_mut() -= 1;
+					if *entry.get() == 0 {
+						entry.remove();
+						return Some(AnnouncedEvent::Ended(am.to_full()));
+					}
+				}
+				Announced::Live => {
+					self.live += 1;
+
+					// Only live once both sources are caught up.
+					if self.live == 2 {
+						return Some(AnnouncedEvent::Live);
+					}
+				}
+			}
+		}
+	}
+
+	fn info(&self, path: String) -> AnnouncedInfo {
+		let local = self.cluster.locals.origin(&path);
+		let remote = self.cluster.remotes.origin(&path);
+
+		// Other nodes relay their tracks to us as locals, so prefer the remote origin if the node is unknown.
+		let origin = match local {
+			Some(local) if local.node.is_some() => Some(local),
+			local => remote.or(local),
+		};
+
+		let node = origin.and_then(|origin| origin.node);
+		let remote = node.is_some() && node.as_deref() != self.cluster.node();
+
+		AnnouncedInfo { path, remote, node }
+	}
+}
+
+#[derive(Deserialize)]
+struct FetchQuery {
+	/// The group sequence: `latest` (default), `N`, or an inclusive range `N-M`.
+	group: Option<String>,
+
+	/// The frame indexes within each group: `N`, `N-`, or an inclusive range `N-M`, defaulting to every frame.
+	frame: Option<String>,
+}
+
+// The maximum number of groups that can be requested at once.
+const FETCH_MAX_GROUPS: u64 = 64;
+
+// The maximum number of groups past the latest that can be requested, as they would take too long to arrive.
+const FETCH_MAX_AHEAD: u64 = 64;
+
+// How long to wait for a requested group to arrive, in case the latest group isn't known yet.
+const FETCH_TIMEOUT: time::Duration = time::Duration::from_secs(30);
+
+/// An inclusive range of sequence numbers, unbounded if there's no end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FetchRange {
+	start: u64,
+	end: Option<u64>,
+}
+
+impl FetchRange {
+	fn contains(&self, index: u64) -> bool {
+		index >= self.start && self.end.is_none_or(|end| index <= end)
+	}
+}
+
+impl FromStr for FetchRange {
+	type Err = ();
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let parse = |s: &str| s.parse::<u64>().map_err(|_| ());
+
+		let range = match s.split_once('-') {
+			Some((start, "")) => Self {
+				start: parse(start)?,
+				end: None,
+			},
+			Some((start, end)) => Self {
+				start: parse(start)?,
+				end: Some(parse(end)?),
+			},
+			None => Self {
+				start: parse(s)?,
+				end: Some(parse(s)?),
+			},
+		};
+
+		match range.end {
+			Some(end) if end < range.start => Err(()),
+			_ => Ok(range),
+		}
+	}
 }
 
-/// Serve the latest group for a given track
-async fn serve_fetch(Path(path): Path<String>, cluster: Cluster) -> axum::response::Result<ServeGroup> {
+/// Serve the requested groups for a given track, defaulting to the latest group.
+///
+/// Each frame is a separate part of a `multipart/mixed` response, so frame boundaries are preserved.
+async fn serve_fetch(
+	Path(path): Path<String>,
+	Query(query): Query<FetchQuery>,
+	Query(token): Query<TokenQuery>,
+	headers: HeaderMap,
+	cluster: Cluster,
+	auth: watch::Receiver<Option<Arc<Auth>>>,
+) -> axum::response::Result<Response> {
+	let claims = authorize(&auth, &token, &headers).map_err(|err| (StatusCode::UNAUTHORIZED, err.to_string()))?;
+	if !claims.can_subscribe(&path) {
+		return Err((StatusCode::FORBIDDEN, "unauthorized path").into());
+	}
+
+	let groups = match query.group.as_deref() {
+		None | Some("latest") => None,
+		Some(group) => match group.parse::<FetchRange>() {
+			Ok(FetchRange { start, end: Some(end) }) if end - start < FETCH_MAX_GROUPS => Some(start..=end),
+			_ => return Err((StatusCode::BAD_REQUEST, "invalid group range").into()),
+		},
+	};
+
+	let frames = match query.frame.as_deref() {
+		None => FetchRange { start: 0, end: None },
+		Some(frame) => frame
+			.parse()
+			.map_err(|_| (StatusCode::BAD_REQUEST, "invalid frame range"))?,
+	};
+
 	let track = moq_transfork::Track {
 		path,
 		priority: 0,
 		order: moq_transfork::GroupOrder::Desc,
 	};
 
-	tracing::info!(?track, "subscribing to track");
+	tracing::info!(?track, ?groups, ?frames, "fetching track");
+
+	let cache = cluster.cache.clone();
+
+	// Don't hold an upstream subscription waiting for a group that won't arrive any time soon.
+	if let (Some(groups), Some(latest)) = (&groups, cache.latest(&track.path)) {
+		if *groups.start() > latest.sequence.saturating_add(FETCH_MAX_AHEAD) {
+			return Err((StatusCode::NOT_FOUND, "group too far in the future").into());
+		}
+	}
 
-	let mut track = match cluster.router.subscribe(track).await {
-		Ok(track) => track,
+	let mut upstream = match cluster.router.subscribe(track.clone()).await {
+		Ok(track) => Some(track),
+		// The origin may be gone, but we can still serve any cached groups.
+		Err(moq_transfork::Error::NotFound) if cache.latest(&track.path).is_some() => None,
 		Err(moq_transfork::Error::NotFound) => return Err(StatusCode::NOT_FOUND.into()),
 		Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
 	};
 
-	let group = match track.next_group().await {
-		Ok(group) => group,
-		Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
+	// Find the first group before responding, so we can return the correct status code.
+	let (group, remaining) = match (groups, &mut upstream) {
+		(None, Some(upstream)) => match upstream.next_group().await {
+			Ok(Some(group)) => (group, None),
+			Ok(None) => return Err(StatusCode::NO_CONTENT.into()),
+			Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
+		},
+		(None, None) => match cache.latest(&track.path) {
+			Some(group) => (group, None),
+			None => return Err(StatusCode::NOT_FOUND.into()),
+		},
+		(Some(mut sequences), _) => loop {
+			let Some(sequence) = sequences.next() else {
+				return Err(StatusCode::NOT_FOUND.into());
+			};
+
+			match fetch_group(&cache, &track.path, upstream.as_mut(), sequence).await {
+				Ok(Some(group)) => break (group, Some(sequences)),
+				Ok(None) => continue,
+				Err(e) => return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into()),
+			}
+		},
 	};
 
-	let group = match group {
-		Some(group) => group,
-		None => return Err(StatusCode::NO_CONTENT.into()),
+	let boundary = format!("moq-{:016x}", RandomState::new().hash_one(group.sequence));
+	let content_type = format!("multipart/mixed; boundary={}", boundary);
+
+	let info = upstream
+		.as_ref()
+		.map_or(track, |upstream| upstream.info.as_ref().clone());
+
+	let headers = [
+		(header::CONTENT_TYPE, content_type),
+		(HeaderName::from_static("moq-track-path"), info.path.clone()),
+		(HeaderName::from_static("moq-track-priority"), info.priority.to_string()),
+		(HeaderName::from_static("moq-group-order"), format!("{:?}", info.order)),
+		(
+			HeaderName::from_static("moq-group-sequence"),
+			group.sequence.to_string(),
+		),
+	];
+
+	let body = FetchBody {
+		cache,
+		path: info.path,
+		upstream,
+		group: Some((group, 0)),
+		remaining: remaining.into_iter().flatten(),
+		frames,
+		boundary,
 	};
 
-	Ok(ServeGroup::new(group))
-}
-
-struct ServeGroup {
-	group: moq_transfork::GroupConsumer,
-	frame: Option<moq_transfork::FrameConsumer>,
+	Ok((headers, Body::from_stream(body.into_stream())).into_response())
 }
 
-impl ServeGroup {
-	fn new(group: moq_transfork::GroupConsumer) -> Self {
-		Self { group, frame: None }
+/// Returns the group with the given sequence from the cache, otherwise waiting for it if it hasn't arrived yet.
+///
+/// Returns None if the group is no longer cached, or doesn't arrive within [FETCH_TIMEOUT].
+async fn fetch_group(
+	cache: &Cache,
+	path: &str,
+	track: Option<&mut moq_transfork::TrackConsumer>,
+	sequence: u64,
+) -> Result<Option<moq_transfork::GroupConsumer>, moq_transfork::Error> {
+	if let Some(group) = cache.group(path, sequence) {
+		return Ok(Some(group));
 	}
 
-	async fn next(&mut self) -> moq_transfork::Result<Option<Bytes>> {
-		loop {
-			if let Some(frame) = self.frame.as_mut() {
-				let data = frame.read_all().await?;
-				self.frame.take();
-				return Ok(Some(data));
-			}
+	let Some(track) = track else {
+		return Ok(None);
+	};
 
-			self.frame = self.group.next_frame().await?;
-			if self.frame.is_none() {
-				return Ok(None);
+	match track.get_group(sequence) {
+		Ok(group) => return Ok(Some(group)),
+		Err(moq_transfork::Error::NotFound) => (),
+		Err(err) => return Err(err),
+	}
+
+	let wait = async {
+		while let Some(group) = track.next_group().await? {
+			match group.sequence.cmp(&sequence) {
+				Ordering::Less => continue,
+				Ordering::Equal => return Ok(Some(group)),
+				Ordering::Greater => return Ok(None),
 			}
 		}
-	}
-}
 
-impl IntoResponse for ServeGroup {
-	fn into_response(self) -> Response {
-		Response::new(Body::new(self))
-	}
+		Ok(None)
+	};
+
+	tokio::time::timeout(FETCH_TIMEOUT, wait).await.unwrap_or(Ok(None))
 }
 
-impl http_body::Body for ServeGroup {
-	type Data = Bytes;
-	type Error = ServeGroupError;
+/// Writes each requested frame as a multipart part.
+struct FetchBody<I> {
+	cache: Cache,
+	path: String,
+
+	// The live track, unless the origin is gone and we're only serving cached groups.
+	upstream: Option<moq_transfork::TrackConsumer>,
 
-	fn poll_frame(
-		self: Pin<&mut Self>,
-		cx: &mut Context<'_>,
-	) -> Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
-		let this = self.get_mut();
+	// The current group and the index of the next frame.
+	group: Option<(moq_transfork::GroupConsumer, u64)>,
 
-		// Use `poll_fn` to turn the async function into a Future
-		let future = this.next();
-		tokio::pin!(future);
+	// The sequence numbers of any remaining groups.
+	remaining: I,
+
+	frames: FetchRange,
+	boundary: String,
+}
 
-		match ready!(future.poll(cx)) {
-			Ok(Some(data)) => {
-				let frame = http_body::Frame::data(data);
-				Poll::Ready(Some(Ok(frame)))
+impl<I: Iterator<Item = u64> + Send + 'static> FetchBody<I> {
+	fn into_stream(self) -> impl Stream<Item = Result<Bytes, moq_transfork::Error>> {
+		futures::stream::try_unfold(Some(self), |this| async move {
+			let Some(mut this) = this else {
+				return Ok(None);
+			};
+
+			match this.next_part().await? {
+				Some(part) => Ok(Some((part, Some(this)))),
+				None => {
+					let end = Bytes::from(format!("--{}--\r\n", this.boundary));
+					Ok(Some((end, None)))
+				}
 			}
-			Ok(None) => Poll::Ready(None),
-			Err(e) => Poll::Ready(Some(Err(ServeGroupError(e)))),
+		})
+	}
+
+	async fn next_part(&mut self) -> Result<Option<Bytes>, moq_transfork::Error> {
+		loop {
+			let Some((group, index)) = &mut self.group else {
+				// Advance to the next cached group, if any.
+				let Some(sequence) = self.remaining.next() else {
+					return Ok(None);
+				};
+
+				self.group = fetch_group(&self.cache, &self.path, self.upstream.as_mut(), sequence)
+					.await?
+					.map(|group| (group, 0));
+				continue;
+			};
+
+			let frame = match self.frames.end {
+				Some(end) if *index > end => None,
+				_ => group.read_frame().await?,
+			};
+
+			let Some(frame) = frame else {
+				self.group = None;
+				continue;
+			};
+
+			let current = *index;
+			*index += 1;
+
+			if !self.frames.contains(current) {
+				continue;
+			}
+
+			let header = format!(
+				"--{}\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nMoq-Group-Sequence: {}\r\nMoq-Frame-Index: {}\r\n\r\n",
+				self.boundary,
+				frame.len(),
+				group.sequence,
+				current
+			);
+
+			let mut part = BytesMut::with_capacity(header.len() + frame.len() + 2);
+			part.extend_from_slice(header.as_bytes());
+			part.extend_from_slice(&frame);
+			part.extend_from_slice(b"\r\n");
+
+			return Ok(Some(part.freeze()));
 		}
 	}
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error(transparent)]
-struct ServeGroupError(moq_transfork::Error);
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::CacheConfig;
+
+	#[test]
+	fn range() {
+		assert_eq!("3".parse(), Ok(FetchRange { start: 3, end: Some(3) }));
+		assert_eq!("3-5".parse(), Ok(FetchRange { start: 3, end: Some(5) }));
+		assert_eq!("3-".parse(), Ok(FetchRange { start: 3, end: None }));
+		assert!("5-3".parse::<FetchRange>().is_err());
+		assert!("latest".parse::<FetchRange>().is_err());
+
+		let range: FetchRange = "1-2".parse().unwrap();
+		assert!(!range.contains(0));
+		assert!(range.contains(1));
+		assert!(range.contains(2));
+		assert!(!range.contains(3));
+	}
 
-impl IntoResponse for ServeGroupError {
-	fn into_response(self) -> Response {
-		(StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()).into_response()
+	#[tokio::test(start_paused = true)]
+	async fn fetch_timeout() {
+		let cache = Cache::new(CacheConfig {
+			cache_duration: 60,
+			cache_size: 1024,
+		});
+
+		let (mut producer, mut consumer) = moq_transfork::Track::new("demo/video").produce();
+		producer.create_group(0);
+
+		// A group far in the future gives up instead of waiting forever.
+		let start = tokio::time::Instant::now();
+		let group = fetch_group(&cache, "demo/video", Some(&mut consumer), 1000)
+			.await
+			.unwrap();
+		assert!(group.is_none());
+		assert_eq!(start.elapsed(), FETCH_TIMEOUT);
+
+		// A group that arrives in time is returned.
+		let fetch = fetch_group(&cache, "demo/video", Some(&mut consumer), 1);
+		producer.create_group(1);
+		assert_eq!(fetch.await.unwrap().unwrap().sequence, 1);
 	}
 }
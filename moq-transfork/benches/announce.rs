@@ -0,0 +1,127 @@
+use std::{sync::Arc, time};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::executor::LocalPool;
+use moq_transfork::{Announced, AnnouncedProducer, Filter, Session};
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+
+// Returns a server and client endpoint using the async-std runtime.
+fn endpoints() -> (quinn::Endpoint, quinn::Endpoint) {
+	let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+	let chain = vec![cert.cert.der().clone()];
+	let key = rustls::pki_types::PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+
+	let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+	let mut server = rustls::ServerConfig::builder_with_provider(provider.clone())
+		.with_protocol_versions(&[&rustls::version::TLS13])
+		.unwrap()
+		.with_no_client_auth()
+		.with_single_cert(chain.clone(), key.into())
+		.unwrap();
+	server.alpn_protocols = vec![moq_transfork::ALPN.to_vec()];
+	let server_config = quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(server).unwrap()));
+
+	let mut roots = rustls::RootCertStore::empty();
+	roots.add(chain[0].clone()).unwrap();
+
+	let mut client = rustls::ClientConfig::builder_with_provider(provider)
+		.with_protocol_versions(&[&rustls::version::TLS13])
+		.unwrap()
+		.with_root_certificates(roots)
+		.with_no_client_auth();
+	client.alpn_protocols = vec![moq_transfork::ALPN.to_vec()];
+	let client_config = quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(client).unwrap()));
+
+	let runtime = Arc::new(quinn::AsyncStdRuntime);
+
+	let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+	let server = quinn::Endpoint::new(Default::default(), Some(server_config), socket, runtime.clone()).unwrap();
+
+	let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+	let mut client = quinn::Endpoint::new(Default::default(), None, socket, runtime).unwrap();
+	client.set_default_client_config(client_config);
+
+	(server, client)
+}
+
+// Open a new announce stream, returning the number of active paths once caught up.
+async fn replay(client: &Session) -> usize {
+	let mut consumer = client.announced(Filter::Any);
+	let mut active = 0;
+
+	while let Some(announced) = consumer.next().await {
+		match announced {
+			Announced::Active(_) => active += 1,
+			Announced::Live => break,
+			Announced::Ended(_) => unreachable!(),
+		}
+	}
+
+	active
+}
+
+// Replays a backlog of announcements over a real stream, so every message is decoded by the stream reader.
+// The time per announcement should stay constant as the backlog grows.
+fn backlog(c: &mut Criterion) {
+	let mut pool = LocalPool::new();
+	moq_async::set_spawner(pool.spawner());
+
+	let (server, client) = endpoints();
+	let addr = server.local_addr().unwrap();
+
+	let (mut server, client) = pool.run_until(async move {
+		let (server, client) = futures::join!(async { server.accept().await.unwrap().await.unwrap() }, async {
+			client.connect(addr, "localhost").unwrap().await.unwrap()
+		});
+
+		let (server, client) = futures::join!(
+			Session::accept(web_transport::quinn::Session::from(server)),
+			Session::connect(web_transport::quinn::Session::from(client)),
+		);
+
+		(server.unwrap(), client.unwrap())
+	});
+
+	let mut group = c.benchmark_group("announce backlog");
+	group.sample_size(10);
+
+	let mut announced = AnnouncedProducer::new();
+	announced.live();
+	server.announce(announced.subscribe(Filter::Any));
+
+	let mut count = 0;
+
+	for size in [1_000, 10_000, 50_000] {
+		while count < size {
+			announced.announce(format!("room/{}/broadcast/{}.karp", count / 16, count));
+			count += 1;
+		}
+
+		// Wait until the server has processed the new announcements, as they're forwarded by a background task.
+		while pool.run_until(replay(&client)) < size {}
+
+		group.throughput(Throughput::Elements(size as u64));
+		group.bench_function(BenchmarkId::from_parameter(size), |b| {
+			b.iter_custom(|iters| {
+				let mut elapsed = time::Duration::ZERO;
+
+				for _ in 0..iters {
+					let start = time::Instant::now();
+					let active = pool.run_until(replay(&client));
+					elapsed += start.elapsed();
+
+					assert_eq!(active, size);
+				}
+
+				elapsed
+			})
+		});
+	}
+
+	group.finish();
+	moq_async::reset_spawner();
+}
+
+criterion_group!(benches, backlog);
+criterion_main!(benches);
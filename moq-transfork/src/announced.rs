@@ -1,11 +1,24 @@
 use moq_async::{Lock, LockWeak};
 use std::{
-	collections::{BTreeSet, VecDeque},
+	collections::{hash_map, BTreeSet, HashMap, HashSet, VecDeque},
 	fmt,
+	future::poll_fn,
+	hash::{BuildHasher, RandomState},
+	pin::Pin,
+	sync::atomic::{AtomicU64, Ordering},
+	task::{ready, Context, Poll},
 };
 use tokio::sync::mpsc;
 
-pub use moq_proto::message::{Filter, FilterMatch};
+use moq_proto::coding::Str;
+pub use moq_proto::message::{AnnounceCursor, AnnounceEpoch, Filter, FilterMatch};
+
+// The number of changes remembered by each producer, used to resume announce streams.
+// Subscribers that fall further behind will receive a full replay instead.
+const HISTORY: usize = 1024;
+
+// Used to uniquely identify each source, so it can be removed when it ends.
+static NEXT_SOURCE: AtomicU64 = AtomicU64::new(0);
 
 /// The suffix of each announced track.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -44,14 +57,21 @@ impl Announced {
 	}
 }
 
-// An owned version of FilterMatch
+// An owned version of FilterMatch, sharing the path with the producer.
 #[derive(Clone, PartialEq, Eq)]
 pub struct AnnouncedMatch {
-	full: String,
+	full: Str,
 	capture: (usize, usize),
 }
 
 impl AnnouncedMatch {
+	fn new(full: &Str, m: FilterMatch) -> Self {
+		Self {
+			full: full.clone(),
+			capture: m.capture_index(),
+		}
+	}
+
 	pub fn full(&self) -> &str {
 		&self.full
 	}
@@ -61,19 +81,23 @@ impl AnnouncedMatch {
 	}
 
 	pub fn to_full(self) -> String {
-		self.full
+		self.full.into()
+	}
+
+	pub fn to_capture(self) -> String {
+		self.capture().to_string()
 	}
 
-	pub fn to_capture(mut self) -> String {
-		self.full.truncate(self.capture.1);
-		self.full.split_off(self.capture.0)
+	// Returns the capture without making a copy.
+	pub(crate) fn capture_str(&self) -> Str {
+		self.full.slice_ref(self.capture())
 	}
 }
 
 impl From<FilterMatch<'_>> for AnnouncedMatch {
 	fn from(value: FilterMatch) -> Self {
 		AnnouncedMatch {
-			full: value.full().to_string(),
+			full: value.full().into(),
 			capture: value.capture_index(),
 		}
 	}
@@ -88,24 +112,54 @@ impl fmt::Debug for AnnouncedMatch {
 	}
 }
 
-#[derive(Default)]
 struct ProducerState {
-	active: BTreeSet<String>,
+	active: BTreeSet<Str>,
 	consumers: Vec<(Lock<ConsumerState>, mpsc::Sender<()>)>,
 	live: bool,
+
+	// A random ID and the number of changes made, used as a resumption cursor.
+	epoch: u64,
+	sequence: u64,
+
+	// The most recent changes, in order, and whether the path became active.
+	history: VecDeque<(Str, bool)>,
+
+	// The cursor of the remote announce stream being mirrored, if any.
+	remote: Option<AnnounceCursor>,
+}
+
+impl Default for ProducerState {
+	fn default() -> Self {
+		// Must fit in a VarInt and be non-zero.
+		let epoch = (RandomState::new().hash_one(0u64) >> 2).max(1);
+
+		Self {
+			active: Default::default(),
+			consumers: Default::default(),
+			live: false,
+			epoch,
+			sequence: 0,
+			history: Default::default(),
+			remote: None,
+		}
+	}
 }
 
 impl ProducerState {
-	fn insert(&mut self, path: String) -> bool {
+	fn insert(&mut self, path: Str) -> bool {
 		if !self.active.insert(path.clone()) {
 			return false;
 		}
 
+		self.record(&path, true);
+
 		let mut i = 0;
 
 		while let Some((consumer, notify)) = self.consumers.get(i) {
 			if !notify.is_closed() {
-				consumer.lock().insert(&path);
+				let mut consumer = consumer.lock();
+				consumer.insert(&path);
+				consumer.advance(self.sequence);
 				notify.try_send(()).ok();
 				i += 1;
 			} else {
@@ -117,15 +171,20 @@ impl ProducerState {
 	}
 
 	fn remove(&mut self, path: &str) -> bool {
-		if !self.active.remove(path) {
-			return false;
-		}
+		let path = match self.active.take(path) {
+			Some(path) => path,
+			None => return false,
+		};
+
+		self.record(&path, false);
 
 		let mut i = 0;
 
 		while let Some((consumer, notify)) = self.consumers.get(i) {
 			if !notify.is_closed() {
-				consumer.lock().remove(path);
+				let mut consumer = consumer.lock();
+				consumer.remove(&path);
+				consumer.advance(self.sequence);
 				notify.try_send(()).ok();
 				i += 1;
 			} else {
@@ -136,6 +195,24 @@ impl ProducerState {
 		true
 	}
 
+	fn record(&mut self, path: &Str, active: bool) {
+		self.sequence += 1;
+		self.remote = None;
+
+		if self.history.len() >= HISTORY {
+			self.history.pop_front();
+		}
+		self.history.push_back((path.clone(), active));
+	}
+
+	fn set_remote(&mut self, cursor: AnnounceCursor) {
+		self.remote = Some(cursor.clone());
+
+		for (consumer, _) in &self.consumers {
+			consumer.lock().remote = Some(cursor.clone());
+		}
+	}
+
 	fn live(&mut self) -> bool {
 		if self.live {
 			return false;
@@ -162,7 +239,7 @@ impl ProducerState {
 
 		for active in &self.active {
 			if let Some(m) = filter.matches(active) {
-				added.push_back(m.into());
+				added.push_back(AnnouncedMatch::new(active, m));
 			}
 		}
 
@@ -171,7 +248,59 @@ impl ProducerState {
 			removed: VecDeque::new(),
 			filter,
 			live: self.live,
+			epoch: self.epoch,
+			sequence: self.sequence,
+			remote: self.remote.clone(),
+		}
+	}
+
+	// Returns a consumer that starts at the given sequence number, if it's still in the history.
+	// The net changes since then are returned instead of queued so they can be merged by the caller.
+	fn resume(&mut self, filter: Filter, sequence: u64) -> Option<(ConsumerState, Vec<(AnnouncedMatch, bool)>)> {
+		let oldest = self.sequence.checked_sub(self.history.len() as u64)?;
+		if sequence < oldest || sequence > self.sequence {
+			return None;
 		}
+
+		// Skip any changes the subscriber has already seen.
+		let skip = (sequence - oldest) as usize;
+
+		// The first change to each path tells us if it was active at the given sequence.
+		let mut changes = Vec::new();
+		let mut seen = HashMap::new();
+
+		for (path, active) in self.history.iter().skip(skip) {
+			if let hash_map::Entry::Vacant(entry) = seen.entry(path.as_str()) {
+				entry.insert(!active);
+				changes.push(path);
+			}
+		}
+
+		let changes = changes
+			.into_iter()
+			.filter_map(|path| {
+				let active = self.active.contains(path);
+				if seen[path.as_str()] == active {
+					// Flickered back to the original state.
+					return None;
+				}
+
+				let m = filter.matches(path)?;
+				Some((AnnouncedMatch::new(path, m), active))
+			})
+			.collect();
+
+		let consumer = ConsumerState {
+			added: VecDeque::new(),
+			removed: VecDeque::new(),
+			filter,
+			live: self.live,
+			epoch: self.epoch,
+			sequence: self.sequence,
+			remote: self.remote.clone(),
+		};
+
+		Some((consumer, changes))
 	}
 
 	fn subscribe(&mut self, consumer: Lock<ConsumerState>) -> mpsc::Receiver<()> {
@@ -200,12 +329,19 @@ struct ConsumerState {
 	added: VecDeque<AnnouncedMatch>,
 	removed: VecDeque<AnnouncedMatch>,
 	live: bool,
+
+	// The producer's epoch and sequence number as of the most recent change.
+	epoch: u64,
+	sequence: u64,
+
+	// The cursor of the remote announce stream, if mirrored.
+	remote: Option<AnnounceCursor>,
 }
 
 impl ConsumerState {
-	pub fn insert(&mut self, path: &str) {
-		let added: AnnouncedMatch = match self.filter.matches(path) {
-			Some(m) => m.into(),
+	pub fn insert(&mut self, path: &Str) {
+		let added = match self.filter.matches(path) {
+			Some(m) => AnnouncedMatch::new(path, m),
 			None => return,
 		};
 
@@ -222,9 +358,9 @@ impl ConsumerState {
 		}
 	}
 
-	pub fn remove(&mut self, path: &str) {
-		let removed: AnnouncedMatch = match self.filter.matches(path) {
-			Some(m) => m.into(),
+	pub fn remove(&mut self, path: &Str) {
+		let removed = match self.filter.matches(path) {
+			Some(m) => AnnouncedMatch::new(path, m),
 			None => return,
 		};
 
@@ -241,6 +377,16 @@ impl ConsumerState {
 		self.live = true;
 	}
 
+	pub fn advance(&mut self, sequence: u64) {
+		self.sequence = sequence;
+		self.remote = None;
+	}
+
+	// Returns true if there are no queued announcements.
+	pub fn is_caught_up(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty()
+	}
+
 	pub fn reset(&mut self) {
 		self.added.clear();
 		self.removed.clear();
@@ -262,7 +408,7 @@ impl AnnouncedProducer {
 
 	/// Announce a track, returning true if it's new.
 	pub fn announce<T: ToString>(&mut self, path: T) -> bool {
-		let path = path.to_string();
+		let path = path.to_string().into();
 		let mut state = self.state.lock();
 		state.insert(path)
 	}
@@ -289,6 +435,42 @@ impl AnnouncedProducer {
 		state.live()
 	}
 
+	/// The cursor of the remote announce stream mirrored into this producer, if any.
+	///
+	/// This is cleared whenever the producer is modified, until the remote sends an updated cursor.
+	pub fn cursor(&self) -> Option<AnnounceCursor> {
+		self.state.lock().remote.clone()
+	}
+
+	pub(crate) fn set_cursor(&mut self, cursor: AnnounceCursor) {
+		self.state.lock().set_remote(cursor);
+	}
+
+	/// Stop announcing any tracks matching the filter that fail the predicate.
+	pub(crate) fn retain<F: FnMut(&str) -> bool>(&mut self, filter: &Filter, mut f: F) {
+		let mut state = self.state.lock();
+
+		let stale: Vec<Str> = state
+			.active
+			.iter()
+			.filter(|path| filter.matches(path).is_some() && !f(path))
+			.cloned()
+			.collect();
+
+		for path in stale {
+			state.remove(&path);
+		}
+	}
+
+	/// Serve these announcements directly, without making a copy.
+	pub(crate) fn source(&self) -> AnnouncedSource {
+		AnnouncedSource {
+			id: NEXT_SOURCE.fetch_add(1, Ordering::Relaxed),
+			producer: self.state.downgrade(),
+			scope: Filter::Any,
+		}
+	}
+
 	/// Subscribe to all announced tracks matching the (wildcard) filter.
 	pub fn subscribe(&self, filter: Filter) -> AnnouncedConsumer {
 		let mut state = self.state.lock();
@@ -360,27 +542,316 @@ impl AnnouncedConsumer {
 
 	/// Returns the next announced track.
 	pub async fn next(&mut self) -> Option<Announced> {
+		poll_fn(|cx| self.poll_next(cx)).await
+	}
+
+	/// Polls for the next announced track.
+	pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Announced>> {
 		loop {
 			{
 				let mut state = self.state.lock();
 
 				if let Some(removed) = state.removed.pop_front() {
-					return Some(Announced::Ended(removed));
+					return Poll::Ready(Some(Announced::Ended(removed)));
 				}
 
 				if let Some(added) = state.added.pop_front() {
-					return Some(Announced::Active(added));
+					return Poll::Ready(Some(Announced::Active(added)));
 				}
 
 				if !self.live && state.live {
 					self.live = true;
-					return Some(Announced::Live);
+					return Poll::Ready(Some(Announced::Live));
 				}
 			}
 
-			self.notify.recv().await?;
+			if ready!(self.notify.poll_recv(cx)).is_none() {
+				return Poll::Ready(None);
+			}
 		}
 	}
+
+	/// The cursor of the remote announce stream, if every announcement up to it has been returned.
+	///
+	/// Provide the same [AnnouncedProducer] to [crate::Session::announced_into] after a reconnect to resume from this cursor.
+	pub fn cursor(&self) -> Option<AnnounceCursor> {
+		let state = self.state.lock();
+		match state.is_caught_up() {
+			true => state.remote.clone(),
+			false => None,
+		}
+	}
+
+	// Our position in the producer's history, if every announcement up to it has been returned.
+	fn position(&self) -> Option<AnnounceEpoch> {
+		let state = self.state.lock();
+		state.is_caught_up().then_some(AnnounceEpoch {
+			id: state.epoch,
+			sequence: state.sequence,
+		})
+	}
+
+	/// Serve the announcements of our producer directly, without making a copy.
+	pub(crate) fn source(&self) -> AnnouncedSource {
+		AnnouncedSource {
+			id: NEXT_SOURCE.fetch_add(1, Ordering::Relaxed),
+			producer: self.producer.clone(),
+			scope: self.state.lock().filter.clone(),
+		}
+	}
+}
+
+/// A handle to an [AnnouncedProducer] matching a filter, used to serve announce streams.
+#[derive(Clone)]
+pub(crate) struct AnnouncedSource {
+	id: u64,
+	producer: LockWeak<ProducerState>,
+
+	// Only paths matching this filter are served.
+	scope: Filter,
+}
+
+impl AnnouncedSource {
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
+	fn epoch(&self) -> Option<u64> {
+		Some(self.producer.upgrade()?.lock().epoch)
+	}
+
+	fn subscribe(&self, filter: Filter) -> Option<AnnouncedConsumer> {
+		let producer = self.producer.upgrade()?;
+		let mut state = producer.lock();
+
+		let consumer = Lock::new(state.consumer(filter));
+		let notify = state.subscribe(consumer.clone());
+		Some(AnnouncedConsumer::new(self.producer.clone(), consumer, notify))
+	}
+
+	fn resume(&self, filter: Filter, sequence: u64) -> Option<(AnnouncedConsumer, Vec<(AnnouncedMatch, bool)>)> {
+		let producer = self.producer.upgrade()?;
+		let mut state = producer.lock();
+
+		let (consumer, changes) = state.resume(filter, sequence)?;
+		let consumer = Lock::new(consumer);
+		let notify = state.subscribe(consumer.clone());
+		let consumer = AnnouncedConsumer::new(self.producer.clone(), consumer, notify);
+
+		Some((consumer, changes))
+	}
+
+	// Returns every active path matching the filter.
+	fn active(&self, filter: &Filter) -> Vec<Str> {
+		let producer = match self.producer.upgrade() {
+			Some(producer) => producer,
+			None => return Vec::new(),
+		};
+
+		let state = producer.lock();
+		state
+			.active
+			.iter()
+			.filter(|path| filter.matches(path).is_some() && self.scope.matches(path).is_some())
+			.cloned()
+			.collect()
+	}
+}
+
+/// Merges the announcements from multiple sources, deduplicating any paths.
+///
+/// Used to serve an announce stream without copying each source into a per-session producer.
+pub(crate) struct AnnouncedMerge {
+	filter: Filter,
+
+	// The consumer for each source, in order, with the scope of the source.
+	consumers: Vec<(Filter, AnnouncedConsumer)>,
+
+	// The number of sources announcing each path.
+	counts: HashMap<Str, usize>,
+
+	// The IDs of the sources that have been merged.
+	merged: HashSet<u64>,
+
+	// Changes to flush before reading from any consumers.
+	pending: VecDeque<Announced>,
+}
+
+impl AnnouncedMerge {
+	pub fn new(filter: Filter) -> Self {
+		Self {
+			filter,
+			consumers: Vec::new(),
+			counts: HashMap::new(),
+			merged: HashSet::new(),
+			pending: VecDeque::new(),
+		}
+	}
+
+	/// Merge any new sources, replaying all of their active paths.
+	///
+	/// Sources that were removed are forgotten; their consumers end on their own once the producer is dropped.
+	pub fn extend(&mut self, sources: &[AnnouncedSource]) {
+		for source in sources {
+			if !self.merged.insert(source.id) {
+				continue;
+			}
+
+			if let Some(consumer) = source.subscribe(self.filter.clone()) {
+				self.consumers.push((source.scope.clone(), consumer));
+			}
+		}
+
+		self.merged.retain(|id| sources.iter().any(|source| source.id == *id));
+	}
+
+	/// Merge the sources, only returning changes since the cursor.
+	///
+	/// Returns false without merging anything if any source in the cursor is unknown or too far behind.
+	pub fn resume(&mut self, sources: &[AnnouncedSource], cursor: &AnnounceCursor) -> bool {
+		let epochs: Vec<_> = sources.iter().map(AnnouncedSource::epoch).collect();
+
+		// Every source in the cursor must still exist, otherwise we can't unannounce its paths.
+		if !cursor.0.iter().all(|e| epochs.contains(&Some(e.id))) {
+			return false;
+		}
+
+		let mut consumers = Vec::with_capacity(sources.len());
+		let mut changes = Vec::new();
+
+		for (source, epoch) in sources.iter().zip(epochs) {
+			let sequence = epoch.and_then(|epoch| cursor.get(epoch));
+
+			match sequence {
+				Some(sequence) => {
+					let (consumer, diff) = match source.resume(self.filter.clone(), sequence) {
+						Some(res) => res,
+						None => return false,
+					};
+
+					consumers.push((source.scope.clone(), consumer));
+					changes.extend(
+						diff.into_iter()
+							.filter(|(m, _)| source.scope.matches(m.full()).is_some()),
+					);
+				}
+				// A new source; the subscriber hasn't seen any of its paths.
+				None => {
+					if let Some(consumer) = source.subscribe(self.filter.clone()) {
+						consumers.push((source.scope.clone(), consumer));
+					}
+				}
+			}
+		}
+
+		// Count the paths that are currently active, as if they were all announced.
+		for (source, (_, consumer)) in sources.iter().zip(consumers.iter()) {
+			let replayed: BTreeSet<_> = consumer.state.lock().added.iter().map(|m| m.full.clone()).collect();
+
+			for path in source.active(&self.filter) {
+				if !replayed.contains(&path) {
+					*self.counts.entry(path).or_default() += 1;
+				}
+			}
+		}
+
+		for (m, active) in changes {
+			match active {
+				true => self.pending.push_back(Announced::Active(m)),
+				// Another source might still be announcing the path.
+				false if !self.counts.contains_key(m.full()) => self.pending.push_back(Announced::Ended(m)),
+				false => {}
+			}
+		}
+
+		self.consumers = consumers;
+		self.merged = sources.iter().map(|source| source.id).collect();
+
+		true
+	}
+
+	/// Returns the next change, or None if there are no more sources.
+	pub async fn next(&mut self) -> Option<Announced> {
+		poll_fn(|cx| self.poll_next(cx)).await
+	}
+
+	fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Announced>> {
+		if let Some(announced) = self.pending.pop_front() {
+			return Poll::Ready(Some(announced));
+		}
+
+		let mut i = 0;
+
+		while let Some((scope, consumer)) = self.consumers.get_mut(i) {
+			let announced = match consumer.poll_next(cx) {
+				Poll::Ready(Some(announced)) => announced,
+				Poll::Ready(None) => {
+					self.consumers.remove(i);
+					continue;
+				}
+				Poll::Pending => {
+					i += 1;
+					continue;
+				}
+			};
+
+			match announced {
+				Announced::Active(m) if scope.matches(m.full()).is_some() => {
+					let count = self.counts.entry(m.full.clone()).or_default();
+					*count += 1;
+
+					if *count == 1 {
+						return Poll::Ready(Some(Announced::Active(m)));
+					}
+				}
+				Announced::Ended(m) if scope.matches(m.full()).is_some() => {
+					if let hash_map::Entry::Occupied(mut entry) = self.counts.entry(m.full.clone()) {
+						*entry.get_mut() -= 1;
+
+						if *entry.get() == 0 {
+							entry.remove();
+							return Poll::Ready(Some(Announced::Ended(m)));
+						}
+					}
+				}
+				// The caller decides when we're live.
+				_ => {}
+			}
+		}
+
+		match self.consumers.is_empty() {
+			true => Poll::Ready(None),
+			false => Poll::Pending,
+		}
+	}
+
+	/// The combined cursor of every source, if all of their announcements have been returned.
+	pub fn cursor(&self) -> Option<AnnounceCursor> {
+		if !self.pending.is_empty() {
+			return None;
+		}
+
+		let mut epochs = Vec::with_capacity(self.consumers.len());
+
+		for (_, consumer) in &self.consumers {
+			let epoch = consumer.position()?;
+
+			// Skip sources that have never changed, so short-lived sources don't prevent resumption.
+			if epoch.sequence > 0 {
+				epochs.push(epoch);
+			}
+		}
+
+		Some(AnnounceCursor(epochs))
+	}
+}
+
+impl futures::Stream for AnnouncedConsumer {
+	type Item = Announced;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		AnnouncedConsumer::poll_next(self.get_mut(), cx)
+	}
 }
 
 // ugh
@@ -618,4 +1089,120 @@ mod test {
 		consumer.next().await.unwrap().assert_ended("a/c");
 		assert_eq!(consumer.next().await, None);
 	}
+
+	fn assert_next(merged: &mut AnnouncedMerge) -> Announced {
+		merged
+			.next()
+			.now_or_never()
+			.expect("would have blocked")
+			.expect("no next announcement")
+	}
+
+	#[test]
+	fn resume() {
+		let mut producer = AnnouncedProducer::new();
+		producer.announce("a");
+		producer.announce("b");
+
+		let mut merged = AnnouncedMerge::new(Filter::Any);
+		merged.extend(&[producer.source()]);
+		assert_next(&mut merged).assert_active("a");
+		assert_next(&mut merged).assert_active("b");
+
+		let cursor = merged.cursor().expect("caught up");
+		assert_eq!(cursor.0.len(), 1);
+		assert_eq!(cursor.0[0].sequence, 2);
+
+		producer.unannounce("a");
+		producer.announce("c");
+		producer.announce("d");
+		producer.unannounce("d");
+		producer.unannounce("b");
+		producer.announce("b");
+
+		// Only the net changes are returned.
+		let mut resumed = AnnouncedMerge::new(Filter::Any);
+		assert!(resumed.resume(&[producer.source()], &cursor));
+		assert_next(&mut resumed).assert_ended("a");
+		assert_next(&mut resumed).assert_active("c");
+		assert_eq!(resumed.next().now_or_never(), None);
+
+		// An unknown epoch can't be resumed.
+		let other = AnnouncedProducer::new();
+		let mut resumed = AnnouncedMerge::new(Filter::Any);
+		assert!(!resumed.resume(&[other.source()], &cursor));
+	}
+
+	#[test]
+	fn resume_expired() {
+		let mut producer = AnnouncedProducer::new();
+		producer.announce("a");
+
+		let mut merged = AnnouncedMerge::new(Filter::Any);
+		merged.extend(&[producer.source()]);
+		assert_next(&mut merged).assert_active("a");
+		let cursor = merged.cursor().expect("caught up");
+
+		for i in 0..=HISTORY {
+			producer.announce(format!("b/{}", i));
+		}
+
+		// The cursor has fallen out of the history.
+		let mut resumed = AnnouncedMerge::new(Filter::Any);
+		assert!(!resumed.resume(&[producer.source()], &cursor));
+	}
+
+	#[test]
+	fn merge() {
+		let mut producer1 = AnnouncedProducer::new();
+		let mut producer2 = AnnouncedProducer::new();
+
+		let mut merged = AnnouncedMerge::new(Filter::Any);
+		merged.extend(&[producer1.source(), producer2.source()]);
+		assert_eq!(merged.cursor(), Some(AnnounceCursor::default()));
+
+		producer1.announce("a");
+		producer2.announce("a");
+		assert_next(&mut merged).assert_active("a");
+		assert_eq!(merged.next().now_or_never(), None);
+
+		producer1.unannounce("a");
+		assert_eq!(merged.next().now_or_never(), None);
+		assert_eq!(merged.cursor().expect("caught up").0.len(), 2);
+
+		drop(producer2);
+		assert_next(&mut merged).assert_ended("a");
+	}
+
+	#[test]
+	fn merge_extend() {
+		let mut producer1 = AnnouncedProducer::new();
+		let mut producer2 = AnnouncedProducer::new();
+		producer1.announce("a");
+		producer2.announce("b");
+
+		let source1 = producer1.source();
+		let mut merged = AnnouncedMerge::new(Filter::Any);
+		merged.extend(&[source1.clone(), producer2.source()]);
+		assert_next(&mut merged).assert_active("a");
+		assert_next(&mut merged).assert_active("b");
+
+		// Existing sources aren't replayed again, and removed sources are forgotten.
+		let mut producer3 = AnnouncedProducer::new();
+		producer3.announce("c");
+		merged.extend(&[source1.clone(), producer3.source()]);
+		assert_next(&mut merged).assert_active("c");
+		assert_eq!(merged.next().now_or_never(), None);
+		assert_eq!(merged.merged.len(), 2);
+		assert_eq!(merged.consumers.len(), 3);
+
+		// The removed source still ends its paths once dropped.
+		drop(producer2);
+		assert_next(&mut merged).assert_ended("b");
+
+		// A new source for the same producer is merged again, but its paths are counted rather than announced twice.
+		merged.extend(&[source1, producer1.source()]);
+		assert_eq!(merged.next().now_or_never(), None);
+		assert_eq!(merged.counts.get("a"), Some(&2));
+	}
 }
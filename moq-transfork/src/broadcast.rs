@@ -0,0 +1,208 @@
+//! A broadcast is a collection of tracks sharing a path prefix, split into a [BroadcastProducer] and [BroadcastConsumer] handle.
+//!
+//! A [BroadcastProducer] creates tracks relative to the broadcast path, announcing each of them.
+//! Every track is unannounced when the producer (and any clones) are dropped.
+//!
+//! A [BroadcastConsumer] subscribes to tracks relative to the broadcast path.
+//! It's backed by either a local [BroadcastProducer] or a remote [Session].
+
+use std::collections::HashMap;
+
+use crate::{AnnouncedConsumer, AnnouncedProducer, Error, Filter, Session, Track, TrackConsumer, TrackProducer};
+
+use moq_async::{Lock, LockWeak};
+
+/// A broadcast, a collection of tracks under a shared path prefix.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Broadcast {
+	/// The path of the broadcast, prepended to each track path with a `/` separator.
+	pub path: String,
+}
+
+impl Broadcast {
+	pub fn new<S: ToString>(path: S) -> Self {
+		Self { path: path.to_string() }
+	}
+
+	/// Returns the full path of a track within the broadcast.
+	pub fn track_path(&self, name: &str) -> String {
+		format!("{}/{}", self.path, name)
+	}
+
+	/// Returns the path of a track relative to the broadcast, or None if it's not within the broadcast.
+	pub fn track_name<'a>(&self, path: &'a str) -> Option<&'a str> {
+		path.strip_prefix(self.path.as_str())?.strip_prefix('/')
+	}
+
+	pub fn produce(self) -> (BroadcastProducer, BroadcastConsumer) {
+		// We start in live mode because we're producing the tracks.
+		let mut state = BroadcastState::default();
+		state.announced.live();
+		let state = Lock::new(state);
+
+		let consumer = BroadcastConsumer {
+			info: self.clone(),
+			source: BroadcastSource::Local(state.downgrade()),
+		};
+
+		let producer = BroadcastProducer { info: self, state };
+
+		(producer, consumer)
+	}
+}
+
+#[derive(Default)]
+struct BroadcastState {
+	// Indexed by the path relative to the broadcast.
+	tracks: HashMap<String, TrackConsumer>,
+
+	// Announces the full path of each track; unannounced when dropped.
+	announced: AnnouncedProducer,
+}
+
+/// Produces tracks within a broadcast.
+#[derive(Clone)]
+pub struct BroadcastProducer {
+	pub info: Broadcast,
+	state: Lock<BroadcastState>,
+}
+
+impl BroadcastProducer {
+	/// Create a track relative to the broadcast, replacing any existing track with the same path.
+	pub fn create_track(&mut self, track: Track) -> TrackProducer {
+		let name = track.path.clone();
+
+		let (producer, consumer) = Track {
+			path: self.info.track_path(&name),
+			..track
+		}
+		.produce();
+
+		let mut state = self.state.lock();
+		state.announced.announce(&consumer.path);
+
+		// Only the clones returned by subscribe count as demand.
+		state.tracks.insert(name, consumer.passive());
+
+		producer
+	}
+
+	/// Remove a track relative to the broadcast, returning true if it existed.
+	///
+	/// Existing subscriptions are unaffected; they end when the [TrackProducer] is dropped.
+	pub fn remove_track(&mut self, name: &str) -> bool {
+		let mut state = self.state.lock();
+
+		match state.tracks.remove(name) {
+			Some(track) => state.announced.unannounce(&track.path),
+			None => false,
+		}
+	}
+
+	/// Returns true if a track relative to the broadcast exists.
+	pub fn has_track(&self, name: &str) -> bool {
+		self.state.lock().tracks.contains_key(name)
+	}
+
+	pub fn consume(&self) -> BroadcastConsumer {
+		BroadcastConsumer {
+			info: self.info.clone(),
+			source: BroadcastSource::Local(self.state.downgrade()),
+		}
+	}
+}
+
+#[derive(Clone)]
+enum BroadcastSource {
+	Local(LockWeak<BroadcastState>),
+	Remote(Box<Session>),
+}
+
+/// Consumes tracks within a broadcast, either produced locally or over the network.
+#[derive(Clone)]
+pub struct BroadcastConsumer {
+	pub info: Broadcast,
+	source: BroadcastSource,
+}
+
+impl BroadcastConsumer {
+	pub(crate) fn remote(info: Broadcast, session: Session) -> Self {
+		Self {
+			info,
+			source: BroadcastSource::Remote(Box::new(session)),
+		}
+	}
+
+	/// Subscribe to a track relative to the broadcast.
+	///
+	/// A local broadcast returns [Error::NotFound] if the track doesn't exist, while a remote broadcast errors on the first read.
+	pub fn subscribe(&self, track: Track) -> Result<TrackConsumer, Error> {
+		match &self.source {
+			BroadcastSource::Local(state) => {
+				let state = state.upgrade().ok_or(Error::NotFound)?;
+				let state = state.lock();
+				state.tracks.get(&track.path).cloned().ok_or(Error::NotFound)
+			}
+			BroadcastSource::Remote(session) => Ok(session.subscribe(Track {
+				path: self.info.track_path(&track.path),
+				..track
+			})),
+		}
+	}
+
+	/// Discover the tracks within the broadcast.
+	///
+	/// Each announcement contains the full path; the capture is the path relative to the broadcast.
+	pub fn announced(&self) -> AnnouncedConsumer {
+		let filter = Filter::Prefix(self.info.track_path(""));
+
+		match &self.source {
+			BroadcastSource::Local(state) => match state.upgrade() {
+				Some(state) => state.lock().announced.subscribe(filter),
+				// The producer was dropped, so return a consumer that immediately ends.
+				None => AnnouncedProducer::new().subscribe(filter),
+			},
+			BroadcastSource::Remote(session) => session.announced(filter),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use futures::FutureExt;
+
+	#[test]
+	fn paths() {
+		let broadcast = Broadcast::new("demo/bbb");
+		assert_eq!(broadcast.track_path("video"), "demo/bbb/video");
+		assert_eq!(broadcast.track_name("demo/bbb/video"), Some("video"));
+		assert_eq!(broadcast.track_name("demo/bbbb/video"), None);
+		assert_eq!(broadcast.track_name("demo/bbb"), None);
+	}
+
+	#[test]
+	fn local() {
+		let (mut producer, consumer) = Broadcast::new("demo").produce();
+		let mut announced = consumer.announced();
+
+		let _video = producer.create_track(Track::new("video"));
+		assert_eq!(consumer.subscribe(Track::new("video")).unwrap().path, "demo/video");
+		assert!(matches!(consumer.subscribe(Track::new("audio")), Err(Error::NotFound)));
+
+		announced.next().now_or_never().unwrap().unwrap().assert_active("video");
+		announced.next().now_or_never().unwrap().unwrap().assert_live();
+
+		assert!(producer.remove_track("video"));
+		announced.next().now_or_never().unwrap().unwrap().assert_ended("video");
+
+		let _audio = producer.create_track(Track::new("audio"));
+		announced.next().now_or_never().unwrap().unwrap().assert_active("audio");
+
+		// Dropping the producer unannounces every track.
+		drop(producer);
+		announced.next().now_or_never().unwrap().unwrap().assert_ended("audio");
+		assert!(announced.next().now_or_never().unwrap().is_none());
+		assert!(matches!(consumer.subscribe(Track::new("audio")), Err(Error::NotFound)));
+	}
+}
@@ -0,0 +1,205 @@
+//! Record every message sent or received, for offline debugging.
+//!
+//! Install a [Capture] once at startup and every [Reader](crate::Reader) and [Writer](crate::Writer) will append a [Record] for each message.
+//! Frame payloads are not recorded, only the headers.
+//! The resulting file can be pretty-printed with `moq-dump capture <path>`.
+//!
+//! The file starts with [MAGIC], followed by a sequence of encoded [Record]s.
+use std::{
+	fs, io,
+	path::Path,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Mutex, OnceLock,
+	},
+	time::{Duration, SystemTime},
+};
+
+use bytes::{Bytes, BytesMut};
+use moq_proto::coding::*;
+
+/// The bytes at the start of every capture file.
+pub const MAGIC: &[u8; 8] = b"moqcap01";
+
+static CAPTURE: OnceLock<Capture> = OnceLock::new();
+static NEXT_STREAM: AtomicU64 = AtomicU64::new(0);
+
+/// A process-wide sink for [Record]s.
+pub struct Capture {
+	output: Mutex<Box<dyn io::Write + Send>>,
+}
+
+impl Capture {
+	/// Write records to the given output, starting with the [MAGIC] header.
+	pub fn new<W: io::Write + Send + 'static>(mut output: W) -> io::Result<Self> {
+		output.write_all(MAGIC)?;
+
+		Ok(Self {
+			output: Mutex::new(Box::new(output)),
+		})
+	}
+
+	/// Create (or truncate) a file and write records to it.
+	pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		Self::new(fs::File::create(path)?)
+	}
+
+	/// Start recording all streams, returning the capture back if one was already installed.
+	pub fn install(self) -> Result<(), Self> {
+		CAPTURE.set(self)
+	}
+
+	fn write(&self, record: &Record) {
+		let mut buf = BytesMut::new();
+		record.encode(&mut buf);
+
+		// Each record is written in one call so concurrent streams don't interleave.
+		let mut output = self.output.lock().unwrap();
+		if let Err(err) = output.write_all(&buf).and_then(|_| output.flush()) {
+			tracing::warn!(?err, "failed to write capture");
+		}
+	}
+}
+
+/// Returns a unique identifier for a new stream.
+///
+/// QUIC stream IDs are not exposed by WebTransport, so this is a process-wide counter instead.
+/// Both halves of a bidirectional stream share the same identifier.
+pub(crate) fn next_stream() -> u64 {
+	NEXT_STREAM.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Record a message if a [Capture] is installed.
+///
+/// The encoded bytes are computed lazily, so this is free when capturing is disabled.
+pub(crate) fn record<T, F>(stream: u64, direction: Direction, payload: F)
+where
+	F: FnOnce() -> Bytes,
+{
+	let Some(capture) = CAPTURE.get() else {
+		return;
+	};
+
+	let record = Record {
+		timestamp: SystemTime::now()
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.unwrap_or_default(),
+		stream,
+		direction,
+		name: name::<T>(),
+		payload: payload(),
+	};
+
+	capture.write(&record);
+}
+
+// A short, stable name for the message type, prefixed with `ietf::` for the compatibility messages.
+fn name<T>() -> String {
+	let path = std::any::type_name::<T>();
+	let short = path.rsplit("::").next().unwrap_or(path);
+
+	match path.contains("::ietf::") {
+		true => format!("ietf::{}", short),
+		false => short.to_string(),
+	}
+}
+
+/// Whether a message was sent or received.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+	Recv,
+	Send,
+}
+
+impl Decode for Direction {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		match u8::decode(r)? {
+			0 => Ok(Self::Recv),
+			1 => Ok(Self::Send),
+			_ => Err(DecodeError::InvalidValue),
+		}
+	}
+}
+
+impl Encode for Direction {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		match self {
+			Self::Recv => 0u8.encode(w),
+			Self::Send => 1u8.encode(w),
+		}
+	}
+}
+
+/// A single message captured from a stream.
+#[derive(Clone, Debug)]
+pub struct Record {
+	/// The time since the UNIX epoch, with microsecond precision.
+	pub timestamp: Duration,
+
+	/// The stream identifier, see [next_stream].
+	pub stream: u64,
+
+	pub direction: Direction,
+
+	/// The name of the message type, ex. `Subscribe` or `ietf::Message`.
+	pub name: String,
+
+	/// The encoded message.
+	pub payload: Bytes,
+}
+
+impl Decode for Record {
+	fn decode<R: bytes::Buf>(r: &mut R) -> Result<Self, DecodeError> {
+		Ok(Self {
+			timestamp: Duration::decode(r)?,
+			stream: u64::decode(r)?,
+			direction: Direction::decode(r)?,
+			name: String::decode(r)?,
+			payload: Bytes::decode(r)?,
+		})
+	}
+}
+
+impl Encode for Record {
+	fn encode<W: bytes::BufMut>(&self, w: &mut W) {
+		self.timestamp.encode(w);
+		self.stream.encode(w);
+		self.direction.encode(w);
+		self.name.encode(w);
+		self.payload.encode(w);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use moq_proto::{ietf, message};
+
+	#[test]
+	fn names() {
+		assert_eq!(name::<message::Subscribe>(), "Subscribe");
+		assert_eq!(name::<ietf::Message>(), "ietf::Message");
+		assert_eq!(name::<u64>(), "u64");
+	}
+
+	#[test]
+	fn roundtrip() {
+		let record = Record {
+			timestamp: Duration::from_micros(1_700_000_000_000_000),
+			stream: 3,
+			direction: Direction::Send,
+			name: "Frame".to_string(),
+			payload: Bytes::from_static(&[0x05]),
+		};
+
+		let mut buf = BytesMut::new();
+		record.encode(&mut buf);
+
+		let decoded = Record::decode(&mut buf.freeze()).unwrap();
+		assert_eq!(decoded.timestamp, record.timestamp);
+		assert_eq!(decoded.stream, 3);
+		assert_eq!(decoded.direction, Direction::Send);
+		assert_eq!(decoded.name, "Frame");
+		assert_eq!(decoded.payload, record.payload);
+	}
+}
@@ -0,0 +1,43 @@
+use std::{
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use futures::{future::BoxFuture, stream::BoxStream, Stream, StreamExt};
+
+use crate::Error;
+
+/// A [Stream] of items read from a consumer, ending after the first error.
+///
+/// Returned by [crate::TrackConsumer::into_stream], [crate::GroupConsumer::into_stream], and [crate::FrameConsumer::into_stream].
+pub struct ConsumerStream<T> {
+	inner: BoxStream<'static, Result<T, Error>>,
+}
+
+impl<T: Send + 'static> ConsumerStream<T> {
+	/// Call `next` with the state until it returns `None` or an error.
+	pub(crate) fn new<S, F>(state: S, next: F) -> Self
+	where
+		S: Send + 'static,
+		F: for<'a> Fn(&'a mut S) -> BoxFuture<'a, Result<Option<T>, Error>> + Send + 'static,
+	{
+		let inner = futures::stream::unfold(Some((state, next)), |args| async move {
+			let (mut state, next) = args?;
+			match next(&mut state).await {
+				Ok(Some(item)) => Some((Ok(item), Some((state, next)))),
+				Ok(None) => None,
+				Err(err) => Some((Err(err), None)),
+			}
+		});
+
+		Self { inner: inner.boxed() }
+	}
+}
+
+impl<T> Stream for ConsumerStream<T> {
+	type Item = Result<T, Error>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.inner.poll_next_unpin(cx)
+	}
+}
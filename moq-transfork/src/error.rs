@@ -46,6 +46,10 @@ pub enum Error {
 
 	#[error("protocol violation")]
 	ProtocolViolation,
+
+	/// The session is not permitted to access the path.
+	#[error("unauthorized")]
+	Unauthorized,
 }
 
 impl Error {
@@ -63,6 +67,7 @@ impl Error {
 			Self::NotFound => 13,
 			Self::WrongSize => 14,
 			Self::ProtocolViolation => 15,
+			Self::Unauthorized => 16,
 			Self::App(app) => *app + 64,
 		}
 	}
@@ -0,0 +1,60 @@
+use std::sync::{Arc, OnceLock};
+
+/// A notable occurrence within a session, reported to an [Observer].
+///
+/// Streams are identified by the same number used by [crate::capture].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+	/// A stream was opened, either by us (local) or the remote.
+	StreamOpened { stream: u64, kind: StreamKind, local: bool },
+
+	/// A stream reported by [Event::StreamOpened] was closed, either gracefully or with an error.
+	StreamClosed { stream: u64 },
+
+	/// A group was dropped instead of delivered, either by us (local) or the remote.
+	GroupDropped {
+		subscribe: u64,
+		sequence: u64,
+		count: u64,
+		code: u32,
+		local: bool,
+	},
+
+	/// The send priority of a stream was set.
+	PrioritySet { stream: u64, priority: i32 },
+}
+
+/// The purpose of a stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StreamKind {
+	Session,
+	Announce,
+	Subscribe,
+	Info,
+	Group { subscribe: u64, sequence: u64 },
+}
+
+/// Receives every [Event] for a session, see [crate::Session::observe].
+///
+/// This is called inline, so it should not block.
+pub trait Observer: Send + Sync {
+	fn event(&self, event: Event);
+}
+
+// Shared by every half of a session, so an observer can be installed after the session has started.
+#[derive(Clone, Default)]
+pub(crate) struct Events {
+	observer: Arc<OnceLock<Arc<dyn Observer>>>,
+}
+
+impl Events {
+	pub fn observe(&self, observer: Arc<dyn Observer>) -> bool {
+		self.observer.set(observer).is_ok()
+	}
+
+	pub fn emit(&self, event: Event) {
+		if let Some(observer) = self.observer.get() {
+			observer.event(event);
+		}
+	}
+}
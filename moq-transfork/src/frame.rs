@@ -1,8 +1,9 @@
 use bytes::{Bytes, BytesMut};
+use futures::FutureExt;
 use std::{fmt, ops};
 use tokio::sync::watch;
 
-use crate::Error;
+use crate::{ConsumerStream, Error};
 
 /// A frame of data with an upfront size.
 #[derive(Clone, PartialEq, Debug)]
@@ -163,6 +164,11 @@ impl FrameConsumer {
 			Err(_) => Ok(()),
 		}
 	}
+
+	/// Return a [futures::Stream] of chunks, see [Self::read].
+	pub fn into_stream(self) -> FrameStream {
+		FrameStream::new(self, |frame| frame.read().boxed())
+	}
 }
 
 impl ops::Deref for FrameConsumer {
@@ -172,3 +178,34 @@ impl ops::Deref for FrameConsumer {
 		&self.info
 	}
 }
+
+/// A [futures::Stream] of chunks in a frame, ending after the first error.
+pub type FrameStream = ConsumerStream<Bytes>;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use futures::StreamExt;
+
+	#[tokio::test]
+	async fn stream() {
+		let (mut producer, consumer) = Frame::new(11).produce();
+		producer.write("hello");
+		producer.write(" world");
+
+		let mut stream = consumer.into_stream();
+		assert_eq!(stream.next().await.unwrap().unwrap(), "hello");
+		assert_eq!(stream.next().await.unwrap().unwrap(), " world");
+
+		// The stream ends once the producer is dropped.
+		drop(producer);
+		assert!(stream.next().await.is_none());
+
+		// Otherwise it ends after the first error.
+		let (producer, consumer) = Frame::new(5).produce();
+		let mut stream = consumer.into_stream();
+		producer.close(Error::NotFound);
+		assert!(matches!(stream.next().await, Some(Err(Error::NotFound))));
+		assert!(stream.next().await.is_none());
+	}
+}
@@ -7,11 +7,19 @@
 //! The reader can be cloned, in which case each reader receives a copy of each frame. (fanout)
 //!
 //! The stream is closed with [ServeError::MoqError] when all writers or readers are dropped.
-use bytes::Bytes;
-use std::ops;
-use tokio::sync::watch;
-
-use crate::Error;
+use bytes::{Buf, Bytes};
+use futures::{FutureExt, StreamExt};
+use std::{
+	io, ops,
+	pin::Pin,
+	task::{ready, Context, Poll},
+};
+use tokio::{
+	io::{AsyncRead, ReadBuf},
+	sync::watch,
+};
+
+use crate::{ConsumerStream, Error};
 
 use super::{Frame, FrameConsumer, FrameProducer};
 
@@ -185,6 +193,18 @@ impl GroupConsumer {
 			Err(_) => Ok(()),
 		}
 	}
+
+	/// Return a [futures::Stream] of whole frames, see [Self::read_frame].
+	pub fn into_stream(self) -> GroupStream {
+		GroupStream::new(self, |group| group.read_frame().boxed())
+	}
+
+	/// Return an [AsyncRead] over the concatenated payload of every frame.
+	///
+	/// Frame boundaries are not preserved; each chunk is returned as soon as it's received.
+	pub fn into_reader(self) -> GroupReader {
+		GroupReader::new(self)
+	}
 }
 
 impl ops::Deref for GroupConsumer {
@@ -194,3 +214,89 @@ impl ops::Deref for GroupConsumer {
 		&self.info
 	}
 }
+
+/// A [futures::Stream] of whole frames in a group, ending after the first error.
+pub type GroupStream = ConsumerStream<Bytes>;
+
+/// An [AsyncRead] over the payload of a group, chunk-by-chunk.
+pub struct GroupReader {
+	chunks: ConsumerStream<Bytes>,
+
+	// The remainder of the current chunk.
+	buffer: Bytes,
+}
+
+impl GroupReader {
+	fn new(group: GroupConsumer) -> Self {
+		// The group and the frame currently being read, if any.
+		let state: (GroupConsumer, Option<FrameConsumer>) = (group, None);
+
+		let chunks = ConsumerStream::new(state, |(group, frame)| {
+			async move {
+				loop {
+					let current = match frame.as_mut() {
+						Some(current) => current,
+						None => match group.next_frame().await? {
+							Some(next) => frame.insert(next),
+							None => return Ok(None),
+						},
+					};
+
+					match current.read().await? {
+						Some(chunk) => return Ok(Some(chunk)),
+						None => *frame = None,
+					}
+				}
+			}
+			.boxed()
+		});
+
+		Self {
+			chunks,
+			buffer: Bytes::new(),
+		}
+	}
+}
+
+impl AsyncRead for GroupReader {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		while !self.buffer.has_remaining() {
+			self.buffer = match ready!(self.chunks.poll_next_unpin(cx)) {
+				Some(Ok(chunk)) => chunk,
+				Some(Err(err)) => return Poll::Ready(Err(io::Error::other(err))),
+				None => return Poll::Ready(Ok(())),
+			};
+		}
+
+		let size = buf.remaining().min(self.buffer.len());
+		buf.put_slice(&self.buffer.split_to(size));
+
+		Poll::Ready(Ok(()))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use tokio::io::AsyncReadExt;
+
+	#[tokio::test]
+	async fn reader() {
+		let (mut producer, consumer) = Group::new(0).produce();
+
+		let mut frame = producer.create_frame(5);
+		frame.write("he");
+		frame.write("llo");
+		drop(frame);
+		producer.write_frame(" world");
+		drop(producer);
+
+		let mut buf = String::new();
+		consumer.clone().into_reader().read_to_string(&mut buf).await.unwrap();
+		assert_eq!(buf, "hello world");
+
+		let frames: Vec<_> = consumer.into_stream().collect().await;
+		assert_eq!(frames.len(), 2);
+		assert_eq!(frames[0].as_ref().unwrap(), "hello");
+	}
+}
@@ -0,0 +1,67 @@
+//! Compatibility with the IETF [moq-transport](https://datatracker.ietf.org/doc/draft-ietf-moq-transport/) draft.
+//!
+//! [crate::Session::accept] detects a moq-transport client automatically, while a client opts in with [crate::Session::connect_version] and [VERSION].
+//! The same [crate::TrackProducer]/[crate::TrackConsumer] model is used, with some caveats:
+//!
+//! - A track path is split at the last `/` into the namespace and name, ex. `room/alice/video` is `room/alice` + `video`.
+//! - Only namespaces are announced, so [crate::Session::announced] will return namespaces and not track paths.
+//! - Each frame is an object, numbered from zero within each group.
+//! - Priorities are not sent over the wire, and subscriptions always start at the latest group.
+mod publisher;
+mod session;
+mod subscriber;
+
+pub(crate) use publisher::*;
+pub(crate) use session::*;
+pub(crate) use subscriber::*;
+
+pub use moq_proto::ietf::VERSION;
+
+/// The ALPN used when connecting to a moq-transport implementation via QUIC directly.
+pub const ALPN: &[u8] = b"moq-00";
+
+use moq_proto::ietf::Message;
+use tokio::sync::mpsc;
+
+// Queues messages for the control stream, which is shared by the publisher and subscriber.
+#[derive(Clone)]
+pub(crate) struct Control(mpsc::UnboundedSender<Message>);
+
+impl Control {
+	fn new() -> (Self, mpsc::UnboundedReceiver<Message>) {
+		let (send, recv) = mpsc::unbounded_channel();
+		(Self(send), recv)
+	}
+
+	fn send<M: Into<Message>>(&self, msg: M) {
+		// NOTE: This only fails if the session is closed.
+		self.0.send(msg.into()).ok();
+	}
+}
+
+// Split a track path into the namespace and name.
+fn split(path: &str) -> (&str, &str) {
+	path.rsplit_once('/').unwrap_or(("", path))
+}
+
+// Join a namespace and name into a track path.
+fn join(namespace: &str, name: &str) -> String {
+	match namespace {
+		"" => name.to_string(),
+		_ => format!("{}/{}", namespace, name),
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn path() {
+		assert_eq!(split("room/alice/video"), ("room/alice", "video"));
+		assert_eq!(split("video"), ("", "video"));
+
+		assert_eq!(join("room/alice", "video"), "room/alice/video");
+		assert_eq!(join("", "video"), "video");
+	}
+}
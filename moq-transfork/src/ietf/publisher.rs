@@ -0,0 +1,391 @@
+use std::collections::{hash_map, HashMap};
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use tokio::sync::oneshot;
+
+use super::{join, split, Control};
+use crate::{
+	Announced, AnnouncedConsumer, BroadcastConsumer, Error, Event, Events, GroupConsumer, RouterConsumer, StreamKind,
+	Track, TrackConsumer, Writer,
+};
+
+use moq_async::{spawn, Lock, OrClose};
+use moq_proto::ietf;
+
+#[derive(Clone)]
+pub(crate) struct Publisher {
+	session: web_transport::Session,
+	control: Control,
+
+	tracks: Lock<HashMap<String, TrackConsumer>>,
+	broadcasts: Lock<HashMap<String, BroadcastConsumer>>,
+	router: Lock<Option<RouterConsumer>>,
+
+	// The number of announced tracks within each namespace.
+	namespaces: Lock<HashMap<String, usize>>,
+
+	// Dropped to cancel the corresponding subscription.
+	subscribes: Lock<HashMap<u64, oneshot::Sender<()>>>,
+
+	events: Events,
+}
+
+impl Publisher {
+	pub fn new(session: web_transport::Session, control: Control, events: Events) -> Self {
+		Self {
+			session,
+			control,
+			events,
+			tracks: Default::default(),
+			broadcasts: Default::default(),
+			router: Default::default(),
+			namespaces: Default::default(),
+			subscribes: Default::default(),
+		}
+	}
+
+	/// Publish a track, announcing its namespace.
+	#[tracing::instrument("publish", skip_all, err, fields(?track))]
+	pub fn publish(&mut self, track: TrackConsumer) -> Result<(), Error> {
+		match self.tracks.lock().entry(track.path.clone()) {
+			hash_map::Entry::Occupied(_) => return Err(Error::Duplicate),
+			hash_map::Entry::Vacant(entry) => entry.insert(track.clone().passive()),
+		};
+
+		self.announce_path(&track.path);
+
+		// Only remote subscriptions count as demand.
+		let track = track.passive();
+
+		let mut this = self.clone();
+
+		spawn(async move {
+			tokio::select! {
+				_ = track.closed() => (),
+				_ = this.session.closed() => (),
+			}
+			this.tracks.lock().remove(&track.path);
+			this.unannounce_path(&track.path);
+		});
+
+		Ok(())
+	}
+
+	/// Announce the namespace of any tracks announced by the consumer.
+	pub fn announce(&mut self, mut upstream: AnnouncedConsumer) {
+		let mut this = self.clone();
+
+		spawn(async move {
+			loop {
+				tokio::select! {
+					res = upstream.next() => match res {
+						Some(Announced::Active(m)) => this.announce_path(m.full()),
+						Some(Announced::Ended(m)) => this.unannounce_path(m.full()),
+						Some(Announced::Live) => (),
+						None => return,
+					},
+					_ = this.session.closed() => return,
+				}
+			}
+		});
+	}
+
+	/// Publish a broadcast, announcing and serving each of its tracks.
+	pub fn publish_broadcast(&mut self, broadcast: BroadcastConsumer) -> Result<(), Error> {
+		match self.broadcasts.lock().entry(broadcast.info.path.clone()) {
+			hash_map::Entry::Occupied(_) => return Err(Error::Duplicate),
+			hash_map::Entry::Vacant(entry) => entry.insert(broadcast.clone()),
+		};
+
+		self.announce(broadcast.announced());
+
+		let this = self.clone();
+
+		spawn(async move {
+			// The announcements end when the broadcast producer is dropped.
+			let mut announced = broadcast.announced();
+
+			tokio::select! {
+				_ = async { while announced.next().await.is_some() {} } => (),
+				_ = this.session.closed() => (),
+			}
+
+			this.broadcasts.lock().remove(&broadcast.info.path);
+		});
+
+		Ok(())
+	}
+
+	pub fn route(&mut self, router: RouterConsumer) {
+		self.router.lock().replace(router);
+	}
+
+	fn announce_path(&mut self, path: &str) {
+		let (namespace, _) = split(path);
+
+		let mut namespaces = self.namespaces.lock();
+		let count = namespaces.entry(namespace.to_string()).or_default();
+		*count += 1;
+
+		if *count == 1 {
+			tracing::debug!(?namespace, "announce");
+
+			self.control.send(ietf::Announce {
+				namespace: namespace.into(),
+				params: Default::default(),
+			});
+		}
+	}
+
+	fn unannounce_path(&mut self, path: &str) {
+		let (namespace, _) = split(path);
+
+		let mut namespaces = self.namespaces.lock();
+		let count = match namespaces.get_mut(namespace) {
+			Some(count) => count,
+			None => return,
+		};
+
+		*count -= 1;
+
+		if *count == 0 {
+			tracing::debug!(?namespace, "unannounce");
+
+			namespaces.remove(namespace);
+			self.control.send(ietf::Unannounce {
+				namespace: namespace.into(),
+			});
+		}
+	}
+
+	pub fn recv_subscribe(&mut self, subscribe: ietf::Subscribe) -> Result<(), Error> {
+		let (cancel, cancelled) = oneshot::channel();
+
+		match self.subscribes.lock().entry(subscribe.id) {
+			hash_map::Entry::Occupied(_) => return Err(Error::Duplicate),
+			hash_map::Entry::Vacant(entry) => entry.insert(cancel),
+		};
+
+		let mut this = self.clone();
+
+		spawn(async move {
+			let id = subscribe.id;
+			this.serve_subscribe(subscribe, cancelled).await;
+			this.subscribes.lock().remove(&id);
+		});
+
+		Ok(())
+	}
+
+	pub fn recv_unsubscribe(&mut self, unsubscribe: ietf::Unsubscribe) {
+		// The subscription replies with a SUBSCRIBE_DONE when cancelled.
+		self.subscribes.lock().remove(&unsubscribe.id);
+	}
+
+	#[tracing::instrument("publishing", skip_all, fields(namespace = ?subscribe.namespace, name = ?subscribe.name, id = subscribe.id))]
+	async fn serve_subscribe(&mut self, subscribe: ietf::Subscribe, cancelled: oneshot::Receiver<()>) {
+		let track = Track::new(join(&subscribe.namespace, &subscribe.name));
+
+		let track = match self.get_track(track).await {
+			Ok(track) => track,
+			Err(err) => {
+				tracing::warn!(?err, "rejected");
+
+				self.control.send(ietf::SubscribeError {
+					id: subscribe.id,
+					code: err.to_code().into(),
+					reason: err.to_string(),
+					alias: subscribe.alias,
+				});
+
+				return;
+			}
+		};
+
+		let latest = track.latest_group();
+		let largest = track.get_group(latest).ok().map(|_| ietf::Location {
+			group: latest,
+			object: 0,
+		});
+
+		tracing::info!(?largest, "active");
+
+		self.control.send(ietf::SubscribeOk {
+			id: subscribe.id,
+			expires: 0,
+			largest,
+		});
+
+		let (code, reason) = match self.serve_groups(&subscribe, track, cancelled).await {
+			Ok(()) => (ietf::SubscribeDone::TRACK_ENDED, String::new()),
+			Err(Error::Cancel) => (ietf::SubscribeDone::UNSUBSCRIBED, String::new()),
+			Err(err) => (ietf::SubscribeDone::INTERNAL_ERROR, err.to_string()),
+		};
+
+		tracing::info!(?code, ?reason, "done");
+
+		self.control.send(ietf::SubscribeDone {
+			id: subscribe.id,
+			code,
+			reason,
+			last: None,
+		});
+	}
+
+	async fn serve_groups(
+		&mut self,
+		subscribe: &ietf::Subscribe,
+		mut track: TrackConsumer,
+		mut cancelled: oneshot::Receiver<()>,
+	) -> Result<(), Error> {
+		// We always start at the latest group, but we can at least honor the requested range.
+		let (start, end) = match subscribe.filter {
+			ietf::SubscribeFilter::AbsoluteStart(start) => (start.group, None),
+			ietf::SubscribeFilter::AbsoluteRange(start, end) => (start.group, Some(end.group)),
+			_ => (0, None),
+		};
+
+		let mut tasks = FuturesUnordered::new();
+
+		loop {
+			tokio::select! {
+				res = track.next_group() => {
+					let group = match res? {
+						Some(group) if group.sequence < start => continue,
+						Some(group) if end.is_some_and(|end| group.sequence > end) => break,
+						Some(group) => group,
+						None => break,
+					};
+
+					let session = self.session.clone();
+					let events = self.events.clone();
+					let priority = crate::Publisher::stream_priority(track.priority, track.order, group.sequence);
+
+					tasks.push(Self::serve_group(session, events, subscribe.id, subscribe.alias, priority, group));
+				},
+				Some(res) = tasks.next() => {
+					if let Err(err) = res {
+						tracing::warn!(?err, "dropped group");
+					}
+				},
+				_ = &mut cancelled => return Err(Error::Cancel),
+			}
+		}
+
+		// Finish any groups in progress before reporting the track has ended.
+		while let Some(res) = tasks.next().await {
+			if let Err(err) = res {
+				tracing::warn!(?err, "dropped group");
+			}
+		}
+
+		Ok(())
+	}
+
+	#[tracing::instrument("group", skip_all, fields(?subscribe, ?priority, sequence = group.sequence))]
+	async fn serve_group(
+		mut session: web_transport::Session,
+		events: Events,
+		subscribe: u64,
+		alias: u64,
+		priority: i32,
+		mut group: GroupConsumer,
+	) -> Result<(), Error> {
+		let mut stream = Writer::new(session.open_uni().await?);
+		stream.set_priority(priority);
+
+		events.emit(Event::StreamOpened {
+			stream: stream.id(),
+			kind: StreamKind::Group {
+				subscribe,
+				sequence: group.sequence,
+			},
+			local: true,
+		});
+		events.emit(Event::PrioritySet {
+			stream: stream.id(),
+			priority,
+		});
+
+		let res = Self::serve_group_inner(subscribe, alias, &mut group, &mut stream)
+			.await
+			.or_close(&mut stream);
+		events.emit(Event::StreamClosed { stream: stream.id() });
+
+		res
+	}
+
+	async fn serve_group_inner(
+		subscribe: u64,
+		alias: u64,
+		group: &mut GroupConsumer,
+		stream: &mut Writer,
+	) -> Result<(), Error> {
+		let header = ietf::StreamHeader::Group(ietf::GroupHeader {
+			subscribe,
+			alias,
+			group: group.sequence,
+			// We use the QUIC stream priority instead.
+			send_order: 0,
+		});
+
+		// Sent along with the first object.
+		stream.queue(&header);
+
+		let mut id = 0;
+
+		while let Some(mut frame) = group.next_frame().await? {
+			let object = ietf::GroupObject {
+				id,
+				size: frame.size,
+				status: ietf::ObjectStatus::Normal,
+			};
+
+			stream.queue(&object);
+
+			let mut remain = frame.size;
+
+			while let Some(chunk) = crate::Publisher::read_chunk(&mut frame, stream).await? {
+				remain = remain.checked_sub(chunk.len()).ok_or(Error::WrongSize)?;
+				stream.queue_chunk(&chunk).await?;
+			}
+
+			if remain > 0 {
+				return Err(Error::WrongSize);
+			}
+
+			stream.flush().await?;
+
+			id += 1;
+		}
+
+		// Send the header if there were no objects.
+		stream.flush().await?;
+
+		tracing::debug!(objects = id, "served");
+
+		Ok(())
+	}
+
+	async fn get_track(&self, track: Track) -> Result<TrackConsumer, Error> {
+		if let Some(track) = self.tracks.lock().get(&track.path) {
+			return Ok(track.clone());
+		}
+
+		let broadcast = self
+			.broadcasts
+			.lock()
+			.values()
+			.find_map(|broadcast| Some((broadcast.clone(), broadcast.info.track_name(&track.path)?.to_string())));
+
+		if let Some((broadcast, name)) = broadcast {
+			return broadcast.subscribe(Track { path: name, ..track });
+		}
+
+		let router = self.router.lock().clone();
+		match router {
+			Some(router) => router.subscribe(track).await,
+			None => Err(Error::NotFound),
+		}
+	}
+}
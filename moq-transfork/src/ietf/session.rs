@@ -0,0 +1,142 @@
+use super::{Control, Publisher, Subscriber};
+use crate::{Error, Events, Reader, Stream, Writer};
+
+use moq_async::{spawn, OrClose};
+use moq_proto::{coding::DecodeLimits, ietf};
+use tokio::sync::mpsc;
+
+/// Perform the moq-transport handshake as a client.
+pub(crate) async fn connect(session: &mut web_transport::Session, limits: DecodeLimits) -> Result<Stream, Error> {
+	let (send, recv) = session.open_bi().await?;
+
+	let mut stream = Stream::new(send, recv, limits);
+
+	connect_setup(&mut stream).await.or_close(&mut stream)?;
+
+	Ok(stream)
+}
+
+async fn connect_setup(stream: &mut Stream) -> Result<(), Error> {
+	let client = ietf::ClientSetup {
+		versions: [ietf::VERSION].into(),
+		role: ietf::Role::Both,
+		params: Default::default(),
+	};
+
+	stream.writer.encode(&ietf::Message::from(client)).await?;
+
+	let server = match stream.reader.decode().await? {
+		ietf::Message::ServerSetup(server) => server,
+		_ => return Err(Error::ProtocolViolation),
+	};
+
+	if server.version != ietf::VERSION {
+		return Err(Error::Version([ietf::VERSION].into(), [server.version].into()));
+	}
+
+	tracing::info!(version = ?server.version, role = ?server.role, "connected");
+
+	Ok(())
+}
+
+/// Perform the moq-transport handshake as a server, after the CLIENT_SETUP type has been read.
+pub(crate) async fn accept(stream: &mut Stream) -> Result<(), Error> {
+	let client: ietf::ClientSetup = stream.reader.decode().await?;
+
+	if !client.versions.contains(&ietf::VERSION) {
+		return Err(Error::Version(client.versions, [ietf::VERSION].into()));
+	}
+
+	let server = ietf::ServerSetup {
+		version: ietf::VERSION,
+		role: ietf::Role::Both,
+		params: Default::default(),
+	};
+
+	stream.writer.encode(&ietf::Message::from(server)).await?;
+
+	tracing::info!(version = ?ietf::VERSION, role = ?client.role, "connected");
+
+	Ok(())
+}
+
+/// Run the session in the background, returning the publisher and subscriber halves.
+pub(crate) fn start(
+	mut session: web_transport::Session,
+	stream: Stream,
+	limits: DecodeLimits,
+	events: Events,
+) -> (Publisher, Subscriber) {
+	let (control, outgoing) = Control::new();
+
+	let publisher = Publisher::new(session.clone(), control.clone(), events.clone());
+	let subscriber = Subscriber::new(control, limits, events);
+
+	let Stream { writer, reader } = stream;
+
+	let this = (publisher.clone(), subscriber.clone());
+
+	spawn(async move {
+		let res = tokio::select! {
+			res = run_send(writer, outgoing) => res,
+			res = run_recv(reader, publisher, subscriber.clone()) => res,
+			res = run_uni(session.clone(), subscriber, limits) => res,
+		};
+
+		if let Err(err) = res {
+			tracing::warn!(?err, "terminated");
+			session.close(err.to_code(), &err.to_string());
+		}
+	});
+
+	this
+}
+
+async fn run_send(mut writer: Writer, mut outgoing: mpsc::UnboundedReceiver<ietf::Message>) -> Result<(), Error> {
+	while let Some(msg) = outgoing.recv().await {
+		tracing::trace!(?msg, "send");
+		writer.encode(&msg).await?;
+	}
+
+	Err(Error::Cancel)
+}
+
+async fn run_recv(mut reader: Reader, mut publisher: Publisher, mut subscriber: Subscriber) -> Result<(), Error> {
+	while let Some(msg) = reader.decode_maybe::<ietf::Message>().await? {
+		tracing::trace!(?msg, "recv");
+
+		match msg {
+			ietf::Message::Subscribe(msg) => publisher.recv_subscribe(msg)?,
+			ietf::Message::Unsubscribe(msg) => publisher.recv_unsubscribe(msg),
+			ietf::Message::SubscribeOk(msg) => subscriber.recv_subscribe_ok(msg),
+			ietf::Message::SubscribeError(msg) => subscriber.recv_subscribe_error(msg),
+			ietf::Message::SubscribeDone(msg) => subscriber.recv_subscribe_done(msg),
+			ietf::Message::Announce(msg) => subscriber.recv_announce(msg),
+			ietf::Message::Unannounce(msg) => subscriber.recv_unannounce(msg),
+			ietf::Message::AnnounceOk(msg) => tracing::debug!(namespace = ?msg.namespace, "announce ok"),
+			ietf::Message::AnnounceError(msg) => {
+				tracing::warn!(namespace = ?msg.namespace, code = msg.code, reason = ?msg.reason, "announce error")
+			}
+			ietf::Message::AnnounceCancel(msg) => tracing::warn!(namespace = ?msg.namespace, "announce cancelled"),
+			ietf::Message::GoAway(msg) => tracing::info!(uri = ?msg.uri, "going away"),
+			ietf::Message::ClientSetup(_) | ietf::Message::ServerSetup(_) => return Err(Error::ProtocolViolation),
+		}
+	}
+
+	Err(Error::Cancel)
+}
+
+async fn run_uni(
+	mut session: web_transport::Session,
+	subscriber: Subscriber,
+	limits: DecodeLimits,
+) -> Result<(), Error> {
+	loop {
+		let mut stream = Reader::accept(&mut session, limits).await?;
+		let mut subscriber = subscriber.clone();
+
+		spawn(async move {
+			subscriber.recv_stream(&mut stream).await.ok();
+		});
+	}
+}
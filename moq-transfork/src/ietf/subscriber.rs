@@ -0,0 +1,276 @@
+use std::{
+	collections::{hash_map, HashMap},
+	sync::{atomic, Arc},
+};
+
+use tokio::sync::oneshot;
+use tracing::Instrument;
+
+use super::{split, Control};
+use crate::{
+	Announced, AnnouncedConsumer, AnnouncedProducer, Error, Event, Events, Filter, GroupProducer, Reader, StreamKind,
+	Track, TrackConsumer, TrackProducer,
+};
+
+use moq_async::{spawn, Lock, OrClose};
+use moq_proto::{
+	coding::{DecodeError, DecodeLimits},
+	ietf,
+};
+
+#[derive(Clone)]
+pub(crate) struct Subscriber {
+	control: Control,
+
+	// Namespaces announced by the remote.
+	announced: AnnouncedProducer,
+
+	tracks: Lock<HashMap<String, TrackProducer>>,
+	subscribes: Lock<HashMap<u64, Subscription>>,
+	next_id: Arc<atomic::AtomicU64>,
+
+	limits: DecodeLimits,
+	events: Events,
+}
+
+struct Subscription {
+	producer: TrackProducer,
+
+	// Dropped when the publisher ends the subscription.
+	_done: oneshot::Sender<()>,
+}
+
+impl Subscriber {
+	pub fn new(control: Control, limits: DecodeLimits, events: Events) -> Self {
+		// Announcements are pushed without any indication of when we're caught up.
+		let mut announced = AnnouncedProducer::new();
+		announced.live();
+
+		Self {
+			control,
+			announced,
+			tracks: Default::default(),
+			subscribes: Default::default(),
+			next_id: Default::default(),
+			limits,
+			events,
+		}
+	}
+
+	/// Discover any namespaces announced by the remote matching a filter.
+	pub fn announced(&self, filter: Filter) -> AnnouncedConsumer {
+		self.announced.subscribe(filter)
+	}
+
+	/// Discover any namespaces matching a filter, mirroring them into the provided producer.
+	pub fn announced_into(&self, filter: Filter, mut producer: AnnouncedProducer) -> AnnouncedConsumer {
+		let consumer = producer.subscribe(filter.clone());
+		let mut upstream = self.announced.subscribe(filter);
+
+		spawn(async move {
+			loop {
+				tokio::select! {
+					res = upstream.next() => match res {
+						Some(Announced::Active(m)) => producer.announce(m.full()),
+						Some(Announced::Ended(m)) => producer.unannounce(m.full()),
+						Some(Announced::Live) => producer.live(),
+						None => return,
+					},
+					_ = producer.closed() => return,
+				};
+			}
+		});
+
+		consumer
+	}
+
+	/// Subscribe to a track, deduplicating subscriptions to the same path.
+	pub fn subscribe(&self, track: Track) -> TrackConsumer {
+		let path = track.path.clone();
+
+		let producer = match self.tracks.lock().entry(path.clone()) {
+			hash_map::Entry::Occupied(entry) => return entry.get().subscribe(),
+			hash_map::Entry::Vacant(entry) => entry.insert(track.produce().0).clone(),
+		};
+
+		let consumer = producer.subscribe();
+
+		let id = self.next_id.fetch_add(1, atomic::Ordering::Relaxed);
+		let (done, mut ended) = oneshot::channel();
+
+		self.subscribes.lock().insert(
+			id,
+			Subscription {
+				producer: producer.clone(),
+				_done: done,
+			},
+		);
+
+		let (namespace, name) = split(&path);
+
+		tracing::info!(?id, track = ?path, "subscribe");
+
+		self.control.send(ietf::Subscribe {
+			id,
+			alias: id,
+			namespace: namespace.into(),
+			name: name.into(),
+			filter: ietf::SubscribeFilter::LatestGroup,
+			params: Default::default(),
+		});
+
+		let this = self.clone();
+
+		spawn(
+			async move {
+				tokio::select! {
+					// Unsubscribe when there are no more consumers, and the publisher will reply with SUBSCRIBE_DONE.
+					_ = producer.unused() => {
+						this.control.send(ietf::Unsubscribe { id });
+						this.subscribes.lock().remove(&id);
+					},
+					_ = &mut ended => (),
+				}
+
+				this.tracks.lock().remove(&path);
+			}
+			.in_current_span(),
+		);
+
+		consumer
+	}
+
+	pub fn recv_subscribe_ok(&mut self, ok: ietf::SubscribeOk) {
+		tracing::info!(id = ok.id, largest = ?ok.largest, "active");
+	}
+
+	pub fn recv_subscribe_error(&mut self, err: ietf::SubscribeError) {
+		tracing::warn!(id = err.id, code = err.code, reason = ?err.reason, "subscribe error");
+
+		if let Some(subscription) = self.subscribes.lock().remove(&err.id) {
+			subscription.producer.close(Self::remote_error(err.code));
+		}
+	}
+
+	pub fn recv_subscribe_done(&mut self, done: ietf::SubscribeDone) {
+		tracing::info!(id = done.id, code = done.code, reason = ?done.reason, "done");
+
+		let subscription = match self.subscribes.lock().remove(&done.id) {
+			Some(subscription) => subscription,
+			None => return,
+		};
+
+		match done.code {
+			// Dropping the producer will end the track cleanly.
+			ietf::SubscribeDone::TRACK_ENDED | ietf::SubscribeDone::UNSUBSCRIBED => (),
+			code => subscription.producer.close(Self::remote_error(code)),
+		}
+	}
+
+	fn remote_error(code: u64) -> Error {
+		Error::App(code.try_into().unwrap_or(u32::MAX))
+	}
+
+	pub fn recv_announce(&mut self, announce: ietf::Announce) {
+		tracing::info!(namespace = ?announce.namespace, "announced");
+
+		self.announced.announce(&announce.namespace);
+		self.control.send(ietf::AnnounceOk {
+			namespace: announce.namespace,
+		});
+	}
+
+	pub fn recv_unannounce(&mut self, unannounce: ietf::Unannounce) {
+		tracing::info!(namespace = ?unannounce.namespace, "unannounced");
+		self.announced.unannounce(&unannounce.namespace);
+	}
+
+	pub async fn recv_stream(&mut self, stream: &mut Reader) -> Result<(), Error> {
+		let header = stream.decode().await?;
+
+		let res = match header {
+			ietf::StreamHeader::Group(header) => self.recv_group(stream, header).await,
+			ietf::StreamHeader::Track(header) => self.recv_track(stream, header).await,
+		};
+
+		res.or_close(stream)
+	}
+
+	fn producer(&self, subscribe: u64) -> Result<TrackProducer, Error> {
+		let subscribes = self.subscribes.lock();
+		let subscription = subscribes.get(&subscribe).ok_or(Error::Cancel)?;
+		Ok(subscription.producer.clone())
+	}
+
+	#[tracing::instrument("group", skip_all, err, fields(subscribe = ?header.subscribe, group = header.group))]
+	async fn recv_group(&mut self, stream: &mut Reader, header: ietf::GroupHeader) -> Result<(), Error> {
+		self.events.emit(Event::StreamOpened {
+			stream: stream.id(),
+			kind: StreamKind::Group {
+				subscribe: header.subscribe,
+				sequence: header.group,
+			},
+			local: false,
+		});
+
+		let res = self.recv_group_inner(stream, header).await;
+		self.events.emit(Event::StreamClosed { stream: stream.id() });
+
+		res
+	}
+
+	async fn recv_group_inner(&mut self, stream: &mut Reader, header: ietf::GroupHeader) -> Result<(), Error> {
+		let mut group = self.producer(header.subscribe)?.create_group(header.group);
+
+		while let Some(object) = stream.decode_maybe::<ietf::GroupObject>().await? {
+			match object.status {
+				ietf::ObjectStatus::Normal => self.recv_object(stream, &mut group, object.size).await?,
+				ietf::ObjectStatus::ObjectMissing | ietf::ObjectStatus::GroupMissing => (),
+				ietf::ObjectStatus::EndOfGroup | ietf::ObjectStatus::EndOfTrack => break,
+			}
+		}
+
+		Ok(())
+	}
+
+	#[tracing::instrument("track", skip_all, err, fields(subscribe = ?header.subscribe))]
+	async fn recv_track(&mut self, stream: &mut Reader, header: ietf::TrackHeader) -> Result<(), Error> {
+		let mut track = self.producer(header.subscribe)?;
+		let mut group: Option<GroupProducer> = None;
+
+		while let Some(object) = stream.decode_maybe::<ietf::TrackObject>().await? {
+			match object.status {
+				ietf::ObjectStatus::Normal => {
+					let current = match group.take() {
+						Some(current) if current.sequence == object.group => current,
+						_ => track.create_group(object.group),
+					};
+
+					self.recv_object(stream, group.insert(current), object.size).await?;
+				}
+				ietf::ObjectStatus::ObjectMissing | ietf::ObjectStatus::GroupMissing => (),
+				ietf::ObjectStatus::EndOfGroup => group = None,
+				ietf::ObjectStatus::EndOfTrack => break,
+			}
+		}
+
+		Ok(())
+	}
+
+	async fn recv_object(&mut self, stream: &mut Reader, group: &mut GroupProducer, size: usize) -> Result<(), Error> {
+		if group.frame_count() >= self.limits.max_group_frames {
+			return Err(DecodeError::TooManyFrames.into());
+		}
+
+		let mut frame = group.create_frame(size);
+		let mut remain = size;
+
+		while remain > 0 {
+			let chunk = stream.read(remain).await?.ok_or(Error::WrongSize)?;
+			remain = remain.checked_sub(chunk.len()).ok_or(Error::WrongSize)?;
+			frame.write(chunk);
+		}
+
+		Ok(())
+	}
+}
@@ -6,12 +6,13 @@
 //! See the [specification](https://datatracker.ietf.org/doc/draft-lcurley-moq-transfork/) and [github](https://github.com/kixelated/moq-drafts) for any updates.
 //!
 //! The core of this crate is [Session], established with [Session::connect] (client) or [Session::accept] (server).
-//! Once you have a session, you can [Session::publish] or [Session::subscribe].
+//! Once you have a session, you can [Session::publish_broadcast] a collection of tracks or consume a remote [Session::broadcast].
+//! Individual tracks can also be used via [Session::publish] or [Session::subscribe].
 //!
 //! # Producing
 //! There can be only 1 publisher.
 //!
-//! - [BroadcastProducer] can create any number of [TrackProducer]s. Each [Track] is produced independently with a specified order/priority.
+//! - [BroadcastProducer] can create any number of [TrackProducer]s, announced under the [Broadcast] path. Each [Track] is produced independently with a specified order/priority.
 //! - [TrackProducer] can append any number of [GroupProducer]s, with new subscribers joining at [Group] boundaries (ex. keyframes).
 //! - [GroupProducer] can append any number of [Frame]s, either using [GroupProducer::write_frame] (contiguous) or [GroupProducer::create_frame] (chunked).
 //! - [FrameProducer] is thus optional, allowing you to specify an upfront size to write multiple chunks.
@@ -23,7 +24,7 @@
 //! # Consuming
 //! There can be N consumers (via [Clone]), each getting a copy of any requested data.
 //!
-//! - [BroadcastConsumer] can fetch any number of [TrackConsumer]s. Each [Track] is consumed independently with a specified order/priority.
+//! - [BroadcastConsumer] can fetch any number of [TrackConsumer]s, relative to the [Broadcast] path. Each [Track] is consumed independently with a specified order/priority.
 //! - [TrackConsumer] can fetch any number of [GroupConsumer]s, joining at a [Group] boundary (ex. keyframes).
 //! - [GroupConsumer] can fetch any number of [Frame]s, either using [GroupConsumer::read_frame] (contiguous) or [GroupConsumer::next_frame] (chunked).
 //! - [FrameConsumer] is thus optional, allowing you to read chunks as they arrive.
@@ -32,25 +33,38 @@
 //! If the publisher disconnects, then the consumer will error.
 //! If the publisher is dropped (clean FIN), then the above methods will return [None].
 //!
+//! # Runtime
+//! Background tasks are spawned with tokio by default, or `wasm_bindgen_futures` on the web.
+//! Use [moq_async::set_spawner] to run a session on any other executor.
+//!
 mod announced;
+mod broadcast;
+pub mod capture;
+mod consumer;
 mod error;
+mod event;
 mod frame;
 mod group;
+pub mod ietf;
 mod publisher;
 mod reader;
 mod router;
 mod session;
 mod stream;
 mod subscriber;
+mod trace;
 mod track;
 mod writer;
 
 pub use announced::*;
+pub use broadcast::*;
+pub use consumer::*;
 pub use frame::*;
 pub use group::*;
 pub use router::*;
 pub use track::*;
 
+pub(crate) use event::Events;
 pub(crate) use publisher::*;
 pub(crate) use reader::*;
 pub(crate) use stream::*;
@@ -58,6 +72,7 @@ pub(crate) use subscriber::*;
 pub(crate) use writer::*;
 
 pub use error::*;
+pub use event::*;
 pub use session::*;
 
 /// The ALPN used when connecting via QUIC directly.
@@ -1,10 +1,13 @@
 use std::collections::{hash_map, HashMap};
 
-use futures::{stream::FuturesUnordered, StreamExt};
+use bytes::Bytes;
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use tokio::sync::watch;
 
 use crate::{
-	Announced, AnnouncedConsumer, AnnouncedProducer, Error, GroupConsumer, GroupOrder, RouterConsumer, Stream, Track,
-	TrackConsumer, Writer,
+	announced::{AnnouncedMerge, AnnouncedSource},
+	trace, Announced, AnnouncedConsumer, AnnouncedProducer, BroadcastConsumer, Error, Event, Events, FrameConsumer,
+	GroupConsumer, GroupOrder, RouterConsumer, Stream, StreamKind, Track, TrackConsumer, Writer,
 };
 
 use moq_async::{spawn, FuturesExt, Lock, OrClose};
@@ -14,21 +17,41 @@ use moq_proto::message;
 pub(super) struct Publisher {
 	session: web_transport::Session,
 	announced: AnnouncedProducer,
+
+	// Every producer that is served to announce streams, starting with our own.
+	sources: watch::Sender<Vec<AnnouncedSource>>,
+
 	tracks: Lock<HashMap<String, TrackConsumer>>,
+	broadcasts: Lock<HashMap<String, BroadcastConsumer>>,
 	router: Lock<Option<RouterConsumer>>,
+
+	// True if each subscribe is followed by a trace context.
+	trace: bool,
+
+	// True if announce streams can resume from a cursor.
+	cursor: bool,
+
+	events: Events,
 }
 
 impl Publisher {
-	pub fn new(session: web_transport::Session) -> Self {
+	pub fn new(session: web_transport::Session, trace: bool, cursor: bool, events: Events) -> Self {
 		// We start the publisher in live mode because we're producing content.
 		let mut announced = AnnouncedProducer::new();
 		announced.live();
 
+		let sources = watch::Sender::new(vec![announced.source()]);
+
 		Self {
 			session,
 			announced,
+			sources,
 			tracks: Default::default(),
+			broadcasts: Default::default(),
 			router: Default::default(),
+			trace,
+			cursor,
+			events,
 		}
 	}
 
@@ -41,9 +64,12 @@ impl Publisher {
 
 		match self.tracks.lock().entry(track.path.clone()) {
 			hash_map::Entry::Occupied(_) => return Err(Error::Duplicate),
-			hash_map::Entry::Vacant(entry) => entry.insert(track.clone()),
+			hash_map::Entry::Vacant(entry) => entry.insert(track.clone().passive()),
 		};
 
+		// Only remote subscriptions count as demand.
+		let track = track.passive();
+
 		let mut this = self.clone();
 
 		spawn(async move {
@@ -64,19 +90,49 @@ impl Publisher {
 	/// NOTE: You may want to call [Self::route] to process any subscriptions for these paths.
 	/// [crate::AnnouncedConsumer] will automatically unannounce if the [crate::AnnouncedProducer] is dropped.
 	pub fn announce(&mut self, mut upstream: AnnouncedConsumer) {
-		let mut downstream = self.announced.clone();
+		// Serve the upstream producer directly so announce streams can resume from its history.
+		let source = upstream.source();
+		let id = source.id();
+		self.sources.send_modify(|sources| sources.push(source));
+
+		let this = self.clone();
+
+		spawn(async move {
+			// Keep the consumer alive until the upstream producer is dropped.
+			tokio::select! {
+				_ = async { while upstream.next().await.is_some() {} } => (),
+				_ = this.session.closed() => (),
+			}
+
+			this.sources
+				.send_modify(|sources| sources.retain(|source| source.id() != id));
+		});
+	}
+
+	/// Publish a broadcast, announcing and serving each of its tracks.
+	pub fn publish_broadcast(&mut self, broadcast: BroadcastConsumer) -> Result<(), Error> {
+		match self.broadcasts.lock().entry(broadcast.info.path.clone()) {
+			hash_map::Entry::Occupied(_) => return Err(Error::Duplicate),
+			hash_map::Entry::Vacant(entry) => entry.insert(broadcast.clone()),
+		};
+
+		self.announce(broadcast.announced());
+
+		let this = self.clone();
 
 		spawn(async move {
-			while let Some(announced) = upstream.next().await {
-				match announced {
-					Announced::Active(m) => downstream.announce(m.full()),
-					Announced::Ended(m) => downstream.unannounce(m.full()),
-
-					// Indicate that we're caught up to live.
-					Announced::Live => downstream.live(),
-				};
+			// The announcements end when the broadcast producer is dropped.
+			let mut announced = broadcast.announced();
+
+			tokio::select! {
+				_ = async { while announced.next().await.is_some() {} } => (),
+				_ = this.session.closed() => (),
 			}
+
+			this.broadcasts.lock().remove(&broadcast.info.path);
 		});
+
+		Ok(())
 	}
 
 	/// Optionally support requests for arbitrary paths using the provided router.
@@ -88,42 +144,108 @@ impl Publisher {
 	}
 
 	pub async fn recv_announce(&mut self, stream: &mut Stream) -> Result<(), Error> {
-		let interest = stream.reader.decode::<message::AnnouncePlease>().await?;
+		let interest = match self.cursor {
+			true => stream.reader.decode::<message::AnnouncePlease>().await?,
+			false => message::AnnouncePlease {
+				filter: stream.reader.decode().await?,
+				cursor: None,
+			},
+		};
+
 		let filter = interest.filter;
-		tracing::debug!(?filter, "announce interest");
+		tracing::debug!(?filter, cursor = ?interest.cursor, "announce interest");
 
-		let mut announced = self.announced.subscribe(filter);
+		let mut sources = self.sources.subscribe();
+		let mut merged = AnnouncedMerge::new(filter);
+
+		let resumed = match interest.cursor {
+			Some(cursor) if merged.resume(&sources.borrow_and_update(), &cursor) => Some(cursor),
+			_ => None,
+		};
+
+		match resumed {
+			// Indicate that we're only sending changes since the cursor.
+			Some(cursor) => stream.writer.encode(&message::Announce::Cursor(cursor)).await?,
+			None => merged.extend(&sources.borrow_and_update()),
+		}
 
 		// Flush any synchronously announced paths
-		while let Some(announced) = announced.next().await {
-			match announced {
-				Announced::Active(m) => {
-					let msg = message::Announce::Active(m.capture().to_string());
-					stream.writer.encode(&msg).await?;
-				}
-				Announced::Ended(m) => {
-					let msg = message::Announce::Ended(m.capture().to_string());
-					stream.writer.encode(&msg).await?;
-				}
-				Announced::Live => {
-					// Indicate that we're caught up to live.
-					stream.writer.encode(&message::Announce::Live).await?;
+		while let Some(Some(announced)) = merged.next().now_or_never() {
+			Self::send_announce(stream, announced).await?;
+		}
+
+		// Indicate that we're caught up to live.
+		stream.writer.encode(&message::Announce::Live).await?;
+
+		let mut cursor = None;
+
+		loop {
+			// Let the subscriber know where to resume from whenever we're caught up.
+			if let Some(latest) = merged.cursor().filter(|_| self.cursor) {
+				if cursor.as_ref() != Some(&latest) {
+					stream.writer.encode(&message::Announce::Cursor(latest.clone())).await?;
+					cursor = Some(latest);
 				}
 			}
+
+			tokio::select! {
+				res = merged.next() => match res {
+					Some(announced) => {
+						Self::send_announce(stream, announced).await?;
+
+						// Send any other ready changes first, so there's only one cursor per burst.
+						while let Some(Some(announced)) = merged.next().now_or_never() {
+							Self::send_announce(stream, announced).await?;
+						}
+					}
+					None => return Ok(()),
+				},
+				res = sources.changed() => match res {
+					Ok(()) => merged.extend(&sources.borrow_and_update()),
+					Err(_) => return Ok(()),
+				},
+				// Stop if the subscriber is no longer interested.
+				res = stream.reader.finished() => return res,
+			}
 		}
+	}
+
+	async fn send_announce(stream: &mut Stream, announced: Announced) -> Result<(), Error> {
+		let msg = match announced {
+			Announced::Active(m) => message::Announce::Active(m.capture_str()),
+			Announced::Ended(m) => message::Announce::Ended(m.capture_str()),
+			// We send our own live message.
+			Announced::Live => return Ok(()),
+		};
+
+		stream.writer.encode(&msg).await?;
 
 		Ok(())
 	}
 
 	pub async fn recv_subscribe(&mut self, stream: &mut Stream) -> Result<(), Error> {
 		let subscribe = stream.reader.decode().await?;
-		self.serve_subscribe(stream, subscribe).await
+		let trace = match self.trace {
+			true => Some(stream.reader.decode().await?),
+			false => None,
+		};
+
+		self.serve_subscribe(stream, subscribe, trace).await
 	}
 
 	#[tracing::instrument("publishing", skip_all, err, fields(track = ?subscribe.path, id = subscribe.id))]
-	async fn serve_subscribe(&mut self, stream: &mut Stream, subscribe: message::Subscribe) -> Result<(), Error> {
+	async fn serve_subscribe(
+		&mut self,
+		stream: &mut Stream,
+		subscribe: message::Subscribe,
+		trace: Option<message::TraceContext>,
+	) -> Result<(), Error> {
+		if let Some(trace) = trace {
+			trace::link(&tracing::Span::current(), &trace);
+		}
+
 		let track = Track {
-			path: subscribe.path,
+			path: subscribe.path.into(),
 			priority: subscribe.priority,
 			order: subscribe.order,
 		};
@@ -143,21 +265,42 @@ impl Publisher {
 		let mut tasks = FuturesUnordered::new();
 		let mut complete = false;
 
+		// Set if the track fails, finishing any groups in progress before returning the error.
+		let mut failed = None;
+
+		// May be changed by a SubscribeUpdate, applied to any new groups.
+		let mut priority = track.priority;
+		let mut order = track.order;
+
 		loop {
+			if failed.is_some() && tasks.is_empty() {
+				break;
+			}
+
 			tokio::select! {
-				Some(group) = track.next_group().transpose() => {
-					let mut group = group?;
+				Some(group) = track.next_group().transpose(), if failed.is_none() => {
+					let mut group = match group {
+						Ok(group) => group,
+						Err(err) => {
+							failed = Some(err);
+							continue;
+						}
+					};
+
 					let session = self.session.clone();
-					let priority = Self::stream_priority(track.priority, track.order, group.sequence);
+					let events = self.events.clone();
+					let priority = Self::stream_priority(priority, order, group.sequence);
 
 					tasks.push(async move {
-						let res = Self::serve_group(session, subscribe.id, priority, &mut group).await;
+						let res = Self::serve_group(session, events, subscribe.id, priority, &mut group).await;
 						(group, res)
 					});
 				},
 				res = stream.reader.decode_maybe::<message::SubscribeUpdate>(), if !complete => match res? {
-					Some(_update) => {
-						// TODO use it
+					Some(update) => {
+						tracing::debug!(?update, "updated");
+						priority = update.priority;
+						order = update.order;
 					},
 					// Subscribe has completed
 					None => {
@@ -177,12 +320,24 @@ impl Publisher {
 						};
 
 						stream.writer.encode(&drop).await?;
+
+						self.events.emit(Event::GroupDropped {
+							subscribe: subscribe.id,
+							sequence: drop.sequence,
+							count: drop.count,
+							code: drop.code,
+							local: true,
+						});
 					}
 				},
 				else => break,
 			}
 		}
 
+		if let Some(err) = failed {
+			return Err(err);
+		}
+
 		tracing::info!("done");
 
 		Ok(())
@@ -191,6 +346,7 @@ impl Publisher {
 	#[tracing::instrument("group", skip_all, fields(?subscribe, ?priority, sequence = group.sequence))]
 	pub async fn serve_group(
 		mut session: web_transport::Session,
+		events: Events,
 		subscribe: u64,
 		priority: i32,
 		group: &mut GroupConsumer,
@@ -199,11 +355,27 @@ impl Publisher {
 		let mut stream = Writer::open(&mut session, message::DataType::Group).await?;
 		stream.set_priority(priority);
 
+		events.emit(Event::StreamOpened {
+			stream: stream.id(),
+			kind: StreamKind::Group {
+				subscribe,
+				sequence: group.sequence,
+			},
+			local: true,
+		});
+		events.emit(Event::PrioritySet {
+			stream: stream.id(),
+			priority,
+		});
+
 		tracing::trace!("serving");
 
-		Self::serve_group_inner(subscribe, group, &mut stream)
+		let res = Self::serve_group_inner(subscribe, group, &mut stream)
 			.await
-			.or_close(&mut stream)
+			.or_close(&mut stream);
+		events.emit(Event::StreamClosed { stream: stream.id() });
+
+		res
 	}
 
 	pub async fn serve_group_inner(
@@ -216,30 +388,37 @@ impl Publisher {
 			sequence: group.sequence,
 		};
 
-		stream.encode(&msg).await?;
+		// Sent along with the first frame.
+		stream.queue(&msg);
 
 		let mut frames = 0;
 
 		while let Some(mut frame) = group.next_frame().await? {
 			let header = message::Frame { size: frame.size };
-			stream.encode(&header).await?;
+			stream.queue(&header);
 
 			let mut remain = frame.size;
 
-			while let Some(chunk) = frame.read().await? {
+			while let Some(chunk) = Self::read_chunk(&mut frame, stream).await? {
 				remain = remain.checked_sub(chunk.len()).ok_or(Error::WrongSize)?;
 				tracing::trace!(chunk = chunk.len(), remain, "chunk");
 
-				stream.write(&chunk).await?;
+				stream.queue_chunk(&chunk).await?;
 			}
 
 			if remain > 0 {
 				return Err(Error::WrongSize);
 			}
 
+			// Flush at frame boundaries so small frames are sent with a single write.
+			stream.flush().await?;
+
 			frames += 1;
 		}
 
+		// Send the header if there were no frames.
+		stream.flush().await?;
+
 		tracing::debug!(frames, "served");
 
 		// TODO block until all bytes have been acknowledged so we can still reset
@@ -248,6 +427,16 @@ impl Publisher {
 		Ok(())
 	}
 
+	// Returns the next chunk, first flushing anything queued if we would otherwise block.
+	pub(crate) async fn read_chunk(frame: &mut FrameConsumer, stream: &mut Writer) -> Result<Option<Bytes>, Error> {
+		if let Some(res) = frame.read().now_or_never() {
+			return res;
+		}
+
+		stream.flush().await?;
+		frame.read().await
+	}
+
 	pub async fn recv_info(&mut self, stream: &mut Stream) -> Result<(), Error> {
 		let info = stream.reader.decode().await?;
 		self.serve_info(stream, info).await
@@ -256,7 +445,7 @@ impl Publisher {
 	#[tracing::instrument("info", skip_all, err, fields(track = ?info.path))]
 	async fn serve_info(&mut self, stream: &mut Stream, info: message::InfoRequest) -> Result<(), Error> {
 		let track = Track {
-			path: info.path,
+			path: info.path.into(),
 			..Default::default()
 		};
 		let track = self.get_track(track).await?;
@@ -277,6 +466,16 @@ impl Publisher {
 			return Ok(track.clone());
 		}
 
+		let broadcast = self
+			.broadcasts
+			.lock()
+			.values()
+			.find_map(|broadcast| Some((broadcast.clone(), broadcast.info.track_name(&track.path)?.to_string())));
+
+		if let Some((broadcast, name)) = broadcast {
+			return broadcast.subscribe(Track { path: name, ..track });
+		}
+
 		let router = self.router.lock().clone();
 		match router {
 			Some(router) => router.subscribe(track).await,
@@ -289,7 +488,7 @@ impl Publisher {
 	// Specifically, group sequence 2^24 will overflow and be incorrectly prioritized.
 	// But even with a group per frame, it will take ~6 days to reach that point.
 	// TODO The behavior when two tracks share the same priority is undefined. Should we round-robin?
-	fn stream_priority(track_priority: i8, group_order: GroupOrder, group_sequence: u64) -> i32 {
+	pub(crate) fn stream_priority(track_priority: i8, group_order: GroupOrder, group_sequence: u64) -> i32 {
 		let sequence = (group_sequence as u32) & 0xFFFFFF;
 		((track_priority as i32) << 24)
 			| match group_order {
@@ -1,40 +1,64 @@
-use std::{cmp, fmt, io};
+use std::{cmp, fmt};
 
-use bytes::{Buf, Bytes, BytesMut};
+use bytes::{Bytes, BytesMut};
 
-use crate::Error;
+use crate::{capture, Error};
 use moq_async::Close;
 use moq_proto::coding::*;
 
 pub struct Reader {
 	stream: web_transport::RecvStream,
-	buffer: BytesMut,
+
+	// Frozen so decoded messages can reference it; only made mutable again when reading more data.
+	buffer: Bytes,
+	limits: DecodeLimits,
+
+	// Used to identify the stream in captures.
+	id: u64,
 }
 
 impl Reader {
-	pub fn new(stream: web_transport::RecvStream) -> Self {
+	pub fn new(stream: web_transport::RecvStream, limits: DecodeLimits) -> Self {
+		Self::with_id(stream, limits, capture::next_stream())
+	}
+
+	pub(crate) fn with_id(stream: web_transport::RecvStream, limits: DecodeLimits, id: u64) -> Self {
 		Self {
 			stream,
 			buffer: Default::default(),
+			limits,
+			id,
 		}
 	}
 
-	pub async fn accept(session: &mut web_transport::Session) -> Result<Self, Error> {
+	/// The identifier used for captures and events.
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
+	pub async fn accept(session: &mut web_transport::Session, limits: DecodeLimits) -> Result<Self, Error> {
 		let stream = session.accept_uni().await?;
-		Ok(Self::new(stream))
+		Ok(Self::new(stream, limits))
 	}
 
 	pub async fn decode<T: Decode + fmt::Debug>(&mut self) -> Result<T, Error> {
 		loop {
-			let mut cursor = io::Cursor::new(&self.buffer);
+			// Decode from Bytes so any strings or payloads can reference the buffer instead of making a copy.
+			let mut cursor = self.buffer.clone();
 
 			// Try to decode with the current buffer.
-			match T::decode(&mut cursor) {
+			match T::decode_limits(&mut cursor, &self.limits) {
 				Ok(msg) => {
-					self.buffer.advance(cursor.position() as usize);
+					let size = self.buffer.len() - cursor.len();
+					capture::record::<T, _>(self.id, capture::Direction::Recv, || self.buffer.slice(..size));
+
+					// Advance past the message without copying the remaining bytes.
+					self.buffer = cursor;
 					return Ok(msg);
 				}
-				Err(DecodeError::Short) => (), // Try again with more data
+				Err(DecodeError::Short) => {
+					// Try again with more data.
+				}
 				Err(err) => return Err(err.into()),
 			};
 
@@ -42,12 +66,34 @@ impl Reader {
 				tracing::trace!(?self.buffer, "more data needed");
 			}
 
-			if self.stream.read_buf(&mut self.buffer).await?.is_none() {
+			if !self.read_more().await? {
 				return Err(DecodeError::Short.into());
 			}
 		}
 	}
 
+	// Append more data to the buffer, returning false if the stream is finished.
+	async fn read_more(&mut self) -> Result<bool, Error> {
+		// Read a chunk rather than into the buffer, so nothing is lost if this future is cancelled.
+		let chunk = match self.stream.read(usize::MAX).await? {
+			Some(chunk) => chunk,
+			None => return Ok(false),
+		};
+
+		self.buffer = match std::mem::take(&mut self.buffer) {
+			buffer if buffer.is_empty() => chunk,
+			buffer => {
+				// This reuses the allocation unless a decoded message still references it.
+				// Otherwise only the unread bytes are copied, which is at most a partial message.
+				let mut buffer = BytesMut::from(buffer);
+				buffer.extend_from_slice(&chunk);
+				buffer.freeze()
+			}
+		};
+
+		Ok(true)
+	}
+
 	// Decode optional messages at the end of a stream
 	pub async fn decode_maybe<T: Decode + fmt::Debug>(&mut self) -> Result<Option<T>, Error> {
 		match self.finished().await {
@@ -61,7 +107,7 @@ impl Reader {
 	pub async fn read(&mut self, max: usize) -> Result<Option<Bytes>, Error> {
 		if !self.buffer.is_empty() {
 			let size = cmp::min(max, self.buffer.len());
-			let data = self.buffer.split_to(size).freeze();
+			let data = self.buffer.split_to(size);
 			return Ok(Some(data));
 		}
 
@@ -70,7 +116,7 @@ impl Reader {
 
 	/// Wait until the stream is closed, ensuring there are no additional bytes
 	pub async fn finished(&mut self) -> Result<(), Error> {
-		if self.buffer.is_empty() && self.stream.read_buf(&mut self.buffer).await?.is_none() {
+		if self.buffer.is_empty() && !self.read_more().await? {
 			return Ok(());
 		}
 
@@ -55,7 +55,11 @@ impl RouterConsumer {
 
 	pub async fn subscribe(&self, track: Track) -> Result<TrackConsumer, Error> {
 		let (send, recv) = oneshot::channel();
-		let request = RouterRequest { track, reply: send };
+		let request = RouterRequest {
+			track,
+			span: tracing::Span::current(),
+			reply: send,
+		};
 
 		if self.queue.send(request).await.is_err() {
 			return Err(Error::Cancel);
@@ -72,6 +76,10 @@ impl RouterConsumer {
 /// An outstanding request for a path.
 pub struct RouterRequest {
 	pub track: Track,
+
+	/// The span of the requester, which should be entered when subscribing upstream so the trace is linked.
+	pub span: tracing::Span,
+
 	reply: oneshot::Sender<Result<TrackConsumer, Error>>,
 }
 
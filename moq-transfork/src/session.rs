@@ -1,37 +1,77 @@
+use std::sync::Arc;
+
 use crate::{
-	AnnouncedConsumer, Error, Filter, Publisher, Reader, RouterConsumer, Stream, Subscriber, Track, TrackConsumer,
+	ietf, AnnouncedConsumer, AnnouncedProducer, Broadcast, BroadcastConsumer, Error, Event, Events, Filter, Observer,
+	Publisher, Reader, RouterConsumer, Stream, StreamKind, Subscriber, Track, TrackConsumer,
 };
-use moq_proto::message;
+use moq_proto::{coding::DecodeLimits, message};
 
+use crate::trace;
 use moq_async::{spawn, OrClose};
 
 /// A MoqTransfork session, used to publish and/or subscribe to broadcasts.
 ///
-/// A publisher will [Self::publish] tracks, or alternatively [Self::announce] and [Self::route] arbitrary paths.
-/// A subscriber will [Self::subscribe] to tracks, or alternatively use [Self::announced] to discover arbitrary paths.
+/// A publisher will [Self::publish] tracks or [Self::publish_broadcast] a collection of them, or alternatively [Self::announce] and [Self::route] arbitrary paths.
+/// A subscriber will [Self::subscribe] to tracks or consume a [Self::broadcast], or alternatively use [Self::announced] to discover arbitrary paths.
+///
+/// The session may instead speak the IETF moq-transport draft, see the [ietf] module for caveats.
 #[derive(Clone)]
 pub struct Session {
 	webtransport: web_transport::Session,
-	publisher: Publisher,
-	subscriber: Subscriber,
+	protocol: Protocol,
+	events: Events,
+
+	// The token presented by the client, if we're the server.
+	authorization: Option<String>,
 }
 
+#[derive(Clone)]
+enum Protocol {
+	Transfork {
+		publisher: Publisher,
+		subscriber: Subscriber,
+	},
+	Ietf {
+		publisher: ietf::Publisher,
+		subscriber: ietf::Subscriber,
+	},
+}
+
+// The transfork versions we support, in order of preference.
+const VERSIONS: [message::Version; 2] = [message::Version::FORK_05, message::Version::FORK_04];
+
 impl Session {
-	fn new(mut session: web_transport::Session, stream: Stream) -> Self {
-		let publisher = Publisher::new(session.clone());
-		let subscriber = Subscriber::new(session.clone());
+	fn new(
+		mut session: web_transport::Session,
+		stream: Stream,
+		version: message::Version,
+		trace: bool,
+		limits: DecodeLimits,
+		authorization: Option<String>,
+	) -> Self {
+		let events = Events::default();
+
+		// Announce streams can only be resumed with newer versions.
+		let cursor = version >= message::Version::FORK_05;
+
+		let publisher = Publisher::new(session.clone(), trace, cursor, events.clone());
+		let subscriber = Subscriber::new(session.clone(), trace, cursor, limits, events.clone());
 
 		let this = Self {
 			webtransport: session.clone(),
-			publisher: publisher.clone(),
-			subscriber: subscriber.clone(),
+			protocol: Protocol::Transfork {
+				publisher: publisher.clone(),
+				subscriber: subscriber.clone(),
+			},
+			events: events.clone(),
+			authorization,
 		};
 
 		spawn(async move {
 			let res = tokio::select! {
 				res = Self::run_session(stream) => res,
-				res = Self::run_bi(session.clone(), publisher) => res,
-				res = Self::run_uni(session.clone(), subscriber) => res,
+				res = Self::run_bi(session.clone(), publisher, limits, events) => res,
+				res = Self::run_uni(session.clone(), subscriber, limits) => res,
 			};
 
 			if let Err(err) = res {
@@ -45,57 +85,183 @@ impl Session {
 
 	/// Perform the MoQ handshake as a client.
 	pub async fn connect<T: Into<web_transport::Session>>(session: T) -> Result<Self, Error> {
-		let mut session = session.into();
-		let mut stream = Stream::open(&mut session, message::ControlType::Session).await?;
-		Self::connect_setup(&mut stream).await.or_close(&mut stream)?;
-		Ok(Self::new(session, stream))
+		Self::connect_with(session, DecodeLimits::default()).await
+	}
+
+	/// Perform the MoQ handshake as a client, enforcing the limits on anything received from the server.
+	pub async fn connect_with<T: Into<web_transport::Session>>(
+		session: T,
+		limits: DecodeLimits,
+	) -> Result<Self, Error> {
+		Self::connect_version(session, message::Version::CURRENT, limits).await
 	}
 
-	async fn connect_setup(setup: &mut Stream) -> Result<(), Error> {
-		let client = message::ClientSetup {
-			versions: [message::Version::CURRENT].into(),
+	/// Perform the MoQ handshake as a client, presenting a token to the server if provided.
+	///
+	/// The token is ignored when connecting via the IETF moq-transport draft.
+	pub async fn connect_token<T: Into<web_transport::Session>>(
+		session: T,
+		token: Option<&str>,
+	) -> Result<Self, Error> {
+		Self::connect_inner(
+			session.into(),
+			message::Version::CURRENT,
+			DecodeLimits::default(),
+			token,
+		)
+		.await
+	}
+
+	/// Perform the MoQ handshake as a client using the given version.
+	///
+	/// Use [ietf::VERSION] to connect to a third-party implementation of the IETF moq-transport draft.
+	/// Older transfork versions are offered alongside the requested version, so the server may pick one of them.
+	pub async fn connect_version<T: Into<web_transport::Session>>(
+		session: T,
+		version: message::Version,
+		limits: DecodeLimits,
+	) -> Result<Self, Error> {
+		Self::connect_inner(session.into(), version, limits, None).await
+	}
+
+	async fn connect_inner(
+		mut session: web_transport::Session,
+		version: message::Version,
+		limits: DecodeLimits,
+		token: Option<&str>,
+	) -> Result<Self, Error> {
+		match version {
+			version if VERSIONS.contains(&version) => {
+				// Offer the requested version and anything older, so we can connect to servers that haven't upgraded.
+				let versions: Vec<_> = VERSIONS.into_iter().filter(|v| *v <= version).collect();
+
+				let mut stream = Stream::open(&mut session, message::ControlType::Session, limits).await?;
+				let (version, trace) = Self::connect_setup(&mut stream, versions.into(), token)
+					.await
+					.or_close(&mut stream)?;
+				Ok(Self::new(session, stream, version, trace, limits, None))
+			}
+			ietf::VERSION => {
+				let stream = ietf::connect(&mut session, limits).await?;
+				Ok(Self::new_ietf(session, stream, limits))
+			}
+			_ => Err(Error::Version(
+				[version].into(),
+				[message::Version::FORK_05, message::Version::FORK_04, ietf::VERSION].into(),
+			)),
+		}
+	}
+
+	fn new_ietf(session: web_transport::Session, stream: Stream, limits: DecodeLimits) -> Self {
+		let events = Events::default();
+		let (publisher, subscriber) = ietf::start(session.clone(), stream, limits, events.clone());
+
+		Self {
+			webtransport: session,
+			protocol: Protocol::Ietf { publisher, subscriber },
+			events,
+			authorization: None,
+		}
+	}
+
+	// Returns the negotiated version, and true if both sides support trace context propagation.
+	async fn connect_setup(
+		setup: &mut Stream,
+		versions: message::Versions,
+		token: Option<&str>,
+	) -> Result<(message::Version, bool), Error> {
+		let mut client = message::ClientSetup {
+			versions,
 			extensions: Default::default(),
 		};
 
+		if let Some(token) = token {
+			client.extensions.set(message::Authorization {
+				token: token.to_string(),
+			});
+		}
+
+		if trace::ENABLED {
+			client.extensions.set(trace::current());
+		}
+
 		setup.writer.encode(&client).await?;
 		let server: message::ServerSetup = setup.reader.decode().await?;
 
+		if !client.versions.contains(&server.version) {
+			return Err(Error::Version([server.version].into(), client.versions));
+		}
+
+		let trace = trace::ENABLED && server.extensions.get::<message::TraceContext>()?.is_some();
+
 		tracing::info!(version = ?server.version, "connected");
 
-		Ok(())
+		Ok((server.version, trace))
 	}
 
 	/// Perform the MoQ handshake as a server
 	pub async fn accept<T: Into<web_transport::Session>>(session: T) -> Result<Self, Error> {
+		Self::accept_with(session, DecodeLimits::default()).await
+	}
+
+	/// Perform the MoQ handshake as a server, enforcing the limits on anything received from the client.
+	///
+	/// A client speaking the IETF moq-transport draft is detected automatically.
+	pub async fn accept_with<T: Into<web_transport::Session>>(session: T, limits: DecodeLimits) -> Result<Self, Error> {
 		let mut session = session.into();
-		let mut stream = Stream::accept(&mut session).await?;
-		let kind = stream.reader.decode().await?;
+		let mut stream = Stream::accept(&mut session, limits).await?;
+		let kind: u64 = stream.reader.decode().await?;
+
+		// moq-transport starts with a CLIENT_SETUP message instead of a stream type.
+		if kind == moq_proto::ietf::ClientSetup::ID {
+			ietf::accept(&mut stream).await.or_close(&mut stream)?;
+			return Ok(Self::new_ietf(session, stream, limits));
+		}
+
+		let kind = message::ControlType::try_from(kind)?;
 
 		if kind != message::ControlType::Session {
 			return Err(Error::UnexpectedStream(kind));
 		}
 
-		Self::accept_setup(&mut stream).await.or_close(&mut stream)?;
-		Ok(Self::new(session, stream))
+		let (version, trace, authorization) = Self::accept_setup(&mut stream).await.or_close(&mut stream)?;
+		Ok(Self::new(session, stream, version, trace, limits, authorization))
 	}
 
-	async fn accept_setup(control: &mut Stream) -> Result<(), Error> {
+	// Returns the negotiated version, true if both sides support trace context propagation, and the client's token if any.
+	async fn accept_setup(control: &mut Stream) -> Result<(message::Version, bool, Option<String>), Error> {
 		let client: message::ClientSetup = control.reader.decode().await?;
-
-		if !client.versions.contains(&message::Version::CURRENT) {
-			return Err(Error::Version(client.versions, [message::Version::CURRENT].into()));
-		}
-
-		let server = message::ServerSetup {
-			version: message::Version::CURRENT,
+		let authorization = client
+			.extensions
+			.get::<message::Authorization>()?
+			.map(|auth| auth.token);
+
+		// Pick our most preferred version that the client supports.
+		let version = VERSIONS
+			.into_iter()
+			.find(|version| client.versions.contains(version))
+			.ok_or_else(|| Error::Version(client.versions.clone(), VERSIONS.into()))?;
+
+		let mut server = message::ServerSetup {
+			version,
 			extensions: Default::default(),
 		};
 
+		// Link the current span to the client's, and indicate that we support propagation.
+		let trace = match client.extensions.get::<message::TraceContext>()? {
+			Some(remote) if trace::ENABLED => {
+				trace::link(&tracing::Span::current(), &remote);
+				server.extensions.set(trace::current());
+				true
+			}
+			_ => false,
+		};
+
 		control.writer.encode(&server).await?;
 
 		tracing::info!(version = ?server.version, "connected");
 
-		Ok(())
+		Ok((version, trace, authorization))
 	}
 
 	async fn run_session(mut stream: Stream) -> Result<(), Error> {
@@ -103,9 +269,13 @@ impl Session {
 		Err(Error::Cancel)
 	}
 
-	async fn run_uni(mut session: web_transport::Session, subscriber: Subscriber) -> Result<(), Error> {
+	async fn run_uni(
+		mut session: web_transport::Session,
+		subscriber: Subscriber,
+		limits: DecodeLimits,
+	) -> Result<(), Error> {
 		loop {
-			let mut stream = Reader::accept(&mut session).await?;
+			let mut stream = Reader::accept(&mut session, limits).await?;
 			let subscriber = subscriber.clone();
 
 			spawn(async move {
@@ -121,13 +291,19 @@ impl Session {
 		}
 	}
 
-	async fn run_bi(mut session: web_transport::Session, publisher: Publisher) -> Result<(), Error> {
+	async fn run_bi(
+		mut session: web_transport::Session,
+		publisher: Publisher,
+		limits: DecodeLimits,
+		events: Events,
+	) -> Result<(), Error> {
 		loop {
-			let mut stream = Stream::accept(&mut session).await?;
+			let mut stream = Stream::accept(&mut session, limits).await?;
 			let publisher = publisher.clone();
+			let events = events.clone();
 
 			spawn(async move {
-				Self::run_control(&mut stream, publisher)
+				Self::run_control(&mut stream, publisher, events)
 					.await
 					.or_close(&mut stream)
 					.ok();
@@ -135,19 +311,46 @@ impl Session {
 		}
 	}
 
-	async fn run_control(stream: &mut Stream, mut publisher: Publisher) -> Result<(), Error> {
+	async fn run_control(stream: &mut Stream, mut publisher: Publisher, events: Events) -> Result<(), Error> {
 		let kind = stream.reader.decode().await?;
-		match kind {
+
+		events.emit(Event::StreamOpened {
+			stream: stream.id(),
+			kind: match kind {
+				message::ControlType::Session => StreamKind::Session,
+				message::ControlType::Announce => StreamKind::Announce,
+				message::ControlType::Subscribe => StreamKind::Subscribe,
+				message::ControlType::Info => StreamKind::Info,
+			},
+			local: false,
+		});
+
+		let res = match kind {
 			message::ControlType::Session => Err(Error::UnexpectedStream(kind)),
 			message::ControlType::Announce => publisher.recv_announce(stream).await,
 			message::ControlType::Subscribe => publisher.recv_subscribe(stream).await,
 			message::ControlType::Info => publisher.recv_info(stream).await,
-		}
+		};
+
+		events.emit(Event::StreamClosed { stream: stream.id() });
+
+		res
 	}
 
 	/// Publish a track, automatically announcing and serving it.
 	pub fn publish(&mut self, track: TrackConsumer) -> Result<(), Error> {
-		self.publisher.publish(track)
+		match &mut self.protocol {
+			Protocol::Transfork { publisher, .. } => publisher.publish(track),
+			Protocol::Ietf { publisher, .. } => publisher.publish(track),
+		}
+	}
+
+	/// Publish a broadcast, automatically announcing and serving each of its tracks.
+	pub fn publish_broadcast(&mut self, broadcast: BroadcastConsumer) -> Result<(), Error> {
+		match &mut self.protocol {
+			Protocol::Transfork { publisher, .. } => publisher.publish_broadcast(broadcast),
+			Protocol::Ietf { publisher, .. } => publisher.publish_broadcast(broadcast),
+		}
 	}
 
 	/// Optionally announce the provided tracks.
@@ -155,24 +358,72 @@ impl Session {
 	/// This is advanced functionality if you wish to perform dynamic track generation in conjunction with [Self::route].
 	/// [AnnouncedConsumer] will automatically unannounce if the [crate::AnnouncedProducer] is dropped.
 	pub fn announce(&mut self, announced: AnnouncedConsumer) {
-		self.publisher.announce(announced);
+		match &mut self.protocol {
+			Protocol::Transfork { publisher, .. } => publisher.announce(announced),
+			Protocol::Ietf { publisher, .. } => publisher.announce(announced),
+		}
 	}
 
 	/// Optionally route unknown paths.
 	///
 	/// This is advanced functionality if you wish to perform dynamic track generation in conjunction with [Self::announce].
 	pub fn route(&mut self, router: RouterConsumer) {
-		self.publisher.route(router);
+		match &mut self.protocol {
+			Protocol::Transfork { publisher, .. } => publisher.route(router),
+			Protocol::Ietf { publisher, .. } => publisher.route(router),
+		}
 	}
 
 	/// Subscribe to a track and start receiving data over the network.
 	pub fn subscribe(&self, track: Track) -> TrackConsumer {
-		self.subscriber.subscribe(track)
+		match &self.protocol {
+			Protocol::Transfork { subscriber, .. } => subscriber.subscribe(track),
+			Protocol::Ietf { subscriber, .. } => subscriber.subscribe(track),
+		}
+	}
+
+	/// Consume a broadcast published by the remote, subscribing to tracks relative to its path.
+	pub fn broadcast(&self, broadcast: Broadcast) -> BroadcastConsumer {
+		BroadcastConsumer::remote(broadcast, self.clone())
 	}
 
 	/// Discover any tracks published by the remote matching a (wildcard) filter.
 	pub fn announced(&self, filter: Filter) -> AnnouncedConsumer {
-		self.subscriber.announced(filter)
+		match &self.protocol {
+			Protocol::Transfork { subscriber, .. } => subscriber.announced(filter),
+			Protocol::Ietf { subscriber, .. } => subscriber.announced(filter),
+		}
+	}
+
+	/// Discover any tracks matching a filter, mirroring them into the provided producer.
+	///
+	/// Reuse the same producer after a reconnect to resume from its [AnnouncedProducer::cursor] instead of a full replay.
+	/// Any paths that are no longer announced are removed once the new stream is live.
+	pub fn announced_into(&self, filter: Filter, producer: AnnouncedProducer) -> AnnouncedConsumer {
+		match &self.protocol {
+			Protocol::Transfork { subscriber, .. } => subscriber.announced_into(filter, producer),
+			Protocol::Ietf { subscriber, .. } => subscriber.announced_into(filter, producer),
+		}
+	}
+
+	/// Report any [Event]s for this session to the observer, ex. to write a qlog.
+	///
+	/// Events that occurred before this call are not reported.
+	/// Returns false if an observer was already installed.
+	pub fn observe(&self, observer: Arc<dyn Observer>) -> bool {
+		self.events.observe(observer)
+	}
+
+	/// The token presented by the client during the handshake, see [Self::connect_token].
+	///
+	/// This is only available to the server, and the application is responsible for verifying it.
+	pub fn authorization(&self) -> Option<&str> {
+		self.authorization.as_deref()
+	}
+
+	/// Returns true if the session is speaking the IETF moq-transport draft.
+	pub fn is_ietf(&self) -> bool {
+		matches!(self.protocol, Protocol::Ietf { .. })
 	}
 
 	/// Close the underlying WebTransport session.
@@ -193,3 +444,176 @@ impl PartialEq for Session {
 }
 
 impl Eq for Session {}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{Broadcast, Filter};
+
+	use futures::executor::LocalPool;
+	use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+
+	// Returns a server and client endpoint using the async-std runtime.
+	fn endpoints() -> (quinn::Endpoint, quinn::Endpoint) {
+		let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+		let chain = vec![cert.cert.der().clone()];
+		let key = rustls::pki_types::PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der());
+
+		let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+		let mut server = rustls::ServerConfig::builder_with_provider(provider.clone())
+			.with_protocol_versions(&[&rustls::version::TLS13])
+			.unwrap()
+			.with_no_client_auth()
+			.with_single_cert(chain.clone(), key.into())
+			.unwrap();
+		server.alpn_protocols = vec![crate::ALPN.to_vec()];
+		let server_config = quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(server).unwrap()));
+
+		let mut roots = rustls::RootCertStore::empty();
+		roots.add(chain[0].clone()).unwrap();
+
+		let mut client = rustls::ClientConfig::builder_with_provider(provider)
+			.with_protocol_versions(&[&rustls::version::TLS13])
+			.unwrap()
+			.with_root_certificates(roots)
+			.with_no_client_auth();
+		client.alpn_protocols = vec![crate::ALPN.to_vec()];
+		let client_config = quinn::ClientConfig::new(Arc::new(QuicClientConfig::try_from(client).unwrap()));
+
+		let runtime = Arc::new(quinn::AsyncStdRuntime);
+
+		let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+		let server = quinn::Endpoint::new(Default::default(), Some(server_config), socket, runtime.clone()).unwrap();
+
+		let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+		let mut client = quinn::Endpoint::new(Default::default(), None, socket, runtime).unwrap();
+		client.set_default_client_config(client_config);
+
+		(server, client)
+	}
+
+	// Drive a session on a single-threaded executor without a tokio runtime.
+	// Only quinn's I/O runs on async-std; every MoQ task runs on the executor.
+	#[test]
+	fn local_executor() {
+		let mut pool = LocalPool::new();
+		moq_async::set_spawner(pool.spawner());
+
+		let (server, client) = endpoints();
+		let addr = server.local_addr().unwrap();
+
+		pool.run_until(async move {
+			let (server, client) = futures::join!(async { server.accept().await.unwrap().await.unwrap() }, async {
+				client.connect(addr, "localhost").unwrap().await.unwrap()
+			},);
+
+			let (server, client) = futures::join!(
+				Session::accept(web_transport::quinn::Session::from(server)),
+				Session::connect(web_transport::quinn::Session::from(client)),
+			);
+			let mut server = server.unwrap();
+			let client = client.unwrap();
+
+			let (mut broadcast, consumer) = Broadcast::new("demo").produce();
+			let mut track = broadcast.create_track(Track::new("clock"));
+			track.append_group().write_frame(bytes::Bytes::from_static(b"hello"));
+			server.publish_broadcast(consumer).unwrap();
+
+			let mut track = client
+				.broadcast(Broadcast::new("demo"))
+				.subscribe(Track::new("clock"))
+				.unwrap();
+			let mut group = track.next_group().await.unwrap().unwrap();
+			assert_eq!(group.read_frame().await.unwrap().unwrap(), "hello");
+		});
+
+		moq_async::reset_spawner();
+	}
+
+	// An older client is still served announcements, just without a cursor to resume from.
+	#[test]
+	fn older_version() {
+		let mut pool = LocalPool::new();
+		moq_async::set_spawner(pool.spawner());
+
+		let (server, client) = endpoints();
+		let addr = server.local_addr().unwrap();
+
+		pool.run_until(async move {
+			let (server, client) = futures::join!(async { server.accept().await.unwrap().await.unwrap() }, async {
+				client.connect(addr, "localhost").unwrap().await.unwrap()
+			},);
+
+			let (server, client) = futures::join!(
+				Session::accept(web_transport::quinn::Session::from(server)),
+				Session::connect_version(
+					web_transport::quinn::Session::from(client),
+					message::Version::FORK_04,
+					DecodeLimits::default(),
+				),
+			);
+			let mut server = server.unwrap();
+			let client = client.unwrap();
+
+			let mut announced = AnnouncedProducer::new();
+			announced.live();
+			announced.announce("demo/clock");
+			server.announce(announced.subscribe(Filter::Any));
+
+			let mut consumer = client.announced(Filter::Any);
+			consumer.next().await.unwrap().assert_active("demo/clock");
+			consumer.next().await.unwrap().assert_live();
+			assert_eq!(consumer.cursor(), None);
+		});
+
+		moq_async::reset_spawner();
+	}
+
+	// A group with more frames than the subscriber's limit is cut short.
+	#[test]
+	fn group_frames_limit() {
+		let mut pool = LocalPool::new();
+		moq_async::set_spawner(pool.spawner());
+
+		let (server, client) = endpoints();
+		let addr = server.local_addr().unwrap();
+
+		pool.run_until(async move {
+			let (server, client) = futures::join!(async { server.accept().await.unwrap().await.unwrap() }, async {
+				client.connect(addr, "localhost").unwrap().await.unwrap()
+			},);
+
+			let limits = DecodeLimits {
+				max_group_frames: 2,
+				..Default::default()
+			};
+
+			let (server, client) = futures::join!(
+				Session::accept(web_transport::quinn::Session::from(server)),
+				Session::connect_with(web_transport::quinn::Session::from(client), limits),
+			);
+			let mut server = server.unwrap();
+			let client = client.unwrap();
+
+			let (mut broadcast, consumer) = Broadcast::new("demo").produce();
+			let mut track = broadcast.create_track(Track::new("clock"));
+			let mut group = track.append_group();
+			for frame in ["a", "b", "c"] {
+				group.write_frame(bytes::Bytes::from_static(frame.as_bytes()));
+			}
+			server.publish_broadcast(consumer).unwrap();
+
+			let mut track = client
+				.broadcast(Broadcast::new("demo"))
+				.subscribe(Track::new("clock"))
+				.unwrap();
+			let mut group = track.next_group().await.unwrap().unwrap();
+			assert_eq!(group.read_frame().await.unwrap().unwrap(), "a");
+			assert_eq!(group.read_frame().await.unwrap().unwrap(), "b");
+			assert!(!matches!(group.read_frame().await, Ok(Some(_))));
+		});
+
+		moq_async::reset_spawner();
+	}
+}
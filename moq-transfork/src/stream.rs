@@ -1,6 +1,6 @@
-use crate::{Error, Reader, Writer};
+use crate::{capture, Error, Reader, Writer};
 use moq_async::Close;
-use moq_proto::message;
+use moq_proto::{coding::DecodeLimits, message};
 
 pub(super) struct Stream {
 	pub writer: Writer,
@@ -8,23 +8,38 @@ pub(super) struct Stream {
 }
 
 impl Stream {
-	pub async fn open(session: &mut web_transport::Session, typ: message::ControlType) -> Result<Self, Error> {
+	pub fn new(send: web_transport::SendStream, recv: web_transport::RecvStream, limits: DecodeLimits) -> Self {
+		// Both halves share an ID so they can be correlated in captures.
+		let id = capture::next_stream();
+
+		Self {
+			writer: Writer::with_id(send, id),
+			reader: Reader::with_id(recv, limits, id),
+		}
+	}
+
+	/// The identifier shared by both halves, used for captures and events.
+	pub fn id(&self) -> u64 {
+		self.writer.id()
+	}
+
+	pub async fn open(
+		session: &mut web_transport::Session,
+		typ: message::ControlType,
+		limits: DecodeLimits,
+	) -> Result<Self, Error> {
 		let (send, recv) = session.open_bi().await?;
 
-		let mut writer = Writer::new(send);
-		let reader = Reader::new(recv);
-		writer.encode(&typ).await?;
+		let mut stream = Self::new(send, recv, limits);
+		stream.writer.encode(&typ).await?;
 
-		Ok(Stream { writer, reader })
+		Ok(stream)
 	}
 
-	pub async fn accept(session: &mut web_transport::Session) -> Result<Self, Error> {
+	pub async fn accept(session: &mut web_transport::Session, limits: DecodeLimits) -> Result<Self, Error> {
 		let (send, recv) = session.accept_bi().await?;
 
-		let writer = Writer::new(send);
-		let reader = Reader::new(recv);
-
-		Ok(Stream { writer, reader })
+		Ok(Self::new(send, recv, limits))
 	}
 }
 
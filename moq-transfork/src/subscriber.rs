@@ -1,41 +1,76 @@
 use std::{
-	collections::{hash_map, HashMap},
-	sync::{atomic, Arc},
+	collections::{hash_map, HashMap, HashSet},
+	sync::{atomic, Arc, Weak},
 };
 
-use crate::{AnnouncedConsumer, AnnouncedProducer, Error, Filter, Reader, Stream, Track, TrackConsumer, TrackProducer};
+use crate::{
+	AnnouncedConsumer, AnnouncedProducer, Error, Event, Events, Filter, Reader, Stream, StreamKind, Track,
+	TrackConsumer, TrackProducer,
+};
 
+use crate::trace;
 use moq_async::{spawn, Lock, OrClose};
-use moq_proto::message;
+use moq_proto::{
+	coding::{DecodeError, DecodeLimits},
+	message,
+};
+use tracing::Instrument;
 
 #[derive(Clone)]
 pub(super) struct Subscriber {
 	session: web_transport::Session,
 
-	tracks: Lock<HashMap<String, TrackProducer>>,
+	tracks: Lock<HashMap<String, TrackShared>>,
 	subscribes: Lock<HashMap<u64, TrackProducer>>,
 	next_id: Arc<atomic::AtomicU64>,
+
+	// True if each subscribe is followed by a trace context.
+	trace: bool,
+
+	// True if announce streams can resume from a cursor.
+	cursor: bool,
+
+	limits: DecodeLimits,
+	events: Events,
 }
 
 impl Subscriber {
-	pub fn new(session: web_transport::Session) -> Self {
+	pub fn new(
+		session: web_transport::Session,
+		trace: bool,
+		cursor: bool,
+		limits: DecodeLimits,
+		events: Events,
+	) -> Self {
 		Self {
 			session,
 
 			tracks: Default::default(),
 			subscribes: Default::default(),
 			next_id: Default::default(),
+			trace,
+			cursor,
+			limits,
+			events,
 		}
 	}
 
 	/// Discover any tracks matching a filter.
 	pub fn announced(&self, filter: Filter) -> AnnouncedConsumer {
-		let producer = AnnouncedProducer::default();
+		self.announced_into(filter, AnnouncedProducer::default())
+	}
+
+	/// Discover any tracks matching a filter, mirroring them into the provided producer.
+	pub fn announced_into(&self, filter: Filter, producer: AnnouncedProducer) -> AnnouncedConsumer {
 		let consumer = producer.subscribe(filter.clone());
 
 		let mut session = self.session.clone();
+		let limits = self.limits;
+		let events = self.events.clone();
+		let resume = self.cursor;
+
 		spawn(async move {
-			let mut stream = match Stream::open(&mut session, message::ControlType::Announce).await {
+			let mut stream = match Stream::open(&mut session, message::ControlType::Announce, limits).await {
 				Ok(stream) => stream,
 				Err(err) => {
 					tracing::warn!(?err, "failed to open announce stream");
@@ -43,31 +78,61 @@ impl Subscriber {
 				}
 			};
 
-			if let Err(err) = Self::run_announce(&mut stream, filter, producer)
+			events.emit(Event::StreamOpened {
+				stream: stream.id(),
+				kind: StreamKind::Announce,
+				local: true,
+			});
+
+			if let Err(err) = Self::run_announce(&mut stream, filter, producer, resume)
 				.await
 				.or_close(&mut stream)
 			{
 				tracing::warn!(?err, "announced error");
 			}
+
+			events.emit(Event::StreamClosed { stream: stream.id() });
 		});
 
 		consumer
 	}
 
-	async fn run_announce(stream: &mut Stream, filter: Filter, mut announced: AnnouncedProducer) -> Result<(), Error> {
-		stream
-			.writer
-			.encode(&message::AnnouncePlease { filter: filter.clone() })
-			.await?;
+	async fn run_announce(
+		stream: &mut Stream,
+		filter: Filter,
+		mut announced: AnnouncedProducer,
+		resume: bool,
+	) -> Result<(), Error> {
+		// Try to resume from where the previous announce stream left off, if supported.
+		let cursor = announced.cursor().filter(|_| resume);
+
+		match resume {
+			true => {
+				let request = message::AnnouncePlease {
+					filter: filter.clone(),
+					cursor: cursor.clone(),
+				};
+				stream.writer.encode(&request).await?
+			}
+			false => stream.writer.encode(&filter).await?,
+		}
 
-		tracing::debug!(?filter, "waiting for announcements");
+		tracing::debug!(?filter, ?cursor, "waiting for announcements");
+
+		let mut mirror = AnnounceMirror {
+			// Any existing paths are stale unless they're replayed.
+			replay: (!announced.is_empty()).then(HashSet::new),
+			expected: cursor,
+			resumed: false,
+			filter,
+		};
 
 		loop {
 			tokio::select! {
 				res = stream.reader.decode_maybe::<message::Announce>() => {
 					match res? {
 						// Handle the announce
-						Some(announce) => Self::recv_announce(announce, &filter, &mut announced)?,
+						Some(announce) => mirror.recv(announce, &mut announced)?,
 						// Stop if the stream has been closed
 						None => return Ok(()),
 					}
@@ -78,69 +143,69 @@ impl Subscriber {
 		}
 	}
 
-	fn recv_announce(
-		announce: message::Announce,
-		filter: &Filter,
-		announced: &mut AnnouncedProducer,
-	) -> Result<(), Error> {
-		match announce {
-			message::Announce::Active(capture) => {
-				let path = filter.reconstruct(&capture);
-				if !announced.announce(path) {
-					return Err(Error::Duplicate);
-				}
-			}
-			message::Announce::Ended(capture) => {
-				let path = filter.reconstruct(&capture);
-				if !announced.unannounce(&path) {
-					return Err(Error::NotFound);
-				}
-			}
-			message::Announce::Live => {
-				announced.live();
-			}
-		};
-
-		Ok(())
-	}
-
 	/// Subscribe to a given track.
+	///
+	/// Subscriptions to the same path are deduplicated, using the highest priority of any active consumer.
 	pub fn subscribe(&self, track: Track) -> TrackConsumer {
 		let path = track.path.clone();
-		let (writer, reader) = track.clone().produce();
 
 		// Check if we can deduplicate this subscription
-		match self.tracks.lock().entry(path.clone()) {
-			hash_map::Entry::Occupied(entry) => return entry.get().subscribe(),
-			hash_map::Entry::Vacant(entry) => entry.insert(writer.clone()),
+		let shared = match self.tracks.lock().entry(path.clone()) {
+			hash_map::Entry::Occupied(entry) => return entry.get().subscribe(track),
+			hash_map::Entry::Vacant(entry) => entry.insert(TrackShared::new(track.clone())).clone(),
 		};
 
+		let reader = shared.subscribe(track);
+
 		let mut this = self.clone();
 		let id = self.next_id.fetch_add(1, atomic::Ordering::Relaxed);
 
-		spawn(async move {
-			if let Ok(mut stream) = Stream::open(&mut this.session, message::ControlType::Subscribe).await {
-				if let Err(err) = this.run_subscribe(id, writer, &mut stream).await.or_close(&mut stream) {
-					tracing::warn!(?err, "subscribe error");
+		// Used to surface any error to the consumers, rather than ending the track cleanly.
+		let producer = shared.producer.clone();
+
+		// Run in the current span so the subscription can be traced back to the caller.
+		spawn(
+			async move {
+				match Stream::open(&mut this.session, message::ControlType::Subscribe, this.limits).await {
+					Ok(mut stream) => {
+						this.events.emit(Event::StreamOpened {
+							stream: stream.id(),
+							kind: StreamKind::Subscribe,
+							local: true,
+						});
+
+						if let Err(err) = this.run_subscribe(id, shared, &mut stream).await.or_close(&mut stream) {
+							tracing::warn!(?err, "subscribe error");
+							producer.close(err);
+						}
+
+						this.events.emit(Event::StreamClosed { stream: stream.id() });
+					}
+					Err(err) => producer.close(err),
 				}
-			}
 
-			this.subscribes.lock().remove(&id);
-			this.tracks.lock().remove(&path);
-		});
+				this.subscribes.lock().remove(&id);
+				this.tracks.lock().remove(&path);
+			}
+			.in_current_span(),
+		);
 
 		reader
 	}
 
-	#[tracing::instrument("subscribe", skip_all, fields(?id, track = ?track.path))]
-	async fn run_subscribe(&mut self, id: u64, track: TrackProducer, stream: &mut Stream) -> Result<(), Error> {
+	#[tracing::instrument("subscribe", skip_all, fields(?id, track = ?shared.producer.path))]
+	async fn run_subscribe(&mut self, id: u64, shared: TrackShared, stream: &mut Stream) -> Result<(), Error> {
+		let track = shared.producer.clone();
 		self.subscribes.lock().insert(id, track.clone());
 
+		let mut requested = shared.requested().unwrap_or_else(|| track.info.as_ref().clone());
+		let mut demand = track.demand();
+
 		let request = message::Subscribe {
 			id,
-			path: track.path.clone(),
-			priority: track.priority,
-			order: track.order,
+			path: track.path.as_str().into(),
+			priority: requested.priority,
+			order: requested.order,
 
 			// TODO
 			start: None,
@@ -149,6 +214,10 @@ impl Subscriber {
 
 		stream.writer.encode(&request).await?;
 
+		if self.trace {
+			stream.writer.encode(&trace::current()).await?;
+		}
+
 		// TODO use the response to correctly populate the track info
 		let info: message::Info = stream.reader.decode().await?;
 
@@ -160,12 +229,42 @@ impl Subscriber {
 					match res? {
 						Some(drop) => {
 							tracing::info!(?drop, "dropped");
+
+							self.events.emit(Event::GroupDropped {
+								subscribe: id,
+								sequence: drop.sequence,
+								count: drop.count,
+								code: drop.code,
+								local: false,
+							});
+
 							// TODO expose updates to application
 							// TODO use to detect gaps
 						},
 						None => break,
 					}
 				}
+				// Update the subscription when a consumer joins or leaves with a different priority.
+				Ok(()) = demand.changed() => {
+					let latest = match shared.requested() {
+						Some(latest) if latest != requested => latest,
+						_ => continue,
+					};
+
+					tracing::debug!(priority = latest.priority, order = ?latest.order, "updating");
+
+					let update = message::SubscribeUpdate {
+						priority: latest.priority,
+						order: latest.order,
+
+						// TODO
+						start: None,
+						end: None,
+					};
+
+					stream.writer.encode(&update).await?;
+					requested = latest;
+				}
 				// Close when there are no more subscribers
 				_ = track.unused() => break
 			};
@@ -177,8 +276,21 @@ impl Subscriber {
 	}
 
 	pub async fn recv_group(&mut self, stream: &mut Reader) -> Result<(), Error> {
-		let group = stream.decode().await?;
-		self.recv_group_inner(stream, group).await.or_close(stream)
+		let group: message::Group = stream.decode().await?;
+
+		self.events.emit(Event::StreamOpened {
+			stream: stream.id(),
+			kind: StreamKind::Group {
+				subscribe: group.subscribe,
+				sequence: group.sequence,
+			},
+			local: false,
+		});
+
+		let res = self.recv_group_inner(stream, group).await.or_close(stream);
+		self.events.emit(Event::StreamClosed { stream: stream.id() });
+
+		res
 	}
 
 	#[tracing::instrument("group", skip_all, err, fields(subscribe = ?group.subscribe, group = group.sequence))]
@@ -191,6 +303,10 @@ impl Subscriber {
 		};
 
 		while let Some(frame) = stream.decode_maybe::<message::Frame>().await? {
+			if group.frame_count() >= self.limits.max_group_frames {
+				return Err(DecodeError::TooManyFrames.into());
+			}
+
 			let mut frame = group.create_frame(frame.size);
 			let mut remain = frame.size;
 
@@ -207,3 +323,134 @@ impl Subscriber {
 		Ok(())
 	}
 }
+
+// A deduplicated subscription, shared by every local consumer.
+#[derive(Clone)]
+struct TrackShared {
+	producer: TrackProducer,
+
+	// The track requested by each consumer, which is dropped along with the consumer (and any clones).
+	requests: Lock<Vec<Weak<Track>>>,
+}
+
+impl TrackShared {
+	fn new(track: Track) -> Self {
+		let (producer, _) = track.produce();
+
+		Self {
+			producer,
+			requests: Default::default(),
+		}
+	}
+
+	fn subscribe(&self, track: Track) -> TrackConsumer {
+		let info = Arc::new(track);
+		self.requests.lock().push(Arc::downgrade(&info));
+		self.producer.subscribe_as(info)
+	}
+
+	// Returns the aggregate of every active request: the highest priority and its order.
+	fn requested(&self) -> Option<Track> {
+		let mut requests = self.requests.lock();
+		requests.retain(|request| request.strong_count() > 0);
+
+		requests
+			.iter()
+			.filter_map(Weak::upgrade)
+			.reduce(|best, request| match request.priority > best.priority {
+				true => request,
+				false => best,
+			})
+			.map(|best| best.as_ref().clone())
+	}
+}
+
+// Mirrors an announce stream into a producer.
+struct AnnounceMirror {
+	filter: Filter,
+
+	// The cursor we requested, which the publisher echoes first if it can resume.
+	expected: Option<message::AnnounceCursor>,
+
+	// True if we're only receiving changes, so duplicates are expected.
+	resumed: bool,
+
+	// The paths replayed so far, used to remove stale paths when live.
+	replay: Option<HashSet<String>>,
+}
+
+impl AnnounceMirror {
+	fn recv(&mut self, announce: message::Announce, announced: &mut AnnouncedProducer) -> Result<(), Error> {
+		if let Some(expected) = self.expected.take() {
+			if matches!(&announce, message::Announce::Cursor(cursor) if *cursor == expected) {
+				tracing::debug!(cursor = ?expected, "resumed announcements");
+				self.resumed = true;
+				self.replay = None;
+				return Ok(());
+			}
+		}
+
+		match announce {
+			message::Announce::Active(capture) => {
+				let path = self.filter.reconstruct(&capture);
+				let added = announced.announce(&path);
+
+				if let Some(replay) = &mut self.replay {
+					replay.insert(path);
+				} else if !added && !self.resumed {
+					return Err(Error::Duplicate);
+				}
+			}
+			message::Announce::Ended(capture) => {
+				let path = self.filter.reconstruct(&capture);
+				let removed = announced.unannounce(&path);
+
+				if let Some(replay) = &mut self.replay {
+					replay.remove(&path);
+				}
+
+				if !removed && !self.resumed {
+					return Err(Error::NotFound);
+				}
+			}
+			message::Announce::Live => {
+				if let Some(replay) = self.replay.take() {
+					announced.retain(&self.filter, |path| replay.contains(path));
+				}
+
+				announced.live();
+			}
+			message::Announce::Cursor(cursor) => {
+				announced.set_cursor(cursor);
+			}
+		};
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn requested() {
+		let shared = TrackShared::new(Track::new("a"));
+
+		let low = shared.subscribe(Track::build().path("a").priority(1).into());
+		assert_eq!(shared.requested().unwrap().priority, 1);
+
+		let high = shared.subscribe(Track::build().path("a").priority(5).into());
+		let clone = high.clone();
+		assert_eq!(shared.requested().unwrap().priority, 5);
+
+		// The request remains until every clone is dropped.
+		drop(high);
+		assert_eq!(shared.requested().unwrap().priority, 5);
+		drop(clone);
+		assert_eq!(shared.requested().unwrap().priority, 1);
+
+		drop(low);
+		assert_eq!(shared.requested(), None);
+	}
+}
@@ -0,0 +1,116 @@
+//! Propagates W3C trace context across sessions, linking the spans of each side.
+//!
+//! This requires the `otel` feature and a [tracing_opentelemetry] layer, otherwise the context is always empty.
+use moq_proto::message::TraceContext;
+
+/// True if we're able to propagate trace context.
+pub(crate) const ENABLED: bool = cfg!(feature = "otel");
+
+/// Returns the trace context of the current span, or an empty context if there's no active trace.
+#[cfg(feature = "otel")]
+pub(crate) fn current() -> TraceContext {
+	use opentelemetry::trace::TraceContextExt;
+	use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+	let cx = tracing::Span::current().context();
+	let span = cx.span();
+	let span = span.span_context();
+
+	if !span.is_valid() {
+		return TraceContext::default();
+	}
+
+	TraceContext {
+		parent: format!(
+			"00-{}-{}-{:02x}",
+			span.trace_id(),
+			span.span_id(),
+			span.trace_flags().to_u8()
+		),
+		state: span.trace_state().header(),
+	}
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn current() -> TraceContext {
+	TraceContext::default()
+}
+
+/// Make the remote span the parent of the given span.
+#[cfg(feature = "otel")]
+pub(crate) fn link(span: &tracing::Span, trace: &TraceContext) {
+	use opentelemetry::trace::TraceContextExt;
+	use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+	if trace.is_empty() {
+		return;
+	}
+
+	match parse(trace) {
+		Some(remote) => span.set_parent(opentelemetry::Context::new().with_remote_span_context(remote)),
+		None => tracing::debug!(?trace, "invalid trace context"),
+	}
+}
+
+#[cfg(not(feature = "otel"))]
+pub(crate) fn link(_span: &tracing::Span, _trace: &TraceContext) {}
+
+#[cfg(feature = "otel")]
+fn parse(trace: &TraceContext) -> Option<opentelemetry::trace::SpanContext> {
+	use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+	use std::str::FromStr;
+
+	let mut parts = trace.parent.split('-');
+
+	// Only version 00 is defined, but future versions must start with the same fields.
+	let version = parts.next()?;
+	if version.len() != 2 || version == "ff" {
+		return None;
+	}
+
+	let trace_id = TraceId::from_hex(parts.next()?).ok()?;
+	let span_id = SpanId::from_hex(parts.next()?).ok()?;
+	let flags = u8::from_str_radix(parts.next()?, 16).ok()?;
+	let state = TraceState::from_str(&trace.state).unwrap_or_default();
+
+	let span = SpanContext::new(trace_id, span_id, TraceFlags::new(flags), true, state);
+	span.is_valid().then_some(span)
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod test {
+	use super::*;
+
+	use opentelemetry::trace::TracerProvider;
+	use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+	use tracing_subscriber::layer::SubscriberExt;
+
+	#[test]
+	fn linked() {
+		let exporter = InMemorySpanExporter::default();
+		let provider = SdkTracerProvider::builder()
+			.with_simple_exporter(exporter.clone())
+			.build();
+
+		let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+		let subscriber = tracing_subscriber::registry().with(layer);
+
+		tracing::subscriber::with_default(subscriber, || {
+			// The subscriber side of one session.
+			let trace = tracing::info_span!("subscribe").in_scope(current);
+			assert!(!trace.is_empty());
+
+			// The publisher side of another session.
+			let publishing = tracing::info_span!("publishing");
+			link(&publishing, &trace);
+			drop(publishing);
+		});
+
+		let spans = exporter.get_finished_spans().unwrap();
+		let subscribe = spans.iter().find(|span| span.name == "subscribe").unwrap();
+		let publishing = spans.iter().find(|span| span.name == "publishing").unwrap();
+
+		assert_eq!(publishing.span_context.trace_id(), subscribe.span_context.trace_id());
+		assert_eq!(publishing.parent_span_id, subscribe.span_context.span_id());
+	}
+}
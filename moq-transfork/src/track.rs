@@ -11,11 +11,14 @@
 //! A cloned [Consumer] will receive a copy of all new stream going forward (fanout).
 //!
 //! The track is closed with [Error] when all writers or readers are dropped.
+//!
+//! A [TrackProducer] can observe the number of active [TrackConsumer]s, local or remote, to only produce on demand.
 
+use futures::FutureExt;
 use tokio::sync::watch;
 
 use super::{Group, GroupConsumer, GroupProducer};
-use crate::Error;
+use crate::{ConsumerStream, Error};
 pub use moq_proto::message::GroupOrder;
 
 use std::{cmp::Ordering, ops, sync::Arc};
@@ -48,9 +51,10 @@ impl Track {
 	pub fn produce(self) -> (TrackProducer, TrackConsumer) {
 		let (send, recv) = watch::channel(TrackState::default());
 		let info = Arc::new(self);
+		let subscribers = watch::Sender::new(0);
 
-		let writer = TrackProducer::new(send, info.clone());
-		let reader = TrackConsumer::new(recv, info);
+		let writer = TrackProducer::new(send, subscribers.clone(), info.clone());
+		let reader = TrackConsumer::new(recv, TrackDemand::new(subscribers), info);
 
 		(writer, reader)
 	}
@@ -130,16 +134,59 @@ impl Default for TrackState {
 	}
 }
 
+// Counts the number of active consumers, decremented on drop.
+#[derive(Debug)]
+struct TrackDemand {
+	subscribers: watch::Sender<usize>,
+	active: bool,
+}
+
+impl TrackDemand {
+	fn new(subscribers: watch::Sender<usize>) -> Self {
+		subscribers.send_modify(|count| *count += 1);
+
+		Self {
+			subscribers,
+			active: true,
+		}
+	}
+
+	fn passive(&mut self) {
+		if self.active {
+			self.subscribers.send_modify(|count| *count -= 1);
+			self.active = false;
+		}
+	}
+}
+
+impl Clone for TrackDemand {
+	// A cloned consumer is a new subscriber, even if the original is passive.
+	fn clone(&self) -> Self {
+		Self::new(self.subscribers.clone())
+	}
+}
+
+impl Drop for TrackDemand {
+	fn drop(&mut self) {
+		self.passive();
+	}
+}
+
 /// A producer for a track, used to create new groups.
 #[derive(Clone, Debug)]
 pub struct TrackProducer {
 	pub info: Arc<Track>,
 	state: watch::Sender<TrackState>,
+	subscribers: watch::Sender<usize>,
 }
 
 impl TrackProducer {
-	fn new(state: watch::Sender<TrackState>, info: Arc<Track>) -> Self {
-		Self { info, state }
+	fn new(state: watch::Sender<TrackState>, subscribers: watch::Sender<usize>, info: Arc<Track>) -> Self {
+		Self {
+			info,
+			state,
+			subscribers,
+		}
 	}
 
 	/// Build a new group with the given sequence number.
@@ -185,13 +232,45 @@ impl TrackProducer {
 
 	/// Create a new consumer for the track.
 	pub fn subscribe(&self) -> TrackConsumer {
-		TrackConsumer::new(self.state.subscribe(), self.info.clone())
+		self.subscribe_as(self.info.clone())
+	}
+
+	/// Create a new consumer for the track with different info, such as the requested priority.
+	pub fn subscribe_as(&self, info: Arc<Track>) -> TrackConsumer {
+		let demand = TrackDemand::new(self.subscribers.clone());
+		TrackConsumer::new(self.state.subscribe(), demand, info)
+	}
+
+	/// Returns a channel that changes whenever a consumer is created or dropped.
+	pub fn demand(&self) -> watch::Receiver<usize> {
+		self.subscribers.subscribe()
 	}
 
 	/// Block until there are no active consumers.
 	pub async fn unused(&self) {
 		self.state.closed().await
 	}
+
+	/// Returns the number of active subscribers, both local and remote.
+	///
+	/// A consumer that was published to a session is not counted, only the remote subscriptions it serves.
+	pub fn subscribers(&self) -> usize {
+		*self.subscribers.borrow()
+	}
+
+	/// Block until there's at least one active subscriber.
+	///
+	/// This can be used to start production on demand.
+	pub async fn subscribed(&self) {
+		self.subscribers.subscribe().wait_for(|count| *count > 0).await.ok();
+	}
+
+	/// Block until there are no active subscribers.
+	///
+	/// Unlike [Self::unused], a consumer published to a session doesn't count, so this can be used to pause production.
+	pub async fn unsubscribed(&self) {
+		self.subscribers.subscribe().wait_for(|count| *count == 0).await.ok();
+	}
 }
 
 impl ops::Deref for TrackProducer {
@@ -207,18 +286,28 @@ impl ops::Deref for TrackProducer {
 pub struct TrackConsumer {
 	pub info: Arc<Track>,
 	state: watch::Receiver<TrackState>,
+	demand: TrackDemand,
 	prev: Option<u64>, // The previous sequence number
 }
 
 impl TrackConsumer {
-	fn new(state: watch::Receiver<TrackState>, info: Arc<Track>) -> Self {
+	fn new(state: watch::Receiver<TrackState>, demand: TrackDemand, info: Arc<Track>) -> Self {
 		Self {
 			state,
 			info,
+			demand,
 			prev: None,
 		}
 	}
 
+	/// Don't count this consumer as a subscriber, although any clones will be.
+	///
+	/// Used when a consumer is only held so it can be cloned on request.
+	pub(crate) fn passive(mut self) -> Self {
+		self.demand.passive();
+		self
+	}
+
 	pub fn get_group(&self, sequence: u64) -> Result<GroupConsumer, Error> {
 		let state = self.state.borrow();
 
@@ -270,6 +359,11 @@ impl TrackConsumer {
 			Err(_) => Ok(()),
 		}
 	}
+
+	/// Return a [futures::Stream] of groups, see [Self::next_group].
+	pub fn into_stream(self) -> TrackStream {
+		TrackStream::new(self, |track| track.next_group().boxed())
+	}
 }
 
 impl ops::Deref for TrackConsumer {
@@ -279,3 +373,60 @@ impl ops::Deref for TrackConsumer {
 		&self.info
 	}
 }
+
+/// A [futures::Stream] of groups in a track, ending after the first error.
+pub type TrackStream = ConsumerStream<GroupConsumer>;
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use futures::FutureExt;
+
+	#[test]
+	fn demand() {
+		let (producer, consumer) = Track::new("a").produce();
+		assert_eq!(producer.subscribers(), 1);
+		assert!(producer.subscribed().now_or_never().is_some());
+
+		let clone = consumer.clone();
+		assert_eq!(producer.subscribers(), 2);
+
+		// A passive consumer doesn't count, but its clones do.
+		let passive = consumer.passive();
+		assert_eq!(producer.subscribers(), 1);
+		drop(clone);
+		assert_eq!(producer.subscribers(), 0);
+		assert!(producer.unsubscribed().now_or_never().is_some());
+		assert!(producer.subscribed().now_or_never().is_none());
+
+		let remote = passive.clone();
+		assert_eq!(producer.subscribers(), 1);
+		drop(remote);
+		assert_eq!(producer.subscribers(), 0);
+		drop(passive);
+		assert_eq!(producer.subscribers(), 0);
+	}
+
+	#[tokio::test]
+	async fn stream() {
+		use futures::StreamExt;
+
+		let (mut producer, consumer) = Track::new("a").produce();
+		let mut stream = consumer.into_stream();
+
+		producer.create_group(0);
+		assert_eq!(stream.next().await.unwrap().unwrap().sequence, 0);
+		producer.create_group(1);
+		assert_eq!(stream.next().await.unwrap().unwrap().sequence, 1);
+
+		// The stream ends after returning the error.
+		producer.close(Error::NotFound);
+		assert!(matches!(stream.next().await, Some(Err(Error::NotFound))));
+		assert!(stream.next().await.is_none());
+
+		// Dropping the producer ends the stream cleanly.
+		let (producer, consumer) = Track::new("b").produce();
+		drop(producer);
+		assert!(consumer.into_stream().next().await.is_none());
+	}
+}
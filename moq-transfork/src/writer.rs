@@ -1,45 +1,89 @@
 use std::fmt;
 
-use crate::Error;
+use crate::{capture, Error};
 use moq_proto::{coding::*, message};
 
 use moq_async::Close;
 
+// The maximum number of bytes to queue before writing to the stream.
+const MAX_BUFFER: usize = 16 * 1024;
+
 pub(super) struct Writer {
 	stream: web_transport::SendStream,
 	buffer: bytes::BytesMut,
+
+	// Used to identify the stream in captures.
+	id: u64,
 }
 
 impl Writer {
 	pub fn new(stream: web_transport::SendStream) -> Self {
+		Self::with_id(stream, capture::next_stream())
+	}
+
+	pub(crate) fn with_id(stream: web_transport::SendStream, id: u64) -> Self {
 		Self {
 			stream,
 			buffer: Default::default(),
+			id,
 		}
 	}
 
+	/// The identifier used for captures and events.
+	pub fn id(&self) -> u64 {
+		self.id
+	}
+
 	pub async fn open(session: &mut web_transport::Session, typ: message::DataType) -> Result<Self, Error> {
 		let send = session.open_uni().await?;
 
+		// The type is sent along with the first flush.
 		let mut writer = Self::new(send);
-		writer.encode(&typ).await?;
+		writer.queue(&typ);
 
 		Ok(writer)
 	}
 
+	/// Encode a message and send it immediately, along with anything queued.
 	pub async fn encode<T: Encode + fmt::Debug>(&mut self, msg: &T) -> Result<(), Error> {
-		self.buffer.clear();
+		self.queue(msg);
+		self.flush().await
+	}
+
+	/// Encode a message to be sent on the next [Self::flush].
+	pub fn queue<T: Encode + fmt::Debug>(&mut self, msg: &T) {
+		let start = self.buffer.len();
 		msg.encode(&mut self.buffer);
 
-		while !self.buffer.is_empty() {
-			self.stream.write_buf(&mut self.buffer).await?;
+		capture::record::<T, _>(self.id, capture::Direction::Send, || {
+			bytes::Bytes::copy_from_slice(&self.buffer[start..])
+		});
+	}
+
+	/// Queue a chunk of payload to be sent on the next [Self::flush].
+	///
+	/// Small chunks are coalesced with any queued messages, while large chunks are written directly to avoid a copy.
+	pub async fn queue_chunk(&mut self, chunk: &[u8]) -> Result<(), Error> {
+		if self.buffer.len() + chunk.len() <= MAX_BUFFER {
+			self.buffer.extend_from_slice(chunk);
+			return Ok(());
 		}
 
+		self.flush().await?;
+		self.stream.write(chunk).await?; // convert the error type
+
 		Ok(())
 	}
 
-	pub async fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
-		self.stream.write(buf).await?; // convert the error type
+	/// Write anything queued to the stream.
+	pub async fn flush(&mut self) -> Result<(), Error> {
+		while !self.buffer.is_empty() {
+			self.stream.write_buf(&mut self.buffer).await?;
+		}
+
+		// Reuse the allocation for the next batch.
+		self.buffer.clear();
+
 		Ok(())
 	}
 